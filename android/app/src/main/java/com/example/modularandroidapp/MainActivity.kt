@@ -9,6 +9,7 @@ import com.example.modularandroidapp.databinding.ActivityMainBinding
 import kotlinx.coroutines.Dispatchers
 import kotlinx.coroutines.launch
 import kotlinx.coroutines.withContext
+import java.io.File
 
 class MainActivity : AppCompatActivity() {
     private lateinit var binding: ActivityMainBinding
@@ -67,7 +68,8 @@ class MainActivity : AppCompatActivity() {
     private fun initializeComponents() {
         libraryManager = LibraryManager(this)
         rustBridge = RustBridge()
-        notificationService = NotificationService(this)
+        notificationService = NotificationService(this, rustBridge)
+        RustBridge.notificationService = notificationService
 
         lifecycleScope.launch {
             try {
@@ -124,9 +126,10 @@ class MainActivity : AppCompatActivity() {
         lifecycleScope.launch {
             try {
                 updateStatus("Testing async callback...")
-                withContext(Dispatchers.IO) {
+                val taskId = withContext(Dispatchers.IO) {
                     rustBridge.testAsyncCallback()
                 }
+                updateStatus("Async task $taskId started")
             } catch (e: Exception) {
                 Log.e(TAG, "Error testing async callback", e)
                 updateStatus("Async test error: ${e.message}")
@@ -152,6 +155,7 @@ class MainActivity : AppCompatActivity() {
             try {
                 updateStatus("Initializing native activity...")
                 val success = withContext(Dispatchers.IO) {
+                    rustBridge.useNotificationStore(File(filesDir, "notifications.db"))
                     rustBridge.initializeNativeActivityWrapper()
                 }
                 if (success) {
@@ -219,6 +223,20 @@ class MainActivity : AppCompatActivity() {
         }
     }
 
+    override fun onResume() {
+        super.onResume()
+        if (::rustBridge.isInitialized) {
+            rustBridge.onResume()
+        }
+    }
+
+    override fun onPause() {
+        if (::rustBridge.isInitialized) {
+            rustBridge.onPause()
+        }
+        super.onPause()
+    }
+
     override fun onDestroy() {
         super.onDestroy()
         libraryManager.cleanup()
@@ -0,0 +1,41 @@
+package com.example.modularandroidapp
+
+import android.app.NotificationManager
+import android.content.BroadcastReceiver
+import android.content.Context
+import android.content.Intent
+import android.util.Log
+
+/**
+ * Hands a tapped notification button to the Rust handler registered for it,
+ * and tells Rust about notifications that were dismissed.
+ */
+class NotificationActionReceiver : BroadcastReceiver() {
+    companion object {
+        private const val TAG = "NotificationAction"
+        /** Intent action of the delete intent sent when a notification is swiped away. */
+        const val ACTION_DISMISSED = "com.example.modularandroidapp.NOTIFICATION_DISMISSED"
+        const val EXTRA_NOTIFICATION_ID = "notification_id"
+        const val EXTRA_ACTION_ID = "action_id"
+        const val EXTRA_DISMISS = "dismiss"
+    }
+
+    override fun onReceive(context: Context, intent: Intent) {
+        val notificationId = intent.getIntExtra(EXTRA_NOTIFICATION_ID, -1)
+        val bridge = RustBridge()
+        if (intent.action == ACTION_DISMISSED) {
+            bridge.notificationDismissed(notificationId)
+            return
+        }
+        val actionId = intent.getStringExtra(EXTRA_ACTION_ID) ?: return
+        if (!bridge.notificationActionTapped(notificationId, actionId)) {
+            Log.w(TAG, "Nothing handled $actionId of notification $notificationId")
+        }
+        if (intent.getBooleanExtra(EXTRA_DISMISS, true)) {
+            val manager = context.getSystemService(Context.NOTIFICATION_SERVICE) as NotificationManager
+            manager.cancel(notificationId)
+            // Cancelling it here does not send its delete intent
+            bridge.notificationDismissed(notificationId)
+        }
+    }
+}
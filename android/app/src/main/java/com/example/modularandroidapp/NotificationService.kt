@@ -1,66 +1,164 @@
 package com.example.modularandroidapp
 
 import android.app.NotificationChannel
+import android.app.NotificationChannelGroup
 import android.app.NotificationManager
 import android.app.PendingIntent
 import android.content.Context
 import android.content.Intent
+import android.net.Uri
 import android.os.Build
 import android.util.Log
 import androidx.core.app.NotificationCompat
 
-class NotificationService(private val context: Context) {
+/**
+ * Shows the notifications Rust posts. Channels are declared in Rust: the
+ * ones Rust already knows are created here on construction, later ones
+ * arrive through [createChannel] before the first notification using them.
+ */
+class NotificationService(private val context: Context, rustBridge: RustBridge) {
     companion object {
         private const val TAG = "NotificationService"
-        private const val CHANNEL_ID = "modular_app_channel"
-        private const val CHANNEL_NAME = "Modular App Notifications"
-        private const val CHANNEL_DESCRIPTION = "Notifications from the modular app"
+        /** Rust's `DEFAULT_CHANNEL`, used until Rust has declared any. */
+        private const val DEFAULT_CHANNEL_ID = "general"
+        /** Rust's `MAX_ACTIONS`. */
+        private const val MAX_ACTIONS = 3
     }
 
     private val notificationManager: NotificationManager = 
         context.getSystemService(Context.NOTIFICATION_SERVICE) as NotificationManager
 
     init {
-        createNotificationChannel()
+        createChannel(
+            NotificationChannelInfo(
+                id = DEFAULT_CHANNEL_ID,
+                name = "General",
+                description = "Notifications from the modular app"
+            )
+        )
+        // Channel events sent before this service was attached to RustBridge
+        // were dropped, so create everything declared so far
+        rustBridge.notificationChannels().forEach { createChannel(it) }
     }
 
-    private fun createNotificationChannel() {
-        if (Build.VERSION.SDK_INT >= Build.VERSION_CODES.O) {
-            val channel = NotificationChannel(
-                CHANNEL_ID,
-                CHANNEL_NAME,
-                NotificationManager.IMPORTANCE_DEFAULT
-            ).apply {
-                description = CHANNEL_DESCRIPTION
+    /** Creates or updates the Android channel for [channel]. */
+    fun createChannel(channel: NotificationChannelInfo) {
+        if (Build.VERSION.SDK_INT < Build.VERSION_CODES.O) {
+            return
+        }
+        channel.group?.let { group ->
+            notificationManager.createNotificationChannelGroup(NotificationChannelGroup(group, group))
+        }
+        val androidChannel = NotificationChannel(
+            channel.id,
+            channel.name,
+            importance(channel.importance)
+        ).apply {
+            description = channel.description
+            group = channel.group
+            enableVibration(channel.vibration)
+            if (!channel.sound) {
+                setSound(null, null)
             }
-            notificationManager.createNotificationChannel(channel)
         }
+        notificationManager.createNotificationChannel(androidChannel)
     }
 
-    fun showNotification(id: Int, title: String, message: String) {
-        Log.d(TAG, "Showing notification: $title - $message")
+    private fun importance(importance: String): Int = when (importance) {
+        "none" -> NotificationManager.IMPORTANCE_NONE
+        "min" -> NotificationManager.IMPORTANCE_MIN
+        "low" -> NotificationManager.IMPORTANCE_LOW
+        "high" -> NotificationManager.IMPORTANCE_HIGH
+        else -> NotificationManager.IMPORTANCE_DEFAULT
+    }
 
-        val intent = Intent(context, MainActivity::class.java).apply {
-            flags = Intent.FLAG_ACTIVITY_NEW_TASK or Intent.FLAG_ACTIVITY_CLEAR_TASK
-        }
+    /** Before Android O the priority decides what channels decide later. */
+    private fun priority(priority: String): Int = when (priority) {
+        "low" -> NotificationCompat.PRIORITY_LOW
+        "high" -> NotificationCompat.PRIORITY_HIGH
+        "urgent" -> NotificationCompat.PRIORITY_MAX
+        else -> NotificationCompat.PRIORITY_DEFAULT
+    }
+
+    /** Shows [notification], or updates the one already shown with its id. */
+    fun showNotification(notification: StoredNotification) {
+        Log.d(TAG, "Showing notification on ${notification.channel}: ${notification.title} - ${notification.message}")
+
+        val intent = notification.deepLink?.let { Intent(Intent.ACTION_VIEW, Uri.parse(it)) }
+            ?: Intent(context, MainActivity::class.java)
+        intent.flags = Intent.FLAG_ACTIVITY_NEW_TASK or Intent.FLAG_ACTIVITY_CLEAR_TASK
         
         val pendingIntent = PendingIntent.getActivity(
             context,
-            0,
+            notification.id,
             intent,
             PendingIntent.FLAG_UPDATE_CURRENT or PendingIntent.FLAG_IMMUTABLE
         )
 
-        val notification = NotificationCompat.Builder(context, CHANNEL_ID)
-            .setContentTitle(title)
-            .setContentText(message)
+        val builder = NotificationCompat.Builder(context, notification.channel)
+            .setContentTitle(notification.title)
+            .setContentText(notification.message)
             .setSmallIcon(android.R.drawable.ic_dialog_info)
             .setContentIntent(pendingIntent)
+            .setDeleteIntent(deleteIntent(notification.id))
             .setAutoCancel(true)
-            .setPriority(NotificationCompat.PRIORITY_DEFAULT)
-            .build()
+            .setPriority(priority(notification.priority))
+            // Progress and dedupe updates re-post the same id; only the first one alerts
+            .setOnlyAlertOnce(true)
+
+        notification.group?.let { group ->
+            builder.setGroup(group).setGroupSummary(notification.groupSummary)
+        }
+
+        when (val style = notification.style) {
+            is NotificationStyle.BigText -> builder.setStyle(NotificationCompat.BigTextStyle().bigText(style.text))
+            is NotificationStyle.Inbox -> builder.setStyle(
+                NotificationCompat.InboxStyle().also { inbox ->
+                    style.lines.forEach { inbox.addLine(it) }
+                    style.summary?.let { inbox.setSummaryText(it) }
+                }
+            )
+            null -> {}
+        }
+        notification.progress?.let { progress ->
+            builder.setProgress(progress.max.toInt(), progress.current.toInt(), progress.indeterminate)
+                .setOngoing(true)
+        }
+        notification.actions.forEachIndexed { index, action ->
+            builder.addAction(0, action.title, actionIntent(notification.id, index, action))
+        }
 
-        notificationManager.notify(id, notification)
+        notificationManager.notify(notification.id, builder.build())
+    }
+
+    private fun actionIntent(notificationId: Int, index: Int, action: NotificationActionInfo): PendingIntent {
+        val intent = Intent(context, NotificationActionReceiver::class.java).apply {
+            putExtra(NotificationActionReceiver.EXTRA_NOTIFICATION_ID, notificationId)
+            putExtra(NotificationActionReceiver.EXTRA_ACTION_ID, action.id)
+            putExtra(NotificationActionReceiver.EXTRA_DISMISS, action.dismiss)
+        }
+        return PendingIntent.getBroadcast(
+            context,
+            // Distinct per button so the extras of one do not replace another's
+            notificationId * MAX_ACTIONS + index,
+            intent,
+            PendingIntent.FLAG_UPDATE_CURRENT or PendingIntent.FLAG_IMMUTABLE
+        )
+    }
+
+    /** Reports the notification to Rust when the user swipes it away. */
+    private fun deleteIntent(notificationId: Int): PendingIntent {
+        val intent = Intent(context, NotificationActionReceiver::class.java).apply {
+            action = NotificationActionReceiver.ACTION_DISMISSED
+            putExtra(NotificationActionReceiver.EXTRA_NOTIFICATION_ID, notificationId)
+        }
+        // The action keeps it apart from the button intents with the same request code
+        return PendingIntent.getBroadcast(
+            context,
+            notificationId,
+            intent,
+            PendingIntent.FLAG_UPDATE_CURRENT or PendingIntent.FLAG_IMMUTABLE
+        )
     }
 
     fun cancelNotification(id: Int) {
@@ -70,4 +168,4 @@ class NotificationService(private val context: Context) {
     fun cancelAllNotifications() {
         notificationManager.cancelAll()
     }
-} 
\ No newline at end of file
+}
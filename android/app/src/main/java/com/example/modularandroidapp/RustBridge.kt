@@ -1,7 +1,585 @@
 package com.example.modularandroidapp
 
 import android.util.Log
+import org.json.JSONArray
+import org.json.JSONException
+import org.json.JSONObject
+import org.msgpack.core.MessagePack
 import java.io.File
+import java.io.IOException
+import java.text.SimpleDateFormat
+import java.util.Date
+import java.util.Locale
+import java.util.TimeZone
+
+/**
+ * One 3-axis reading from Rust. [sensor] is `accelerometer`, `gyroscope` or
+ * `magnetometer`; [accuracy] is `unreliable`, `low`, `medium` or `high`.
+ * [timestampNs] is monotonic, compare it with `SystemClock.elapsedRealtimeNanos()`.
+ */
+data class SensorSample(
+    val sensor: String,
+    val x: Float,
+    val y: Float,
+    val z: Float,
+    val accuracy: String,
+    val timestampNs: Long
+)
+
+/**
+ * Fused device orientation. Angles are radians in an earth frame with x
+ * towards magnetic north, y west and z up; [linearAcceleration] is the
+ * accelerometer reading minus gravity, in m/s² in the device frame.
+ */
+data class Orientation(
+    val algorithm: String,
+    val quaternion: FloatArray,
+    val roll: Float,
+    val pitch: Float,
+    val yaw: Float,
+    val linearAcceleration: FloatArray,
+    val timestampNs: Long
+) {
+    companion object {
+        fun fromJson(json: JSONObject): Orientation {
+            val q = json.getJSONObject("quaternion")
+            val euler = json.getJSONObject("euler")
+            val linear = json.getJSONArray("linear_acceleration")
+            return Orientation(
+                algorithm = json.getString("algorithm"),
+                quaternion = floatArrayOf(
+                    q.getDouble("w").toFloat(),
+                    q.getDouble("x").toFloat(),
+                    q.getDouble("y").toFloat(),
+                    q.getDouble("z").toFloat()
+                ),
+                roll = euler.getDouble("roll").toFloat(),
+                pitch = euler.getDouble("pitch").toFloat(),
+                yaw = euler.getDouble("yaw").toFloat(),
+                linearAcceleration = FloatArray(3) { linear.getDouble(it).toFloat() },
+                timestampNs = json.getLong("timestamp_ns")
+            )
+        }
+    }
+}
+
+/**
+ * Passed to [RustBridge.setFusionConfig]. [algorithm] is `complementary`,
+ * `madgwick` or `mahony`; only the gains of the chosen algorithm apply.
+ */
+data class FusionConfig(
+    val algorithm: String = "madgwick",
+    val useMagnetometer: Boolean = true,
+    val alpha: Float = 0.98f,
+    val beta: Float = 0.1f,
+    val kp: Float = 1.0f,
+    val ki: Float = 0.0f,
+    val eventPeriodMs: Long = 100
+) {
+    fun toJson(): String = JSONObject()
+        .put("algorithm", algorithm)
+        .put("use_magnetometer", useMagnetometer)
+        .put("alpha", alpha.toDouble())
+        .put("beta", beta.toDouble())
+        .put("kp", kp.toDouble())
+        .put("ki", ki.toDouble())
+        .put("event_period_ms", eventPeriodMs)
+        .toString()
+}
+
+/** Token bucket of a channel: [burst] posts at once, then one every [refillMs]. */
+data class RateLimit(val burst: Int, val refillMs: Long) {
+    fun toJson(): JSONObject = JSONObject().put("burst", burst).put("refill_ms", refillMs)
+
+    companion object {
+        fun fromJson(json: JSONObject) = RateLimit(json.getInt("burst"), json.getLong("refill_ms"))
+    }
+}
+
+/**
+ * A notification channel declared in Rust, created on Android by
+ * [NotificationService.createChannel]. [importance] is `none`, `min`, `low`,
+ * `default` or `high`; [group] is the id of a channel group. Rust drops
+ * posts beyond [rateLimit], if set.
+ */
+data class NotificationChannelInfo(
+    val id: String,
+    val name: String,
+    val description: String = "",
+    val importance: String = "default",
+    val sound: Boolean = true,
+    val vibration: Boolean = true,
+    val group: String? = null,
+    val rateLimit: RateLimit? = null
+) {
+    fun toJson(): String = JSONObject()
+        .put("id", id)
+        .put("name", name)
+        .put("description", description)
+        .put("importance", importance)
+        .put("sound", sound)
+        .put("vibration", vibration)
+        .put("group", group ?: JSONObject.NULL)
+        .apply { rateLimit?.let { put("rate_limit", it.toJson()) } }
+        .toString()
+
+    companion object {
+        fun fromJson(json: JSONObject) = NotificationChannelInfo(
+            id = json.getString("id"),
+            name = json.getString("name"),
+            description = json.optString("description", ""),
+            importance = json.optString("importance", "default"),
+            sound = json.optBoolean("sound", true),
+            vibration = json.optBoolean("vibration", true),
+            group = if (json.isNull("group")) null else json.getString("group"),
+            rateLimit = json.optJSONObject("rate_limit")?.let { RateLimit.fromJson(it) }
+        )
+    }
+}
+
+/** A channel's notification counts since Rust started, see [RustBridge.notificationStats]. */
+data class NotificationStats(val posted: Long, val updated: Long, val coalesced: Long, val suppressed: Long) {
+    companion object {
+        fun fromJson(json: JSONObject) = NotificationStats(
+            posted = json.getLong("posted"),
+            updated = json.getLong("updated"),
+            coalesced = json.optLong("coalesced", 0),
+            suppressed = json.getLong("suppressed")
+        )
+    }
+}
+
+/** A button on a notification; tapping it runs the Rust handler registered as [id]. */
+data class NotificationActionInfo(val id: String, val title: String, val dismiss: Boolean = true) {
+    fun toJson(): JSONObject = JSONObject().put("id", id).put("title", title).put("dismiss", dismiss)
+
+    companion object {
+        fun fromJson(json: JSONObject) = NotificationActionInfo(
+            id = json.getString("id"),
+            title = json.getString("title"),
+            dismiss = json.optBoolean("dismiss", true)
+        )
+    }
+}
+
+/** A progress bar; [indeterminate] ignores [current] and [max]. */
+data class NotificationProgress(val current: Long = 0, val max: Long = 0, val indeterminate: Boolean = false) {
+    fun toJson(): JSONObject =
+        if (indeterminate) JSONObject().put("type", "indeterminate")
+        else JSONObject().put("type", "determinate").put("current", current).put("max", max)
+
+    companion object {
+        fun fromJson(json: JSONObject) =
+            if (json.getString("type") == "indeterminate") NotificationProgress(indeterminate = true)
+            else NotificationProgress(json.getLong("current"), json.getLong("max"))
+    }
+}
+
+/** How a notification expands. */
+sealed class NotificationStyle {
+    data class BigText(val text: String) : NotificationStyle()
+    data class Inbox(val lines: List<String>, val summary: String? = null) : NotificationStyle()
+
+    fun toJson(): JSONObject = when (this) {
+        is BigText -> JSONObject().put("type", "big_text").put("text", text)
+        is Inbox -> JSONObject()
+            .put("type", "inbox")
+            .put("lines", JSONArray(lines))
+            .apply { summary?.let { put("summary", it) } }
+    }
+
+    companion object {
+        fun fromJson(json: JSONObject): NotificationStyle? = when (json.getString("type")) {
+            "big_text" -> BigText(json.getString("text"))
+            "inbox" -> {
+                val lines = json.getJSONArray("lines")
+                Inbox(
+                    List(lines.length()) { lines.getString(it) },
+                    if (json.isNull("summary")) null else json.getString("summary")
+                )
+            }
+            else -> null
+        }
+    }
+}
+
+/**
+ * A notification for [RustBridge.postNotification]. [deepLink] is opened when
+ * it is tapped. Posting again with the same [dedupeKey] updates the
+ * notification instead of adding one; notifications of a [group] are bundled
+ * under a summary.
+ */
+data class NotificationContent(
+    val title: String,
+    val message: String,
+    val channel: String = "general",
+    val priority: String = "normal",
+    val style: NotificationStyle? = null,
+    val progress: NotificationProgress? = null,
+    val actions: List<NotificationActionInfo> = emptyList(),
+    val deepLink: String? = null,
+    val dedupeKey: String? = null,
+    val group: String? = null
+) {
+    fun toJson(): String = JSONObject()
+        .put("title", title)
+        .put("message", message)
+        .put("channel", channel)
+        .put("priority", priority)
+        .put("actions", JSONArray().apply { actions.forEach { put(it.toJson()) } })
+        .apply {
+            style?.let { put("style", it.toJson()) }
+            progress?.let { put("progress", it.toJson()) }
+            deepLink?.let { put("deep_link", it) }
+            dedupeKey?.let { put("dedupe_key", it) }
+            group?.let { put("group", it) }
+        }
+        .toString()
+}
+
+/** A notification posted or kept by Rust, see [RustBridge.notificationHistory]. */
+data class StoredNotification(
+    val id: Int,
+    val channel: String,
+    val title: String,
+    val message: String,
+    val timestamp: String,
+    /** `low`, `normal`, `high` or `urgent`. */
+    val priority: String,
+    val style: NotificationStyle? = null,
+    val progress: NotificationProgress? = null,
+    val actions: List<NotificationActionInfo> = emptyList(),
+    val deepLink: String? = null,
+    val group: String? = null,
+    /** Shown on top of the other notifications of [group]. */
+    val groupSummary: Boolean = false
+) {
+    companion object {
+        fun fromJson(json: JSONObject): StoredNotification {
+            val actions = json.optJSONArray("actions") ?: JSONArray()
+            return StoredNotification(
+                id = json.getInt("id"),
+                channel = json.getString("channel"),
+                title = json.getString("title"),
+                message = json.getString("message"),
+                timestamp = json.getString("timestamp"),
+                priority = json.getString("priority"),
+                style = json.optJSONObject("style")?.let { NotificationStyle.fromJson(it) },
+                progress = json.optJSONObject("progress")?.let { NotificationProgress.fromJson(it) },
+                actions = List(actions.length()) { NotificationActionInfo.fromJson(actions.getJSONObject(it)) },
+                deepLink = if (json.isNull("deep_link")) null else json.getString("deep_link"),
+                group = if (json.isNull("group")) null else json.getString("group"),
+                groupSummary = json.optBoolean("group_summary", false)
+            )
+        }
+    }
+}
+
+/** How a [NotificationSchedule] repeats. Daily and weekly keep the UTC time of day. */
+sealed class Repeat {
+    data class Interval(val everyMs: Long) : Repeat()
+    object Daily : Repeat()
+    object Weekly : Repeat()
+    /** Six-field cron expression with seconds, evaluated in UTC, e.g. `0 30 8 * * Mon-Fri`. */
+    data class Cron(val expression: String) : Repeat()
+
+    fun toJson(): JSONObject = when (this) {
+        is Interval -> JSONObject().put("type", "interval").put("every_ms", everyMs)
+        Daily -> JSONObject().put("type", "daily")
+        Weekly -> JSONObject().put("type", "weekly")
+        is Cron -> JSONObject().put("type", "cron").put("expression", expression)
+    }
+}
+
+/**
+ * A notification posted by Rust later, see [RustBridge.scheduleNotification].
+ * Set exactly one of [atMillis] (epoch milliseconds) and [afterMs]. A
+ * repeating schedule stops after [repeatCount] posts in total or at
+ * [repeatUntilMillis], whichever comes first.
+ */
+data class NotificationSchedule(
+    val title: String,
+    val message: String,
+    val channel: String = "general",
+    val priority: String = "normal",
+    val atMillis: Long? = null,
+    val afterMs: Long? = null,
+    val repeat: Repeat? = null,
+    val repeatCount: Long? = null,
+    val repeatUntilMillis: Long? = null
+) {
+    fun toJson(): String {
+        val json = JSONObject()
+            .put("title", title)
+            .put("message", message)
+            .put("channel", channel)
+            .put("priority", priority)
+        atMillis?.let { json.put("at", rfc3339(it)) }
+        afterMs?.let { json.put("after_ms", it) }
+        repeat?.let { repeat ->
+            val rule = repeat.toJson()
+            repeatCount?.let { rule.put("count", it) }
+            repeatUntilMillis?.let { rule.put("until", rfc3339(it)) }
+            json.put("repeat", rule)
+        }
+        return json.toString()
+    }
+
+    private fun rfc3339(millis: Long): String =
+        SimpleDateFormat("yyyy-MM-dd'T'HH:mm:ss.SSS'Z'", Locale.US)
+            .apply { timeZone = TimeZone.getTimeZone("UTC") }
+            .format(Date(millis))
+}
+
+/** A pending schedule, see [RustBridge.scheduledNotifications]. [nextAt] is RFC 3339 in UTC. */
+data class ScheduledNotification(
+    val id: Long,
+    val channel: String,
+    val title: String,
+    val message: String,
+    val priority: String,
+    val nextAt: String,
+    /** `interval`, `daily`, `weekly`, `cron`, or null for a one-off. */
+    val repeat: String?,
+    val fired: Long
+) {
+    companion object {
+        fun fromJson(json: JSONObject) = ScheduledNotification(
+            id = json.getLong("id"),
+            channel = json.getString("channel"),
+            title = json.getString("title"),
+            message = json.getString("message"),
+            priority = json.getString("priority"),
+            nextAt = json.getString("next_at"),
+            repeat = if (json.isNull("repeat")) null else json.getJSONObject("repeat").getString("type"),
+            fired = json.getLong("fired")
+        )
+    }
+}
+
+/** Statistics of one signal over a history window. */
+data class SignalStats(val min: Float, val max: Float, val mean: Float, val stddev: Float, val rms: Float) {
+    companion object {
+        fun fromJson(json: JSONObject) = SignalStats(
+            min = json.getDouble("min").toFloat(),
+            max = json.getDouble("max").toFloat(),
+            mean = json.getDouble("mean").toFloat(),
+            stddev = json.getDouble("stddev").toFloat(),
+            rms = json.getDouble("rms").toFloat()
+        )
+    }
+}
+
+/** Statistics of a sensor's readings, see [RustBridge.sensorStats]. */
+data class SensorStats(
+    val sensor: String,
+    val count: Int,
+    val durationMs: Long,
+    val x: SignalStats,
+    val y: SignalStats,
+    val z: SignalStats,
+    val magnitude: SignalStats
+) {
+    companion object {
+        fun fromJson(json: JSONObject) = SensorStats(
+            sensor = json.getString("sensor"),
+            count = json.getInt("count"),
+            durationMs = json.getLong("duration_ms"),
+            x = SignalStats.fromJson(json.getJSONObject("x")),
+            y = SignalStats.fromJson(json.getJSONObject("y")),
+            z = SignalStats.fromJson(json.getJSONObject("z")),
+            magnitude = SignalStats.fromJson(json.getJSONObject("magnitude"))
+        )
+    }
+}
+
+/** A gesture detected from the accelerometer and gyroscope stream. */
+sealed class Gesture {
+    abstract val timestampNs: Long
+
+    data class Shake(val peaks: Int, override val timestampNs: Long) : Gesture()
+    data class Tap(override val timestampNs: Long) : Gesture()
+    /** Follows a [Tap] instead of a second one. */
+    data class DoubleTap(override val timestampNs: Long) : Gesture()
+    data class FreeFall(val durationMs: Long, override val timestampNs: Long) : Gesture()
+    /** [tilted] is true when the device tipped past the angle, false when it came back. */
+    data class Tilt(val angleDegrees: Float, val tilted: Boolean, override val timestampNs: Long) : Gesture()
+    data class Step(val count: Long, override val timestampNs: Long) : Gesture()
+
+    companion object {
+        /** Returns null for gestures this version does not know. */
+        fun fromJson(json: JSONObject): Gesture? {
+            val timestampNs = json.getLong("timestamp_ns")
+            return when (json.getString("gesture")) {
+                "shake" -> Shake(json.getInt("peaks"), timestampNs)
+                "tap" -> Tap(timestampNs)
+                "double_tap" -> DoubleTap(timestampNs)
+                "free_fall" -> FreeFall(json.getLong("duration_ms"), timestampNs)
+                "tilt" -> Tilt(json.getDouble("angle_degrees").toFloat(), json.getBoolean("tilted"), timestampNs)
+                "step" -> Step(json.getLong("count"), timestampNs)
+                else -> null
+            }
+        }
+    }
+}
+
+/**
+ * Passed to [RustBridge.setGestureConfig]. Thresholds are in m/s² except
+ * [shakeRotationThreshold] (rad/s, 0 ignores the gyroscope); applying a
+ * config restarts the step count.
+ */
+data class GestureConfig(
+    val shakeEnabled: Boolean = true,
+    val shakeThreshold: Float = 12.0f,
+    val shakeRotationThreshold: Float = 8.0f,
+    val shakeMinPeaks: Int = 3,
+    val shakeWindowMs: Long = 1000,
+    val shakeCooldownMs: Long = 1000,
+    val tapEnabled: Boolean = true,
+    val tapThreshold: Float = 4.0f,
+    val tapMaxDurationMs: Long = 60,
+    val doubleTapWindowMs: Long = 400,
+    val freeFallEnabled: Boolean = true,
+    val freeFallThreshold: Float = 3.0f,
+    val freeFallMinDurationMs: Long = 150,
+    val tiltEnabled: Boolean = true,
+    val tiltAngleDegrees: Float = 45.0f,
+    val tiltHysteresisDegrees: Float = 5.0f,
+    val stepsEnabled: Boolean = true,
+    val stepThreshold: Float = 1.0f,
+    val stepMinIntervalMs: Long = 250
+) {
+    fun toJson(): String = JSONObject()
+        .put("shake", JSONObject()
+            .put("enabled", shakeEnabled)
+            .put("threshold", shakeThreshold.toDouble())
+            .put("rotation_threshold", shakeRotationThreshold.toDouble())
+            .put("min_peaks", shakeMinPeaks)
+            .put("window_ms", shakeWindowMs)
+            .put("cooldown_ms", shakeCooldownMs))
+        .put("tap", JSONObject()
+            .put("enabled", tapEnabled)
+            .put("threshold", tapThreshold.toDouble())
+            .put("max_duration_ms", tapMaxDurationMs)
+            .put("double_tap_window_ms", doubleTapWindowMs))
+        .put("free_fall", JSONObject()
+            .put("enabled", freeFallEnabled)
+            .put("threshold", freeFallThreshold.toDouble())
+            .put("min_duration_ms", freeFallMinDurationMs))
+        .put("tilt", JSONObject()
+            .put("enabled", tiltEnabled)
+            .put("angle_degrees", tiltAngleDegrees.toDouble())
+            .put("hysteresis_degrees", tiltHysteresisDegrees.toDouble()))
+        .put("steps", JSONObject()
+            .put("enabled", stepsEnabled)
+            .put("threshold", stepThreshold.toDouble())
+            .put("min_interval_ms", stepMinIntervalMs))
+        .toString()
+}
+
+/** Per-sensor settings in a [SensorConfig]. */
+data class SensorSettings(val enabled: Boolean = true, val periodMs: Long = 100)
+
+/** One stage of a sensor's filter pipeline, see [SensorFilters]. */
+sealed class FilterSpec {
+    /** Exponential smoothing; smaller [alpha] (0, 1] smooths more. */
+    data class LowPass(val alpha: Float) : FilterSpec()
+    /** Removes constant offsets such as gravity; [alpha] in (0, 1]. */
+    data class HighPass(val alpha: Float) : FilterSpec()
+    data class MovingAverage(val window: Int) : FilterSpec()
+    data class Median(val window: Int) : FilterSpec()
+    data class Kalman(val processNoise: Float, val measurementNoise: Float) : FilterSpec()
+
+    fun toJson(): JSONObject = when (this) {
+        is LowPass -> JSONObject().put("type", "low_pass").put("alpha", alpha.toDouble())
+        is HighPass -> JSONObject().put("type", "high_pass").put("alpha", alpha.toDouble())
+        is MovingAverage -> JSONObject().put("type", "moving_average").put("window", window)
+        is Median -> JSONObject().put("type", "median").put("window", window)
+        is Kalman -> JSONObject()
+            .put("type", "kalman")
+            .put("process_noise", processNoise.toDouble())
+            .put("measurement_noise", measurementNoise.toDouble())
+    }
+}
+
+/**
+ * Passed to [RustBridge.setSensorFilters]. Each sensor's readings go through
+ * its stages in order; an empty list leaves the sensor unfiltered.
+ */
+data class SensorFilters(
+    val accelerometer: List<FilterSpec> = emptyList(),
+    val gyroscope: List<FilterSpec> = emptyList(),
+    val magnetometer: List<FilterSpec> = emptyList()
+) {
+    fun toJson(): String {
+        fun stages(specs: List<FilterSpec>) = JSONArray().apply { specs.forEach { put(it.toJson()) } }
+        return JSONObject()
+            .put("accelerometer", stages(accelerometer))
+            .put("gyroscope", stages(gyroscope))
+            .put("magnetometer", stages(magnetometer))
+            .toString()
+    }
+}
+
+/**
+ * Threshold rule registered with [RustBridge.addSensorRule]. [signal] is
+ * `magnitude`, `x`, `y` or `z`; [condition] is `above`, `below` or
+ * `rate_of_change` (absolute change per second). After firing, the rule
+ * waits until the value is back past the threshold by [hysteresis] and
+ * [cooldownMs] has passed. Firing posts a sensor alert notification.
+ */
+data class SensorRule(
+    val sensor: String,
+    val condition: String,
+    val threshold: Float,
+    val signal: String = "magnitude",
+    val hysteresis: Float = 0f,
+    val cooldownMs: Long = 0
+) {
+    fun toJson(): String = JSONObject()
+        .put("sensor", sensor)
+        .put("signal", signal)
+        .put("condition", condition)
+        .put("threshold", threshold.toDouble())
+        .put("hysteresis", hysteresis.toDouble())
+        .put("cooldown_ms", cooldownMs)
+        .toString()
+
+    companion object {
+        fun fromJson(json: JSONObject) = SensorRule(
+            sensor = json.getString("sensor"),
+            condition = json.getString("condition"),
+            threshold = json.getDouble("threshold").toFloat(),
+            signal = json.getString("signal"),
+            hysteresis = json.getDouble("hysteresis").toFloat(),
+            cooldownMs = json.getLong("cooldown_ms")
+        )
+    }
+}
+
+/**
+ * Passed to [RustBridge.startSensors]. Samples are held back for up to
+ * [batchLatencyMs] (0 = deliver immediately) or until [batchSize] are pending.
+ */
+data class SensorConfig(
+    val accelerometer: SensorSettings = SensorSettings(),
+    val gyroscope: SensorSettings = SensorSettings(),
+    val magnetometer: SensorSettings = SensorSettings(),
+    val batchLatencyMs: Long = 0,
+    val batchSize: Int = 64
+) {
+    fun toJson(): String {
+        fun settings(s: SensorSettings) =
+            JSONObject().put("enabled", s.enabled).put("period_ms", s.periodMs)
+        return JSONObject()
+            .put("accelerometer", settings(accelerometer))
+            .put("gyroscope", settings(gyroscope))
+            .put("magnetometer", settings(magnetometer))
+            .put("batch_latency_ms", batchLatencyMs)
+            .put("batch_size", batchSize)
+            .toString()
+    }
+}
 
 class RustBridge {
     companion object {
@@ -17,22 +595,498 @@ class RustBridge {
                 throw e
             }
         }
+
+        // Callback methods that Rust can call. They must stay static: Rust
+        // resolves them once with GetStaticMethodID when the library loads.
+        @JvmStatic
+        fun onRustEvents(batch: ByteArray) {
+            try {
+                val events = decodeBatch(batch).getJSONArray("events")
+                for (i in 0 until events.length()) {
+                    handleEvent(events.getJSONObject(i))
+                }
+            } catch (e: JSONException) {
+                Log.e(TAG, "Malformed event batch from Rust", e)
+            } catch (e: IOException) {
+                Log.e(TAG, "Malformed MessagePack event batch from Rust", e)
+            }
+        }
+
+        /**
+         * JSON batches start with '{'; anything else is the MessagePack
+         * encoding selected with MAINLOGIC_EVENT_FORMAT, which has the same
+         * field names and is converted so both share [handleEvent].
+         */
+        private fun decodeBatch(batch: ByteArray): JSONObject {
+            if (batch.isNotEmpty() && batch[0] == '{'.code.toByte()) {
+                return JSONObject(String(batch, Charsets.UTF_8))
+            }
+            MessagePack.newDefaultUnpacker(batch).use { unpacker ->
+                return JSONObject(unpacker.unpackValue().toJson())
+            }
+        }
+
+        private fun handleEvent(event: JSONObject) {
+            when (val type = event.getString("type")) {
+                "sensor_sample" -> onSensorSample(
+                    SensorSample(
+                        sensor = event.getString("sensor"),
+                        x = event.getDouble("x").toFloat(),
+                        y = event.getDouble("y").toFloat(),
+                        z = event.getDouble("z").toFloat(),
+                        accuracy = event.getString("accuracy"),
+                        timestampNs = event.getLong("timestamp_ns")
+                    )
+                )
+                "orientation" -> onOrientation(Orientation.fromJson(event))
+                "gesture" -> Gesture.fromJson(event)?.let { onGesture(it) }
+                "notification" -> onNotification(StoredNotification.fromJson(event))
+                "notification_channel" -> onNotificationChannel(NotificationChannelInfo.fromJson(event))
+                "task_progress" -> onTaskProgress(
+                    event.getLong("task_id"),
+                    event.getString("name"),
+                    event.getInt("percent"),
+                    event.getString("message")
+                )
+                "task_state" -> onTaskState(
+                    event.getLong("task_id"),
+                    event.getString("name"),
+                    event.getString("state"),
+                    event.optString("message", "")
+                )
+                "job_run" -> onJobRun(
+                    event.getLong("job_id"),
+                    event.getString("name"),
+                    event.getLong("run")
+                )
+                "status" -> onStatusUpdate(event.getString("message"))
+                "message" -> onRustMessage(
+                    event.getString("topic"),
+                    event.getString("payload")
+                )
+                else -> Log.d(TAG, "Ignoring unknown Rust event type: $type")
+            }
+        }
+
+        private fun onSensorSample(sample: SensorSample) {
+            Log.d(TAG, "Sensor data received: $sample")
+            // Handle sensor data from Rust
+        }
+
+        private fun onOrientation(orientation: Orientation) {
+            Log.d(TAG, "Orientation received: roll=${orientation.roll} pitch=${orientation.pitch} yaw=${orientation.yaw}")
+            // Handle orientation from Rust
+        }
+
+        private fun onGesture(gesture: Gesture) {
+            Log.d(TAG, "Gesture detected: $gesture")
+            // Handle gestures from Rust
+        }
+
+        /** Most notifications kept while no [notificationService] is set. */
+        private const val MAX_UNDELIVERED = 32
+
+        /**
+         * Notifications that arrived before [notificationService] was set, by
+         * id, e.g. scheduled ones that came due while the app was not running.
+         * Also guards [notificationService].
+         */
+        private val undelivered = LinkedHashMap<Int, StoredNotification>()
+
+        /**
+         * Shows Rust's notifications and creates their channels once set.
+         * Setting it shows the notifications that arrived before.
+         */
+        var notificationService: NotificationService? = null
+            get() = synchronized(undelivered) { field }
+            set(service) {
+                val pending = synchronized(undelivered) {
+                    field = service
+                    if (service == null) return
+                    undelivered.values.toList().also { undelivered.clear() }
+                }
+                pending.forEach { service?.showNotification(it) }
+            }
+
+        private fun onNotification(notification: StoredNotification) {
+            Log.d(TAG, "Notification received: [${notification.id}] on ${notification.channel} " +
+                "(${notification.priority}) ${notification.title} - ${notification.message}")
+            val service = synchronized(undelivered) {
+                notificationService ?: run {
+                    // Updates replace what is waiting under the same id
+                    undelivered.remove(notification.id)
+                    undelivered[notification.id] = notification
+                    if (undelivered.size > MAX_UNDELIVERED) {
+                        undelivered.remove(undelivered.keys.first())
+                    }
+                    return
+                }
+            }
+            service.showNotification(notification)
+        }
+
+        private fun onNotificationChannel(channel: NotificationChannelInfo) {
+            Log.d(TAG, "Notification channel declared: ${channel.id}")
+            notificationService?.createChannel(channel)
+        }
+
+        private fun onTaskProgress(taskId: Long, name: String, percent: Int, message: String) {
+            Log.d(TAG, "Rust task $taskId ($name) at $percent%: $message")
+            // This is reported from Rust background threads
+        }
+
+        private fun onTaskState(taskId: Long, name: String, state: String, message: String) {
+            // state is one of queued, running, succeeded, failed, cancelled
+            Log.d(TAG, "Rust task $taskId ($name) is $state $message")
+            // This is reported from Rust background threads
+        }
+
+        private fun onJobRun(jobId: Long, name: String, run: Long) {
+            Log.d(TAG, "Scheduled job $jobId ($name) fired, run $run")
+            // Runs of jobs created with schedule* and startPeriodicTask
+        }
+
+        private fun onStatusUpdate(status: String) {
+            Log.d(TAG, "Status update: $status")
+            // Handle status update from Rust
+        }
+
+        private fun onRustMessage(topic: String, payload: String) {
+            Log.d(TAG, "Rust message on $topic: $payload")
+            // Replies from Rust bus subscribers, e.g. "echo.reply"
+        }
     }
 
     // Native method declarations
     external fun rustSum(a: Int, b: Int): Int
-    external fun rustNotifyJava(message: String)
-    external fun rustAsyncCallback(delayMs: Long)
+    external fun rustNotifyJava(topic: String, payload: String): Int
+    external fun rustAsyncCallback(delayMs: Long): Long
+    external fun startPeriodicTask(intervalMs: Long, count: Int): Long
+    external fun startLongRunningTask(): Long
+    external fun cancelTask(taskId: Long): Boolean
+    external fun getTaskState(taskId: Long): String
+    external fun getWorkerPoolMetrics(): String
+
+    // Scheduler: maxRuns <= 0 means unlimited, cron expressions start with a seconds field
+    external fun scheduleOnce(name: String, delayMs: Long): Long
+    external fun scheduleFixedRate(name: String, initialDelayMs: Long, periodMs: Long, maxRuns: Int): Long
+    external fun scheduleFixedDelay(name: String, initialDelayMs: Long, delayMs: Long, maxRuns: Int): Long
+    external fun scheduleCron(name: String, expression: String): Long
+    external fun pauseJob(jobId: Long): Boolean
+    external fun resumeJob(jobId: Long): Boolean
+    external fun cancelJob(jobId: Long): Boolean
+    external fun getJobState(jobId: Long): String
     external fun rustGetVersion(): String
     external fun rustProcessData(input: String): String
     
     // New native activity methods
     external fun initializeNativeActivity(): Boolean
     external fun sendTestNotification()
+    private external fun postNotification(contentJson: String): Int
+    private external fun onNotificationAction(notificationId: Int, actionId: String): Boolean
+    private external fun onNotificationDismissed(notificationId: Int): Boolean
+    private external fun getNotificationStats(): String
+    private external fun registerNotificationChannel(channelJson: String)
+    private external fun getNotificationChannels(): String
+    private external fun setNotificationStore(store: String)
+    private external fun getNotificationHistory(): String
+    private external fun scheduleNotification(requestJson: String): Long
+    external fun cancelScheduledNotification(scheduleId: Long): Boolean
+    private external fun listScheduledNotifications(): String
     external fun updateStatus(message: String)
     external fun getSensorData(): String
+    private external fun startSensors(configJson: String)
+    external fun stopSensors()
+    private external fun setSensorFilters(configJson: String)
+    external fun setSensorBackend(backend: String, speed: Float)
+    external fun startSensorRecording(path: String, format: String)
+    external fun stopSensorRecording(): Long
+    private external fun addSensorRule(ruleJson: String): Long
+    external fun removeSensorRule(ruleId: Long): Boolean
+    private external fun listSensorRules(): String
+    private external fun setSensorHistoryWindow(windowMs: Long)
+    private external fun getSensorHistory(sensor: String, windowMs: Long): FloatArray
+    private external fun getSensorStats(sensor: String, windowMs: Long): String
+    private external fun getOrientation(): String
+    private external fun setFusionConfig(configJson: String)
+    private external fun setGestureConfig(configJson: String)
+    external fun onActivityPause()
+    external fun onActivityResume()
 
     // Kotlin wrapper methods
+    /** False if the config is invalid or a sensor could not be opened. */
+    fun startSensors(config: SensorConfig): Boolean {
+        return try {
+            startSensors(config.toJson())
+            true
+        } catch (e: Exception) {
+            Log.e(TAG, "Error starting sensors", e)
+            false
+        }
+    }
+
+    fun setSensorFilters(filters: SensorFilters) {
+        try {
+            setSensorFilters(filters.toJson())
+        } catch (e: Exception) {
+            Log.e(TAG, "Error setting sensor filters", e)
+        }
+    }
+
+    /**
+     * Keeps notifications in the SQLite database [file] (or an append-only
+     * log if it ends in `.jsonl`) so IDs and history survive restarts. Call
+     * before [initializeNativeActivityWrapper] to load the history on init.
+     */
+    fun useNotificationStore(file: File) {
+        try {
+            val kind = if (file.extension == "jsonl") "file" else "sqlite"
+            setNotificationStore("$kind:${file.absolutePath}")
+        } catch (e: Exception) {
+            Log.e(TAG, "Error setting notification store", e)
+        }
+    }
+
+    fun registerNotificationChannel(channel: NotificationChannelInfo) {
+        try {
+            registerNotificationChannel(channel.toJson())
+        } catch (e: Exception) {
+            Log.e(TAG, "Error registering notification channel", e)
+        }
+    }
+
+    fun notificationChannels(): List<NotificationChannelInfo> {
+        return try {
+            val array = JSONArray(getNotificationChannels())
+            List(array.length()) { NotificationChannelInfo.fromJson(array.getJSONObject(it)) }
+        } catch (e: Exception) {
+            Log.e(TAG, "Error getting notification channels", e)
+            emptyList()
+        }
+    }
+
+    /** Posts [content] through Rust and returns its id, or -1 if it was rejected. */
+    fun postNotification(content: NotificationContent): Int {
+        return try {
+            postNotification(content.toJson())
+        } catch (e: Exception) {
+            Log.e(TAG, "Error posting notification", e)
+            -1
+        }
+    }
+
+    /** Posted, updated, coalesced and suppressed counts by channel. */
+    fun notificationStats(): Map<String, NotificationStats> {
+        return try {
+            val stats = JSONObject(getNotificationStats())
+            stats.keys().asSequence().associateWith { NotificationStats.fromJson(stats.getJSONObject(it)) }
+        } catch (e: Exception) {
+            Log.e(TAG, "Error getting notification stats", e)
+            emptyMap()
+        }
+    }
+
+    /** Reports a tapped action button; returns whether a Rust handler ran. */
+    fun notificationActionTapped(notificationId: Int, actionId: String): Boolean {
+        return try {
+            onNotificationAction(notificationId, actionId)
+        } catch (e: Exception) {
+            Log.e(TAG, "Error handling notification action $actionId", e)
+            false
+        }
+    }
+
+    /** Reports a notification the user dismissed; returns whether Rust knew it. */
+    fun notificationDismissed(notificationId: Int): Boolean {
+        return try {
+            onNotificationDismissed(notificationId)
+        } catch (e: Exception) {
+            Log.e(TAG, "Error reporting dismissed notification $notificationId", e)
+            false
+        }
+    }
+
+    /** Stored notifications, oldest first. */
+    fun notificationHistory(): List<StoredNotification> {
+        return try {
+            val array = JSONArray(getNotificationHistory())
+            List(array.length()) { StoredNotification.fromJson(array.getJSONObject(it)) }
+        } catch (e: Exception) {
+            Log.e(TAG, "Error getting notification history", e)
+            emptyList()
+        }
+    }
+
+    /**
+     * Has Rust post a notification later, repeatedly if [NotificationSchedule.repeat]
+     * is set. Schedules are kept in the notification store and re-armed on
+     * the next start. Returns the schedule id, or -1 if it was rejected.
+     */
+    fun scheduleNotification(schedule: NotificationSchedule): Long {
+        return try {
+            scheduleNotification(schedule.toJson())
+        } catch (e: Exception) {
+            Log.e(TAG, "Error scheduling notification", e)
+            -1
+        }
+    }
+
+    /** Pending schedules by id. */
+    fun scheduledNotifications(): List<ScheduledNotification> {
+        return try {
+            val array = JSONArray(listScheduledNotifications())
+            List(array.length()) { ScheduledNotification.fromJson(array.getJSONObject(it)) }
+        } catch (e: Exception) {
+            Log.e(TAG, "Error listing scheduled notifications", e)
+            emptyList()
+        }
+    }
+
+    /**
+     * Records raw sensor readings to [file] until [stopRecording]. The format
+     * follows the extension: `.bin` (compact binary), `.csv` or `.jsonl`.
+     */
+    fun startRecording(file: File) {
+        try {
+            startSensorRecording(file.absolutePath, "")
+        } catch (e: Exception) {
+            Log.e(TAG, "Error starting sensor recording", e)
+        }
+    }
+
+    /** Returns the number of recorded samples, or -1 if nothing was recording. */
+    fun stopRecording(): Long {
+        return try {
+            stopSensorRecording()
+        } catch (e: Exception) {
+            Log.e(TAG, "Error stopping sensor recording", e)
+            -1
+        }
+    }
+
+    /** Plays [file] back instead of the live sensors, [speed] times faster than real time. */
+    fun replay(file: File, speed: Float = 1f) {
+        try {
+            setSensorBackend("replay:${file.absolutePath}", speed)
+        } catch (e: Exception) {
+            Log.e(TAG, "Error replaying ${file.name}", e)
+        }
+    }
+
+    /** Switches back to the hardware sensors, or the simulator without them. */
+    fun useLiveSensors() {
+        try {
+            setSensorBackend("auto", 1f)
+        } catch (e: Exception) {
+            Log.e(TAG, "Error switching to live sensors", e)
+        }
+    }
+
+    /** Registers [rule] and returns its id, or -1 if it was rejected. */
+    fun addSensorRule(rule: SensorRule): Long {
+        return try {
+            addSensorRule(rule.toJson())
+        } catch (e: Exception) {
+            Log.e(TAG, "Error adding sensor rule", e)
+            -1
+        }
+    }
+
+    /** Registered rules by id. */
+    fun sensorRules(): Map<Long, SensorRule> {
+        return try {
+            val rules = JSONArray(listSensorRules())
+            (0 until rules.length()).associate { i ->
+                val rule = rules.getJSONObject(i)
+                rule.getLong("id") to SensorRule.fromJson(rule)
+            }
+        } catch (e: Exception) {
+            Log.e(TAG, "Error listing sensor rules", e)
+            emptyMap()
+        }
+    }
+
+    /** How many milliseconds of readings each sensor keeps (default 10 s). */
+    fun setHistoryWindow(windowMs: Long) {
+        try {
+            setSensorHistoryWindow(windowMs)
+        } catch (e: Exception) {
+            Log.e(TAG, "Error setting sensor history window", e)
+        }
+    }
+
+    /**
+     * The last [windowMs] of [sensor]'s readings, everything kept if 0. Four
+     * floats per sample, oldest first: seconds relative to the newest sample
+     * (0 or negative), then x, y and z.
+     */
+    fun sensorHistory(sensor: String, windowMs: Long = 0): FloatArray {
+        return try {
+            getSensorHistory(sensor, windowMs)
+        } catch (e: Exception) {
+            Log.e(TAG, "Error getting sensor history", e)
+            FloatArray(0)
+        }
+    }
+
+    /** Statistics over the last [windowMs] of [sensor]'s readings, null before it has reported. */
+    fun sensorStats(sensor: String, windowMs: Long = 0): SensorStats? {
+        return try {
+            val json = getSensorStats(sensor, windowMs)
+            if (json == "null") null else SensorStats.fromJson(JSONObject(json))
+        } catch (e: Exception) {
+            Log.e(TAG, "Error getting sensor stats", e)
+            null
+        }
+    }
+
+    /** Latest fused orientation, or null before the gyroscope has reported. */
+    fun orientation(): Orientation? {
+        return try {
+            val json = getOrientation()
+            if (json == "null") null else Orientation.fromJson(JSONObject(json))
+        } catch (e: Exception) {
+            Log.e(TAG, "Error getting orientation", e)
+            null
+        }
+    }
+
+    fun setFusionConfig(config: FusionConfig) {
+        try {
+            setFusionConfig(config.toJson())
+        } catch (e: Exception) {
+            Log.e(TAG, "Error setting fusion config", e)
+        }
+    }
+
+    fun setGestureConfig(config: GestureConfig) {
+        try {
+            setGestureConfig(config.toJson())
+        } catch (e: Exception) {
+            Log.e(TAG, "Error setting gesture config", e)
+        }
+    }
+
+    fun onPause() {
+        try {
+            onActivityPause()
+        } catch (e: UnsatisfiedLinkError) {
+            // Library not loaded yet, so there is nothing to pause
+        } catch (e: Exception) {
+            Log.e(TAG, "Error pausing native activity", e)
+        }
+    }
+
+    fun onResume() {
+        try {
+            onActivityResume()
+        } catch (e: UnsatisfiedLinkError) {
+            // Library not loaded yet, so there is nothing to resume
+        } catch (e: Exception) {
+            Log.e(TAG, "Error resuming native activity", e)
+        }
+    }
+
     fun testSum(a: Int, b: Int): Int {
         return try {
             rustSum(a, b)
@@ -42,11 +1096,30 @@ class RustBridge {
         }
     }
 
-    fun testAsyncCallback() {
-        try {
+    fun publish(topic: String, payload: String): Int {
+        return try {
+            rustNotifyJava(topic, payload)
+        } catch (e: Exception) {
+            Log.e(TAG, "Error publishing to $topic", e)
+            0
+        }
+    }
+
+    fun testAsyncCallback(): Long {
+        return try {
             rustAsyncCallback(2000) // 2 second delay
         } catch (e: Exception) {
             Log.e(TAG, "Error calling rustAsyncCallback", e)
+            -1
+        }
+    }
+
+    fun cancelTaskWrapper(taskId: Long): Boolean {
+        return try {
+            cancelTask(taskId)
+        } catch (e: Exception) {
+            Log.e(TAG, "Error cancelling task $taskId", e)
+            false
         }
     }
 
@@ -102,31 +1175,4 @@ class RustBridge {
             "Error getting sensor data"
         }
     }
-
-    // Callback methods that Rust can call
-    fun onRustEvent(eventType: String, data: String) {
-        Log.d(TAG, "Rust event received: $eventType - $data")
-        // This method can be called from Rust via JNI
-    }
-
-    fun onRustAsyncResult(result: String) {
-        Log.d(TAG, "Rust async result: $result")
-        // This method can be called from Rust background threads
-    }
-    
-    // New callback methods for native activity
-    fun onSensorData(sensorData: String) {
-        Log.d(TAG, "Sensor data received: $sensorData")
-        // Handle sensor data from Rust
-    }
-    
-    fun onNotification(id: Int, title: String, message: String) {
-        Log.d(TAG, "Notification received: [$id] $title - $message")
-        // Handle notification from Rust
-    }
-    
-    fun onStatusUpdate(status: String) {
-        Log.d(TAG, "Status update: $status")
-        // Handle status update from Rust
-    }
-} 
\ No newline at end of file
+}
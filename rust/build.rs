@@ -1,4 +1,5 @@
 fn main() {
     slint_build::compile("ui/app.slint").unwrap();
     println!("cargo:rerun-if-changed=ui/app.slint");
+    println!("cargo:rerun-if-env-changed=MAINLOGIC_BRIDGE_CLASS");
 } 
\ No newline at end of file
@@ -1,61 +1,76 @@
-use jni::JavaVM;
-use std::sync::{Arc, Mutex};
-use std::thread;
 use std::time::Duration;
 
-use crate::jni_bridge::call_java_method;
+use crate::error::{Error, Result};
+use crate::notifications::channels::DEFAULT_CHANNEL;
+use crate::notifications::content::{NotificationAction, NotificationContent, Progress};
+use crate::notifications::progress::ProgressNotification;
+use crate::notifications::NotificationManager;
+use crate::tasks::TaskContext;
 
+/// Task bodies run by `TaskManager`. Each returns the result string
+/// reported with the `succeeded` state.
 pub struct AsyncWorker;
 
 impl AsyncWorker {
-    pub fn run_async_task(java_vm: Arc<Mutex<JavaVM>>, delay_ms: i64) {
+    pub fn run_async_task(context: &TaskContext, delay_ms: i64) -> Result<String> {
         log::info!("AsyncWorker: Starting async task with delay: {}ms", delay_ms);
         
         // Simulate some work
-        thread::sleep(Duration::from_millis(delay_ms as u64));
-        
-        // Call back to Java with result
-        if let Ok(java_vm_guard) = java_vm.lock() {
-            let result = format!("Async task completed after {}ms", delay_ms);
-            log::info!("AsyncWorker: {}", result);
-            
-            call_java_method(&java_vm_guard, "onRustAsyncResult", &result);
+        if !context.sleep(Duration::from_millis(delay_ms as u64)) {
+            return Err(Error::Cancelled);
         }
+        
+        let result = format!("Async task completed after {}ms", delay_ms);
+        log::info!("AsyncWorker: {}", result);
+        Ok(result)
     }
     
-    pub fn run_periodic_task(java_vm: Arc<Mutex<JavaVM>>, interval_ms: i64, count: i32) {
-        log::info!("AsyncWorker: Starting periodic task with interval: {}ms, count: {}", interval_ms, count);
+    /// With `notifications`, also shows the progress in a notification
+    /// whose Cancel button cancels the task.
+    pub fn run_long_running_task(context: &TaskContext, notifications: Option<&NotificationManager>) -> Result<String> {
+        log::info!("AsyncWorker: Starting long running task");
         
-        for i in 1..=count {
-            thread::sleep(Duration::from_millis(interval_ms as u64));
-            
-            if let Ok(java_vm_guard) = java_vm.lock() {
-                let result = format!("Periodic task iteration {}/{}", i, count);
-                log::info!("AsyncWorker: {}", result);
-                
-                call_java_method(&java_vm_guard, "onRustAsyncResult", &result);
-            }
+        let cancel_action = format!("cancel_task:{}", context.id().0);
+        let notification = notifications.and_then(|manager| {
+            let token = context.token().clone();
+            manager.register_action(&cancel_action, move |_| token.cancel());
+            let content = NotificationContent::new(DEFAULT_CHANNEL, "Long running task", "Starting")
+                .with_progress(Progress::Determinate { current: 0, max: 100 })
+                .with_action(NotificationAction::new(&cancel_action, "Cancel"));
+            ProgressNotification::start(manager, content)
+                .inspect_err(|e| log::warn!("AsyncWorker: No progress notification: {}", e))
+                .ok()
+        });
+        
+        let result = Self::long_running_steps(context, notification.as_ref());
+        if let Some(notification) = &notification {
+            notification.finish(match &result {
+                Ok(message) => message.clone(),
+                Err(Error::Cancelled) => "Cancelled".to_string(),
+                Err(e) => format!("Failed: {}", e),
+            });
+        }
+        if let Some(manager) = notifications {
+            manager.unregister_action(&cancel_action);
         }
+        result
     }
     
-    pub fn run_long_running_task(java_vm: Arc<Mutex<JavaVM>>) {
-        log::info!("AsyncWorker: Starting long running task");
-        
+    fn long_running_steps(context: &TaskContext, notification: Option<&ProgressNotification>) -> Result<String> {
         // Simulate a long-running operation
         for i in 1..=10 {
-            thread::sleep(Duration::from_millis(500));
+            if !context.sleep(Duration::from_millis(500)) {
+                return Err(Error::Cancelled);
+            }
             
-            if let Ok(java_vm_guard) = java_vm.lock() {
-                let progress = format!("Long running task progress: {}%", i * 10);
-                log::info!("AsyncWorker: {}", progress);
-                
-                call_java_method(&java_vm_guard, "onRustAsyncResult", &progress);
+            let message = format!("Long running task progress: {}%", i * 10);
+            log::info!("AsyncWorker: {}", message);
+            if let Some(notification) = notification {
+                notification.update(i as u64 * 10, 100, format!("{}%", i * 10));
             }
+            context.progress(i * 10, message);
         }
         
-        // Final completion message
-        if let Ok(java_vm_guard) = java_vm.lock() {
-            call_java_method(&java_vm_guard, "onRustAsyncResult", "Long running task completed!");
-        }
+        Ok("Long running task completed!".to_string())
     }
-} 
\ No newline at end of file
+}
@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use crate::events::{Event, EventSender};
+use crate::utils;
+
+/// Replies can trigger further replies; stop following a chain after this
+/// many hops so two subscribers answering each other cannot loop forever.
+const MAX_REPLY_DEPTH: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub topic: String,
+    pub payload: String,
+}
+
+impl Message {
+    pub fn new(topic: impl Into<String>, payload: impl Into<String>) -> Self {
+        Self {
+            topic: topic.into(),
+            payload: payload.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+/// A subscriber receives every message on its topic and may answer with a
+/// message on another topic.
+type Handler = Arc<dyn Fn(&Message) -> Option<Message> + Send + Sync>;
+
+/// Topic-based publish/subscribe bus between Kotlin and Rust modules.
+///
+/// Kotlin publishes with `rustNotifyJava(topic, payload)`; the message goes
+/// to the Rust subscribers of that topic. Their replies are forwarded to
+/// Kotlin as `Event::Message` and also routed to Rust subscribers of the
+/// reply topic.
+pub struct Bus {
+    subscribers: RwLock<HashMap<String, Vec<(SubscriptionId, Handler)>>>,
+    next_id: AtomicU64,
+    events: EventSender,
+}
+
+impl Bus {
+    pub fn new(events: EventSender) -> Self {
+        Self {
+            subscribers: RwLock::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+            events,
+        }
+    }
+
+    pub fn subscribe(
+        &self,
+        topic: &str,
+        handler: impl Fn(&Message) -> Option<Message> + Send + Sync + 'static,
+    ) -> SubscriptionId {
+        let id = SubscriptionId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        self.subscribers
+            .write()
+            .unwrap()
+            .entry(topic.to_string())
+            .or_default()
+            .push((id, Arc::new(handler)));
+        id
+    }
+
+    pub fn unsubscribe(&self, id: SubscriptionId) -> bool {
+        let mut subscribers = self.subscribers.write().unwrap();
+        let mut removed = false;
+        subscribers.retain(|_, handlers| {
+            let before = handlers.len();
+            handlers.retain(|(handler_id, _)| *handler_id != id);
+            removed |= handlers.len() != before;
+            !handlers.is_empty()
+        });
+        removed
+    }
+
+    /// Delivers `message` to the Rust subscribers of its topic and routes
+    /// their replies. Returns how many subscribers received it.
+    pub fn publish(&self, message: Message) -> usize {
+        self.deliver(&message, 0)
+    }
+
+    /// Sends `message` to Kotlin and to the Rust subscribers of its topic.
+    pub fn reply(&self, message: Message) {
+        self.reply_at_depth(message, 0);
+    }
+
+    fn reply_at_depth(&self, message: Message, depth: usize) {
+        self.deliver(&message, depth);
+        self.events.send(Event::Message {
+            topic: message.topic,
+            payload: message.payload,
+        });
+    }
+
+    fn deliver(&self, message: &Message, depth: usize) -> usize {
+        if depth >= MAX_REPLY_DEPTH {
+            log::warn!("Bus: dropping reply chain at topic {}", message.topic);
+            return 0;
+        }
+
+        // Clone the handlers so they run without the lock held and can
+        // subscribe or unsubscribe themselves
+        let handlers: Vec<Handler> = match self.subscribers.read().unwrap().get(&message.topic) {
+            Some(handlers) => handlers.iter().map(|(_, handler)| handler.clone()).collect(),
+            None => return 0,
+        };
+
+        for handler in &handlers {
+            if let Some(reply) = handler(message) {
+                self.reply_at_depth(reply, depth + 1);
+            }
+        }
+        handlers.len()
+    }
+}
+
+/// Subscribers every app gets. Feature modules add their own on top.
+pub fn register_default_handlers(bus: &Bus) {
+    bus.subscribe("echo", |message| {
+        Some(Message::new("echo.reply", message.payload.clone()))
+    });
+    bus.subscribe("process", |message| {
+        Some(Message::new(
+            "process.result",
+            utils::process_input_data(&message.payload),
+        ))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::mpsc::Receiver;
+    use std::sync::Mutex;
+
+    fn bus() -> (Bus, Receiver<Event>) {
+        let (sender, receiver) = events::channel();
+        (Bus::new(sender), receiver)
+    }
+
+    /// Subscribes to `topic`, collecting the payloads it receives.
+    fn collect(bus: &Bus, topic: &str) -> (SubscriptionId, Arc<Mutex<Vec<String>>>) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = received.clone();
+        let id = bus.subscribe(topic, move |message| {
+            sink.lock().unwrap().push(message.payload.clone());
+            None
+        });
+        (id, received)
+    }
+
+    fn sent_to_kotlin(receiver: &Receiver<Event>) -> Vec<(String, String)> {
+        receiver
+            .try_iter()
+            .filter_map(|event| match event {
+                Event::Message { topic, payload } => Some((topic, payload)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn publish_reaches_every_subscriber_of_the_topic() {
+        let (bus, receiver) = bus();
+        let (_, first) = collect(&bus, "sensors");
+        let (_, second) = collect(&bus, "sensors");
+        let (_, other) = collect(&bus, "tasks");
+
+        assert_eq!(bus.publish(Message::new("sensors", "start")), 2);
+        assert_eq!(bus.publish(Message::new("nobody", "hello")), 0);
+        assert_eq!(*first.lock().unwrap(), vec!["start"]);
+        assert_eq!(*second.lock().unwrap(), vec!["start"]);
+        assert!(other.lock().unwrap().is_empty());
+        assert!(sent_to_kotlin(&receiver).is_empty());
+    }
+
+    #[test]
+    fn replies_go_to_kotlin_and_to_rust_subscribers() {
+        let (bus, receiver) = bus();
+        register_default_handlers(&bus);
+        let (_, replies) = collect(&bus, "echo.reply");
+
+        assert_eq!(bus.publish(Message::new("echo", "ping")), 1);
+        assert_eq!(*replies.lock().unwrap(), vec!["ping"]);
+        assert_eq!(sent_to_kotlin(&receiver), vec![("echo.reply".to_string(), "ping".to_string())]);
+    }
+
+    #[test]
+    fn reply_loops_are_cut_off() {
+        let (bus, receiver) = bus();
+        let calls = Arc::new(AtomicUsize::new(0));
+        for (topic, answer) in [("ping", "pong"), ("pong", "ping")] {
+            let calls = calls.clone();
+            bus.subscribe(topic, move |message| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Some(Message::new(answer, message.payload.clone()))
+            });
+        }
+
+        assert_eq!(bus.publish(Message::new("ping", "rally")), 1);
+        assert_eq!(calls.load(Ordering::SeqCst), MAX_REPLY_DEPTH);
+        assert_eq!(sent_to_kotlin(&receiver).len(), MAX_REPLY_DEPTH);
+    }
+
+    #[test]
+    fn unsubscribed_handlers_receive_nothing() {
+        let (bus, _receiver) = bus();
+        let (id, received) = collect(&bus, "sensors");
+        let (_, kept) = collect(&bus, "sensors");
+
+        assert!(bus.unsubscribe(id));
+        assert!(!bus.unsubscribe(id));
+        assert_eq!(bus.publish(Message::new("sensors", "start")), 1);
+        assert!(received.lock().unwrap().is_empty());
+        assert_eq!(*kept.lock().unwrap(), vec!["start"]);
+    }
+}
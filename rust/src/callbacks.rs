@@ -0,0 +1,78 @@
+use jni::{
+    objects::{GlobalRef, JClass, JStaticMethodID, JValue},
+    signature::{Primitive, ReturnType},
+    sys::jvalue,
+    JNIEnv, JavaVM,
+};
+
+use crate::error::Result;
+
+/// Enough for the handful of objects a single callback creates.
+const LOCAL_FRAME_CAPACITY: i32 = 8;
+
+/// Static callbacks on the bridge class, resolved once while the library is
+/// loaded.
+///
+/// `find_class` only sees the application class loader on threads that
+/// Java started, so it fails on our own worker threads. Holding a
+/// `GlobalRef` to the class and the method IDs sidesteps that and avoids
+/// the per-call string lookups.
+pub struct JavaCallbacks {
+    java_vm: JavaVM,
+    class: GlobalRef,
+    on_rust_events: JStaticMethodID,
+}
+
+impl JavaCallbacks {
+    /// Resolves the bridge class and its callback methods. Must run on a
+    /// thread that can see the application class loader, i.e. `JNI_OnLoad`.
+    pub fn new(env: &mut JNIEnv, class_name: &str) -> Result<Self> {
+        let class = env.find_class(class_name)?;
+
+        Ok(Self {
+            java_vm: env.get_java_vm()?,
+            on_rust_events: env.get_static_method_id(&class, "onRustEvents", "([B)V")?,
+            class: env.new_global_ref(class)?,
+        })
+    }
+
+    /// Delivers an encoded `events::EventBatch`.
+    pub fn on_rust_events(&self, batch: &[u8]) -> Result<()> {
+        self.dispatch(|env| {
+            let batch = env.byte_array_from_slice(batch)?;
+            self.call_void(env, self.on_rust_events, &[JValue::Object(&batch).as_jni()])
+        })
+    }
+
+    /// Attaches the calling thread if needed and runs `f` inside a local
+    /// frame, so long-lived worker threads do not accumulate local refs.
+    /// An exception thrown by the Kotlin callback is logged and cleared.
+    fn dispatch<F>(&self, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut JNIEnv) -> Result<()>,
+    {
+        let mut env = self.java_vm.attach_current_thread_as_daemon()?;
+        let result = env.with_local_frame(LOCAL_FRAME_CAPACITY, f);
+
+        if env.exception_check()? {
+            env.exception_describe()?;
+            env.exception_clear()?;
+        }
+        result
+    }
+
+    fn call_void(&self, env: &mut JNIEnv, method: JStaticMethodID, args: &[jvalue]) -> Result<()> {
+        let class: &JClass = self.class.as_obj().into();
+        // SAFETY: `method` was resolved on `class` in `new` and every caller
+        // passes arguments matching the signature it was resolved with.
+        unsafe {
+            env.call_static_method_unchecked(
+                class,
+                method,
+                ReturnType::Primitive(Primitive::Void),
+                args,
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+use log::LevelFilter;
+use std::env;
+use std::str::FromStr;
+
+use crate::events::EventFormat;
+use crate::notifications::store::StoreConfig;
+use crate::pool::{PoolConfig, RejectionPolicy};
+use crate::sensors::SensorBackend;
+
+/// Bridge class used when neither the build nor the process overrides it.
+/// Set `MAINLOGIC_BRIDGE_CLASS` at build time to change the default.
+pub const DEFAULT_BRIDGE_CLASS: &str = match option_env!("MAINLOGIC_BRIDGE_CLASS") {
+    Some(class_name) => class_name,
+    None => "com/example/modularandroidapp/RustBridge",
+};
+
+/// Settings read once in `JNI_OnLoad`, before any other subsystem starts.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Class that declares the natives and callbacks, in JNI form
+    /// (`com/example/Foo`). Dotted names are accepted and converted.
+    pub bridge_class: String,
+    pub log_level: LevelFilter,
+    /// Encoding of the batches passed to `onRustEvents`.
+    pub event_format: EventFormat,
+    /// Size and queueing behaviour of the worker pool that runs tasks.
+    pub pool: PoolConfig,
+    /// Sensor source opened by the native activity.
+    pub sensor_backend: SensorBackend,
+    /// Where the native activity keeps notifications across restarts.
+    pub notification_store: StoreConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bridge_class: DEFAULT_BRIDGE_CLASS.to_string(),
+            log_level: LevelFilter::Debug,
+            event_format: EventFormat::Json,
+            pool: PoolConfig::default(),
+            sensor_backend: SensorBackend::Auto,
+            notification_store: StoreConfig::Memory,
+        }
+    }
+}
+
+impl Config {
+    /// Defaults overridden by `MAINLOGIC_*` environment variables. Invalid
+    /// values are logged and ignored.
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Ok(class_name) = env::var("MAINLOGIC_BRIDGE_CLASS") {
+            config.bridge_class = class_name.replace('.', "/");
+        }
+        if let Ok(level) = env::var("MAINLOGIC_LOG_LEVEL") {
+            match LevelFilter::from_str(&level) {
+                Ok(level) => config.log_level = level,
+                Err(_) => log::warn!("Ignoring invalid MAINLOGIC_LOG_LEVEL: {}", level),
+            }
+        }
+        if let Ok(format) = env::var("MAINLOGIC_EVENT_FORMAT") {
+            match EventFormat::from_str(&format) {
+                Ok(format) => config.event_format = format,
+                Err(e) => log::warn!("Ignoring MAINLOGIC_EVENT_FORMAT: {}", e),
+            }
+        }
+        if let Ok(workers) = env::var("MAINLOGIC_WORKER_THREADS") {
+            match workers.parse::<usize>() {
+                Ok(workers) if workers > 0 => config.pool.workers = workers,
+                _ => log::warn!("Ignoring invalid MAINLOGIC_WORKER_THREADS: {}", workers),
+            }
+        }
+        if let Ok(capacity) = env::var("MAINLOGIC_WORKER_QUEUE_CAPACITY") {
+            match capacity.parse::<usize>() {
+                Ok(capacity) if capacity > 0 => config.pool.queue_capacity = capacity,
+                _ => log::warn!("Ignoring invalid MAINLOGIC_WORKER_QUEUE_CAPACITY: {}", capacity),
+            }
+        }
+        if let Ok(policy) = env::var("MAINLOGIC_WORKER_REJECTION") {
+            match RejectionPolicy::from_str(&policy) {
+                Ok(policy) => config.pool.rejection_policy = policy,
+                Err(e) => log::warn!("Ignoring MAINLOGIC_WORKER_REJECTION: {}", e),
+            }
+        }
+        if let Ok(backend) = env::var("MAINLOGIC_SENSOR_BACKEND") {
+            match SensorBackend::from_str(&backend) {
+                Ok(backend) => config.sensor_backend = backend,
+                Err(e) => log::warn!("Ignoring MAINLOGIC_SENSOR_BACKEND: {}", e),
+            }
+        }
+        if let Ok(speed) = env::var("MAINLOGIC_SENSOR_REPLAY_SPEED") {
+            match (&mut config.sensor_backend, speed.parse::<f32>()) {
+                (SensorBackend::Replay(replay), Ok(value)) if value >= 0.0 => replay.speed = value,
+                (SensorBackend::Replay(_), _) => {
+                    log::warn!("Ignoring invalid MAINLOGIC_SENSOR_REPLAY_SPEED: {}", speed)
+                }
+                _ => log::warn!("Ignoring MAINLOGIC_SENSOR_REPLAY_SPEED: the sensor backend is not replay"),
+            }
+        }
+        if let Ok(store) = env::var("MAINLOGIC_NOTIFICATION_STORE") {
+            match StoreConfig::from_str(&store) {
+                Ok(store) => config.notification_store = store,
+                Err(e) => log::warn!("Ignoring MAINLOGIC_NOTIFICATION_STORE: {}", e),
+            }
+        }
+
+        config
+    }
+}
@@ -0,0 +1,110 @@
+use std::any::Any;
+use std::fmt;
+use std::io;
+use std::sync::PoisonError;
+
+use crate::runtime::RuntimeError;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Crate-wide error type. Every JNI entry point reports failures through this
+/// so they can be rethrown on the Java side as a matching exception.
+#[derive(Debug)]
+pub enum Error {
+    /// The runtime or one of its subsystems is not ready yet.
+    Runtime(RuntimeError),
+    /// Java passed something we cannot use (null string, out of range value, ...).
+    InvalidArgument(String),
+    /// A JNI call failed.
+    Jni(jni::errors::Error),
+    /// A Rust invariant broke, e.g. a poisoned lock.
+    Internal(String),
+    /// Rust code panicked inside a JNI call.
+    Panic(String),
+    /// A task stopped because it was cancelled.
+    Cancelled,
+    /// The worker pool refused a job because its queue is full.
+    Rejected(String),
+    /// Reading or writing a file failed.
+    Io(io::Error),
+    /// A SQLite statement failed.
+    Database(rusqlite::Error),
+}
+
+impl Error {
+    /// Fully-qualified JNI name of the exception class thrown for this error.
+    pub fn java_exception_class(&self) -> &'static str {
+        match self {
+            Error::Runtime(_) => "java/lang/IllegalStateException",
+            Error::InvalidArgument(_) => "java/lang/IllegalArgumentException",
+            Error::Jni(_) | Error::Internal(_) | Error::Panic(_) => "java/lang/RuntimeException",
+            Error::Cancelled => "java/util/concurrent/CancellationException",
+            Error::Rejected(_) => "java/util/concurrent/RejectedExecutionException",
+            Error::Io(_) | Error::Database(_) => "java/io/IOException",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Runtime(e) => write!(f, "{}", e),
+            Error::InvalidArgument(msg) => write!(f, "Invalid argument: {}", msg),
+            Error::Jni(e) => write!(f, "JNI error: {}", e),
+            Error::Internal(msg) => write!(f, "Internal error: {}", msg),
+            Error::Panic(msg) => write!(f, "Rust panic: {}", msg),
+            Error::Cancelled => write!(f, "Cancelled"),
+            Error::Rejected(msg) => write!(f, "Rejected: {}", msg),
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::Database(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Text of a panic payload caught with `catch_unwind`.
+pub(crate) fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+impl From<RuntimeError> for Error {
+    fn from(e: RuntimeError) -> Self {
+        Error::Runtime(e)
+    }
+}
+
+impl From<jni::errors::Error> for Error {
+    fn from(e: jni::errors::Error) -> Self {
+        match e {
+            jni::errors::Error::NullPtr(what) | jni::errors::Error::NullDeref(what) => {
+                Error::InvalidArgument(format!("unexpected null: {}", what))
+            }
+            e => Error::Jni(e),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(e: rusqlite::Error) -> Self {
+        Error::Database(e)
+    }
+}
+
+impl<T> From<PoisonError<T>> for Error {
+    fn from(_: PoisonError<T>) -> Self {
+        Error::Internal("lock poisoned".to_string())
+    }
+}
@@ -0,0 +1,269 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+
+use crate::callbacks::JavaCallbacks;
+use crate::error::{Error, Result};
+use crate::notifications::channels::NotificationChannel;
+use crate::notifications::NotificationData;
+use crate::scheduler::JobId;
+use crate::sensors::fusion::FusionOutput;
+use crate::sensors::gestures::GestureEvent;
+use crate::sensors::SensorEvent;
+use crate::tasks::{TaskId, TaskState};
+use crate::threads;
+
+/// Bumped whenever a field is renamed or removed. Adding fields or event
+/// types does not change it; Kotlin must ignore what it does not know.
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// Most events delivered in a single `onRustEvents` call.
+const MAX_BATCH_SIZE: usize = 64;
+/// How long the dispatcher waits for more events before flushing a batch.
+const BATCH_WINDOW: Duration = Duration::from_millis(16);
+/// How often an idle dispatcher checks for shutdown.
+const IDLE_POLL: Duration = Duration::from_millis(250);
+
+/// Everything Rust reports to Kotlin. Serialized internally tagged, e.g.
+/// `{"type":"status","message":"ready"}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// Serialized flat, e.g. `{"type":"sensor_sample","sensor":"gyroscope","x":0.1,...}`.
+    SensorSample(SensorEvent),
+    /// Fused orientation, serialized flat like `SensorSample`.
+    Orientation(FusionOutput),
+    /// A detected gesture, flat with its `gesture` tag, e.g.
+    /// `{"type":"gesture","gesture":"step","count":3,"timestamp_ns":...}`.
+    Gesture(GestureEvent),
+    /// Posted, or updated under the same id. Serialized flat, e.g.
+    /// `{"type":"notification","id":3,"channel":"general","title":...,"actions":[...]}`;
+    /// `style`, `progress`, `actions` and `deep_link` are left out when unset.
+    Notification(NotificationData),
+    /// A channel was declared or changed; Kotlin creates it on Android.
+    /// Serialized flat, e.g. `{"type":"notification_channel","id":"general","name":"General",...}`.
+    NotificationChannel(NotificationChannel),
+    TaskProgress {
+        task_id: TaskId,
+        name: String,
+        percent: u8,
+        message: String,
+    },
+    /// Sent on every transition. `message` carries the result of a
+    /// succeeded task or the error of a failed one.
+    TaskState {
+        task_id: TaskId,
+        name: String,
+        state: TaskState,
+        message: Option<String>,
+    },
+    /// A scheduled job fired. `run` counts from 1.
+    JobRun {
+        job_id: JobId,
+        name: String,
+        run: u64,
+    },
+    Status {
+        message: String,
+    },
+    /// A reply published on the `bus`.
+    Message {
+        topic: String,
+        payload: String,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EventBatch {
+    pub version: u32,
+    pub events: Vec<Event>,
+}
+
+/// Wire encoding of an `EventBatch`. Both are self-describing and never
+/// start with the same byte: JSON batches start with `{`, MessagePack maps
+/// with a byte in `0x80..=0x8f` or `0xde`/`0xdf`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventFormat {
+    Json,
+    MessagePack,
+}
+
+impl FromStr for EventFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(EventFormat::Json),
+            "msgpack" | "messagepack" => Ok(EventFormat::MessagePack),
+            other => Err(Error::InvalidArgument(format!("unknown event format: {}", other))),
+        }
+    }
+}
+
+/// Encodes a batch in `format`; MessagePack keeps the JSON field names.
+pub fn encode_batch(events: Vec<Event>, format: EventFormat) -> Result<Vec<u8>> {
+    let batch = EventBatch {
+        version: SCHEMA_VERSION,
+        events,
+    };
+    match format {
+        EventFormat::Json => serde_json::to_vec(&batch)
+            .map_err(|e| Error::Internal(format!("failed to encode events: {}", e))),
+        EventFormat::MessagePack => rmp_serde::to_vec_named(&batch)
+            .map_err(|e| Error::Internal(format!("failed to encode events: {}", e))),
+    }
+}
+
+/// Cheap, cloneable handle used by every subsystem to publish events.
+#[derive(Clone)]
+pub struct EventSender(Sender<Event>);
+
+impl EventSender {
+    pub fn send(&self, event: Event) {
+        if self.0.send(event).is_err() {
+            log::warn!("Event dropped: dispatcher stopped");
+        }
+    }
+}
+
+pub fn channel() -> (EventSender, Receiver<Event>) {
+    let (sender, receiver) = mpsc::channel();
+    (EventSender(sender), receiver)
+}
+
+/// Starts the thread that batches events and hands them to
+/// `onRustEvents(byte[])`.
+pub fn start_dispatcher(
+    receiver: Receiver<Event>,
+    callbacks: &'static JavaCallbacks,
+    format: EventFormat,
+) -> std::io::Result<()> {
+    threads::spawn("event-dispatcher", move || {
+        while let Some(batch) = next_batch(&receiver) {
+            let count = batch.len();
+            let result = encode_batch(batch, format)
+                .and_then(|bytes| callbacks.on_rust_events(&bytes));
+            if let Err(e) = result {
+                log::error!("Failed to deliver {} events: {}", count, e);
+            }
+        }
+    })?;
+    Ok(())
+}
+
+/// Blocks for the first event, then collects whatever else arrives within
+/// `BATCH_WINDOW`. Returns `None` once the crate shuts down or every sender
+/// is gone.
+fn next_batch(receiver: &Receiver<Event>) -> Option<Vec<Event>> {
+    let first = loop {
+        if threads::is_shutting_down() {
+            return None;
+        }
+        match receiver.recv_timeout(IDLE_POLL) {
+            Ok(event) => break event,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => return None,
+        }
+    };
+
+    let mut batch = vec![first];
+    let deadline = Instant::now() + BATCH_WINDOW;
+    while batch.len() < MAX_BATCH_SIZE {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match receiver.recv_timeout(remaining) {
+            Ok(event) => batch.push(event),
+            Err(_) => break,
+        }
+    }
+    Some(batch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensors::{SensorAccuracy, SensorKind};
+
+    fn status(message: &str) -> Event {
+        Event::Status {
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn encodes_versioned_json_batches() {
+        let bytes = encode_batch(vec![status("ready")], EventFormat::Json).unwrap();
+        assert_eq!(
+            String::from_utf8(bytes.clone()).unwrap(),
+            format!(r#"{{"version":{},"events":[{{"type":"status","message":"ready"}}]}}"#, SCHEMA_VERSION)
+        );
+        let batch: EventBatch = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(batch.events, vec![status("ready")]);
+
+        let task = Event::TaskState {
+            task_id: TaskId(7),
+            name: "upload".to_string(),
+            state: TaskState::Failed,
+            message: Some("boom".to_string()),
+        };
+        let json: serde_json::Value = serde_json::from_slice(&encode_batch(vec![task], EventFormat::Json).unwrap()).unwrap();
+        assert_eq!(json["events"][0]["type"], "task_state");
+        assert_eq!(json["events"][0]["task_id"], 7);
+        assert_eq!(json["events"][0]["state"], "failed");
+    }
+
+    #[test]
+    fn both_formats_round_trip() {
+        let events = vec![
+            status("ready"),
+            Event::SensorSample(SensorEvent {
+                sensor: SensorKind::Gyroscope,
+                x: 0.125,
+                y: -3.5,
+                z: 9.81,
+                accuracy: SensorAccuracy::High,
+                timestamp_ns: u64::MAX - 1,
+            }),
+            Event::TaskState {
+                task_id: TaskId(7),
+                name: "upload".to_string(),
+                state: TaskState::Failed,
+                message: None,
+            },
+        ];
+        let json = encode_batch(events.clone(), EventFormat::Json).unwrap();
+        let msgpack = encode_batch(events.clone(), EventFormat::MessagePack).unwrap();
+        assert_eq!(json[0], b'{');
+        assert!(matches!(msgpack[0], 0x80..=0x8f | 0xde | 0xdf), "{:#x}", msgpack[0]);
+        assert!(msgpack.len() < json.len());
+
+        let from_json: EventBatch = serde_json::from_slice(&json).unwrap();
+        let from_msgpack: EventBatch = rmp_serde::from_slice(&msgpack).unwrap();
+        for batch in [from_json, from_msgpack] {
+            assert_eq!(batch.version, SCHEMA_VERSION);
+            assert_eq!(batch.events, events);
+        }
+    }
+
+    #[test]
+    fn parses_event_formats() {
+        assert_eq!("JSON".parse::<EventFormat>().unwrap(), EventFormat::Json);
+        assert_eq!("msgpack".parse::<EventFormat>().unwrap(), EventFormat::MessagePack);
+        assert!("cbor".parse::<EventFormat>().is_err());
+    }
+
+    #[test]
+    fn splits_batches_at_the_size_limit() {
+        let (sender, receiver) = channel();
+        for i in 0..MAX_BATCH_SIZE * 2 + 5 {
+            sender.send(status(&i.to_string()));
+        }
+        let batches: Vec<Vec<Event>> = (0..3).map(|_| next_batch(&receiver).unwrap()).collect();
+        let sizes: Vec<usize> = batches.iter().map(Vec::len).collect();
+        assert_eq!(sizes, vec![MAX_BATCH_SIZE, MAX_BATCH_SIZE, 5]);
+        assert_eq!(batches[1][0], status(&MAX_BATCH_SIZE.to_string()));
+
+        drop(sender);
+        assert!(next_batch(&receiver).is_none());
+    }
+}
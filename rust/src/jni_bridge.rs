@@ -1,216 +1,958 @@
 use jni::{
     objects::{JClass, JString},
-    JNIEnv, JavaVM,
+    sys::{jboolean, jfloat, jfloatArray, jint, jlong, jstring, JNI_FALSE, JNI_TRUE},
+    JNIEnv, NativeMethod,
 };
-use std::sync::{Arc, Mutex};
-use std::thread;
+use std::ffi::c_void;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::time::Duration;
 
 use crate::async_worker::AsyncWorker;
+use crate::bus::Message;
+use crate::events::Event;
+use crate::error::{panic_message, Error, Result};
 use crate::native_activity::NativeActivity;
+use crate::notifications::channels::NotificationChannel;
+use crate::notifications::content::NotificationContent;
+use crate::notifications::schedule::{ScheduleId, ScheduleRequest};
+use crate::notifications::store::StoreConfig;
+use crate::pool::Priority;
+use crate::runtime::{self, RuntimeError};
+use crate::scheduler::{JobId, JobRun, Schedule};
+use crate::sensors::filters::FilterConfig;
+use crate::sensors::fusion::FusionConfig;
+use crate::sensors::gestures::GestureConfig;
+use crate::sensors::rules::{RuleId, RuleSpec};
+use crate::sensors::recording::RecordingFormat;
+use crate::sensors::{SensorBackend, SensorConfig, SensorKind};
+use crate::tasks::TaskId;
 use crate::utils;
 
-static mut JAVA_VM: Option<Arc<Mutex<JavaVM>>> = None;
-static mut NATIVE_ACTIVITY: Option<Arc<Mutex<NativeActivity>>> = None;
+/// Value handed back to Java when a call fails and an exception is pending.
+/// Java ignores the return value in that case, it only has to be well-formed.
+pub trait JniDefault {
+    fn jni_default() -> Self;
+}
+
+impl JniDefault for () {
+    fn jni_default() -> Self {}
+}
+
+impl JniDefault for jint {
+    fn jni_default() -> Self {
+        0
+    }
+}
+
+impl JniDefault for jlong {
+    fn jni_default() -> Self {
+        0
+    }
+}
+
+impl JniDefault for jboolean {
+    fn jni_default() -> Self {
+        JNI_FALSE
+    }
+}
+
+impl JniDefault for jstring {
+    fn jni_default() -> Self {
+        std::ptr::null_mut()
+    }
+}
+
+/// Runs the body of a JNI entry point. Errors and panics are turned into a
+/// Java exception (see `Error::java_exception_class`) and the default value
+/// for the return type is returned instead.
+pub fn jni_call<'local, T, F>(env: &mut JNIEnv<'local>, name: &str, f: F) -> T
+where
+    T: JniDefault,
+    F: FnOnce(&mut JNIEnv<'local>) -> Result<T>,
+{
+    let error = match panic::catch_unwind(AssertUnwindSafe(|| f(env))) {
+        Ok(Ok(value)) => return value,
+        Ok(Err(e)) => e,
+        Err(payload) => Error::Panic(panic_message(payload)),
+    };
+
+    log::error!("Rust: {} failed: {}", name, error);
+    throw_error(env, &error);
+    T::jni_default()
+}
+
+fn throw_error(env: &mut JNIEnv, error: &Error) {
+    // A failed JNI call may already have left a Java exception pending, which
+    // is more precise than anything we could throw here.
+    if env.exception_check().unwrap_or(false) {
+        return;
+    }
+    if let Err(e) = env.throw_new(error.java_exception_class(), error.to_string()) {
+        log::error!("Failed to throw {}: {}", error.java_exception_class(), e);
+    }
+}
+
+fn get_string(env: &mut JNIEnv, value: &JString) -> Result<String> {
+    Ok(env.get_string(value)?.into())
+}
 
-#[no_mangle]
-pub extern "C" fn Java_com_example_modularandroidapp_RustBridge_rustSum(
-    _env: JNIEnv,
+fn new_string(env: &mut JNIEnv, value: &str) -> Result<jstring> {
+    Ok(env.new_string(value)?.into_raw())
+}
+
+/// One row of the `RegisterNatives` table. `arity` is the number of Java
+/// parameters the Rust function takes after `JNIEnv` and the class.
+pub struct NativeMethodSpec {
+    pub name: &'static str,
+    pub sig: &'static str,
+    pub fn_ptr: *mut c_void,
+    pub arity: usize,
+}
+
+/// Builds a `NativeMethodSpec`. The Rust parameter types are spelled out so
+/// the cast fails to compile if they drift from the function definition.
+macro_rules! native_method {
+    ($name:literal, $sig:literal, $func:ident($($arg:ty),*) $(-> $ret:ty)?) => {
+        NativeMethodSpec {
+            name: $name,
+            sig: $sig,
+            fn_ptr: ($func as extern "system" fn(JNIEnv, JClass $(, $arg)*) $(-> $ret)?) as *mut c_void,
+            arity: <[&str]>::len(&[$(stringify!($arg)),*]),
+        }
+    };
+}
+
+/// Every native method exposed to the bridge class.
+pub fn native_methods() -> Vec<NativeMethodSpec> {
+    vec![
+        native_method!("rustSum", "(II)I", rust_sum(jint, jint) -> jint),
+        native_method!(
+            "rustNotifyJava",
+            "(Ljava/lang/String;Ljava/lang/String;)I",
+            rust_notify_java(JString, JString) -> jint
+        ),
+        native_method!("rustAsyncCallback", "(J)J", rust_async_callback(jlong) -> jlong),
+        native_method!("startPeriodicTask", "(JI)J", start_periodic_task(jlong, jint) -> jlong),
+        native_method!("startLongRunningTask", "()J", start_long_running_task() -> jlong),
+        native_method!("cancelTask", "(J)Z", cancel_task(jlong) -> jboolean),
+        native_method!("getTaskState", "(J)Ljava/lang/String;", get_task_state(jlong) -> jstring),
+        native_method!("scheduleOnce", "(Ljava/lang/String;J)J", schedule_once(JString, jlong) -> jlong),
+        native_method!(
+            "scheduleFixedRate",
+            "(Ljava/lang/String;JJI)J",
+            schedule_fixed_rate(JString, jlong, jlong, jint) -> jlong
+        ),
+        native_method!(
+            "scheduleFixedDelay",
+            "(Ljava/lang/String;JJI)J",
+            schedule_fixed_delay(JString, jlong, jlong, jint) -> jlong
+        ),
+        native_method!(
+            "scheduleCron",
+            "(Ljava/lang/String;Ljava/lang/String;)J",
+            schedule_cron(JString, JString) -> jlong
+        ),
+        native_method!("pauseJob", "(J)Z", pause_job(jlong) -> jboolean),
+        native_method!("resumeJob", "(J)Z", resume_job(jlong) -> jboolean),
+        native_method!("cancelJob", "(J)Z", cancel_job(jlong) -> jboolean),
+        native_method!("getJobState", "(J)Ljava/lang/String;", get_job_state(jlong) -> jstring),
+        native_method!("getWorkerPoolMetrics", "()Ljava/lang/String;", get_worker_pool_metrics() -> jstring),
+        native_method!("rustGetVersion", "()Ljava/lang/String;", rust_get_version() -> jstring),
+        native_method!(
+            "rustProcessData",
+            "(Ljava/lang/String;)Ljava/lang/String;",
+            rust_process_data(JString) -> jstring
+        ),
+        native_method!("initializeNativeActivity", "()Z", initialize_native_activity() -> jboolean),
+        native_method!("sendTestNotification", "()V", send_test_notification()),
+        native_method!("postNotification", "(Ljava/lang/String;)I", post_notification(JString) -> jint),
+        native_method!(
+            "getNotificationStats",
+            "()Ljava/lang/String;",
+            get_notification_stats() -> jstring
+        ),
+        native_method!(
+            "onNotificationAction",
+            "(ILjava/lang/String;)Z",
+            on_notification_action(jint, JString) -> jboolean
+        ),
+        native_method!("onNotificationDismissed", "(I)Z", on_notification_dismissed(jint) -> jboolean),
+        native_method!(
+            "registerNotificationChannel",
+            "(Ljava/lang/String;)V",
+            register_notification_channel(JString)
+        ),
+        native_method!(
+            "getNotificationChannels",
+            "()Ljava/lang/String;",
+            get_notification_channels() -> jstring
+        ),
+        native_method!("setNotificationStore", "(Ljava/lang/String;)V", set_notification_store(JString)),
+        native_method!(
+            "getNotificationHistory",
+            "()Ljava/lang/String;",
+            get_notification_history() -> jstring
+        ),
+        native_method!(
+            "scheduleNotification",
+            "(Ljava/lang/String;)J",
+            schedule_notification(JString) -> jlong
+        ),
+        native_method!("cancelScheduledNotification", "(J)Z", cancel_scheduled_notification(jlong) -> jboolean),
+        native_method!(
+            "listScheduledNotifications",
+            "()Ljava/lang/String;",
+            list_scheduled_notifications() -> jstring
+        ),
+        native_method!("updateStatus", "(Ljava/lang/String;)V", update_status(JString)),
+        native_method!("getSensorData", "()Ljava/lang/String;", get_sensor_data() -> jstring),
+        native_method!("startSensors", "(Ljava/lang/String;)V", start_sensors(JString)),
+        native_method!("stopSensors", "()V", stop_sensors()),
+        native_method!("setSensorFilters", "(Ljava/lang/String;)V", set_sensor_filters(JString)),
+        native_method!("setSensorBackend", "(Ljava/lang/String;F)V", set_sensor_backend(JString, jfloat)),
+        native_method!(
+            "startSensorRecording",
+            "(Ljava/lang/String;Ljava/lang/String;)V",
+            start_sensor_recording(JString, JString)
+        ),
+        native_method!("stopSensorRecording", "()J", stop_sensor_recording() -> jlong),
+        native_method!("addSensorRule", "(Ljava/lang/String;)J", add_sensor_rule(JString) -> jlong),
+        native_method!("removeSensorRule", "(J)Z", remove_sensor_rule(jlong) -> jboolean),
+        native_method!("listSensorRules", "()Ljava/lang/String;", list_sensor_rules() -> jstring),
+        native_method!("setSensorHistoryWindow", "(J)V", set_sensor_history_window(jlong)),
+        native_method!(
+            "getSensorHistory",
+            "(Ljava/lang/String;J)[F",
+            get_sensor_history(JString, jlong) -> jfloatArray
+        ),
+        native_method!(
+            "getSensorStats",
+            "(Ljava/lang/String;J)Ljava/lang/String;",
+            get_sensor_stats(JString, jlong) -> jstring
+        ),
+        native_method!("getOrientation", "()Ljava/lang/String;", get_orientation() -> jstring),
+        native_method!("setFusionConfig", "(Ljava/lang/String;)V", set_fusion_config(JString)),
+        native_method!("setGestureConfig", "(Ljava/lang/String;)V", set_gesture_config(JString)),
+        native_method!("onActivityPause", "()V", on_activity_pause()),
+        native_method!("onActivityResume", "()V", on_activity_resume()),
+    ]
+}
+
+/// Registers `native_methods()` on `class_name` (JNI form, e.g.
+/// `com/example/modularandroidapp/RustBridge`).
+pub fn register_natives(env: &mut JNIEnv, class_name: &str) -> Result<()> {
+    let methods: Vec<NativeMethod> = native_methods()
+        .into_iter()
+        .map(|method| NativeMethod {
+            name: method.name.into(),
+            sig: method.sig.into(),
+            fn_ptr: method.fn_ptr,
+        })
+        .collect();
+
+    env.register_native_methods(class_name, &methods)?;
+    log::info!("Registered {} native methods on {}", methods.len(), class_name);
+    Ok(())
+}
+
+extern "system" fn rust_sum(
+    mut env: JNIEnv,
     _class: JClass,
-    a: jni::sys::jint,
-    b: jni::sys::jint,
-) -> jni::sys::jint {
-    log::info!("Rust: rustSum called with {} + {}", a, b);
-    a + b
+    a: jint,
+    b: jint,
+) -> jint {
+    jni_call(&mut env, "rustSum", |_env| {
+        log::info!("Rust: rustSum called with {} + {}", a, b);
+        Ok(a + b)
+    })
 }
 
-#[no_mangle]
-pub extern "C" fn Java_com_example_modularandroidapp_RustBridge_rustNotifyJava(
+extern "system" fn rust_notify_java(
     mut env: JNIEnv,
     _class: JClass,
-    message: JString,
-) {
-    let message_str: String = env.get_string(&message).unwrap().into();
-    log::info!("Rust: rustNotifyJava called with message: {}", message_str);
-    
-    // Call back to Java
-    if let Ok(class) = env.find_class("com/example/modularandroidapp/RustBridge") {
-        if let Ok(java_message) = env.new_string(&message_str) {
-            let _ = env.call_static_method(
-                class,
-                "onRustEvent",
-                "(Ljava/lang/String;Ljava/lang/String;)V",
-                &[(&java_message).into(), (&java_message).into()],
-            );
+    topic: JString,
+    payload: JString,
+) -> jint {
+    jni_call(&mut env, "rustNotifyJava", |env| {
+        let topic = get_string(env, &topic)?;
+        let payload = get_string(env, &payload)?;
+        log::info!("Rust: rustNotifyJava called on topic {}: {}", topic, payload);
+        if topic.is_empty() {
+            return Err(Error::InvalidArgument("empty topic".to_string()));
         }
-    }
+
+        // Subscribers answer Kotlin asynchronously through onRustEvents
+        let delivered = runtime::get()?.bus().publish(Message::new(topic, payload));
+        Ok(delivered as jint)
+    })
 }
 
-#[no_mangle]
-pub extern "C" fn Java_com_example_modularandroidapp_RustBridge_rustAsyncCallback(
-    env: JNIEnv,
+extern "system" fn rust_async_callback(
+    mut env: JNIEnv,
     _class: JClass,
-    delay_ms: jni::sys::jlong,
-) {
-    log::info!("Rust: rustAsyncCallback called with delay: {}ms", delay_ms);
-    
-    // Store JavaVM for use in background thread
-    unsafe {
-        JAVA_VM = Some(Arc::new(Mutex::new(env.get_java_vm().unwrap())));
+    delay_ms: jlong,
+) -> jlong {
+    jni_call(&mut env, "rustAsyncCallback", |_env| {
+        log::info!("Rust: rustAsyncCallback called with delay: {}ms", delay_ms);
+        if delay_ms < 0 {
+            return Err(Error::InvalidArgument(format!("negative delay: {}ms", delay_ms)));
+        }
+
+        let id = runtime::get()?
+            .tasks()
+            .spawn("async", Priority::Normal, move |context| AsyncWorker::run_async_task(context, delay_ms))?;
+        Ok(id.0 as jlong)
+    })
+}
+
+extern "system" fn start_periodic_task(
+    mut env: JNIEnv,
+    _class: JClass,
+    interval_ms: jlong,
+    count: jint,
+) -> jlong {
+    jni_call(&mut env, "startPeriodicTask", |_env| {
+        log::info!("Rust: startPeriodicTask called with interval: {}ms, count: {}", interval_ms, count);
+        if interval_ms <= 0 || count <= 0 {
+            return Err(Error::InvalidArgument(format!(
+                "invalid periodic task: interval {}ms, count {}",
+                interval_ms, count
+            )));
+        }
+
+        let interval = Duration::from_millis(interval_ms as u64);
+        let schedule = Schedule::FixedRate {
+            initial_delay: interval,
+            period: interval,
+        };
+        let id = runtime::get()?.scheduler().schedule("periodic", schedule, Some(count as u64), move |run| {
+            log::info!("AsyncWorker: Periodic task iteration {}/{}", run.run, count);
+            report_job_run(run);
+        })?;
+        Ok(id.0 as jlong)
+    })
+}
+
+extern "system" fn start_long_running_task(mut env: JNIEnv, _class: JClass) -> jlong {
+    jni_call(&mut env, "startLongRunningTask", |_env| {
+        log::info!("Rust: startLongRunningTask called");
+
+        // Shown as a progress notification once the native activity exists
+        let runtime = runtime::get()?;
+        let notifications = match runtime.native_activity() {
+            Ok(activity) => Some(activity.lock()?.notifications()?),
+            Err(_) => None,
+        };
+        let id = runtime.tasks().spawn("long_running", Priority::Background, move |context| {
+            AsyncWorker::run_long_running_task(context, notifications.as_ref())
+        })?;
+        Ok(id.0 as jlong)
+    })
+}
+
+extern "system" fn cancel_task(mut env: JNIEnv, _class: JClass, task_id: jlong) -> jboolean {
+    jni_call(&mut env, "cancelTask", |_env| {
+        log::info!("Rust: cancelTask called for task {}", task_id);
+
+        let cancelled = runtime::get()?.tasks().cancel(TaskId(task_id as u64));
+        Ok(if cancelled { JNI_TRUE } else { JNI_FALSE })
+    })
+}
+
+extern "system" fn get_task_state(mut env: JNIEnv, _class: JClass, task_id: jlong) -> jstring {
+    jni_call(&mut env, "getTaskState", |env| {
+        let info = runtime::get()?
+            .tasks()
+            .info(TaskId(task_id as u64))
+            .ok_or_else(|| Error::InvalidArgument(format!("unknown task id: {}", task_id)))?;
+        new_string(env, info.state.as_str())
+    })
+}
+
+/// Job callback for jobs scheduled from Kotlin: every run is reported as
+/// `Event::JobRun`.
+fn report_job_run(run: &JobRun) {
+    if let Ok(runtime) = runtime::get() {
+        runtime.events().send(Event::JobRun {
+            job_id: run.id,
+            name: run.name.clone(),
+            run: run.run,
+        });
+    }
+}
+
+/// Converts a Java `maxRuns` argument: zero or less means unlimited.
+fn max_runs(value: jint) -> Option<u64> {
+    (value > 0).then_some(value as u64)
+}
+
+fn millis(name: &str, value: jlong) -> Result<Duration> {
+    if value < 0 {
+        return Err(Error::InvalidArgument(format!("negative {}: {}ms", name, value)));
     }
-    
-    // Spawn background thread
-    let java_vm = unsafe { JAVA_VM.as_ref().unwrap().clone() };
-    thread::spawn(move || {
-        AsyncWorker::run_async_task(java_vm, delay_ms);
-    });
+    Ok(Duration::from_millis(value as u64))
+}
+
+fn schedule_job(env: &mut JNIEnv, name: &JString, schedule: Schedule, max_runs: Option<u64>) -> Result<jlong> {
+    let name = get_string(env, name)?;
+    let id = runtime::get()?.scheduler().schedule(&name, schedule, max_runs, report_job_run)?;
+    Ok(id.0 as jlong)
 }
 
-#[no_mangle]
-pub extern "C" fn Java_com_example_modularandroidapp_RustBridge_rustGetVersion(
+extern "system" fn schedule_once(mut env: JNIEnv, _class: JClass, name: JString, delay_ms: jlong) -> jlong {
+    jni_call(&mut env, "scheduleOnce", |env| {
+        let schedule = Schedule::Once {
+            delay: millis("delay", delay_ms)?,
+        };
+        schedule_job(env, &name, schedule, None)
+    })
+}
+
+extern "system" fn schedule_fixed_rate(
     mut env: JNIEnv,
     _class: JClass,
-) -> jni::sys::jstring {
-    let version = "1.0.0";
-    log::info!("Rust: rustGetVersion called, returning: {}", version);
-    env.new_string(version).unwrap().into_raw()
+    name: JString,
+    initial_delay_ms: jlong,
+    period_ms: jlong,
+    max_run_count: jint,
+) -> jlong {
+    jni_call(&mut env, "scheduleFixedRate", |env| {
+        let schedule = Schedule::FixedRate {
+            initial_delay: millis("initial delay", initial_delay_ms)?,
+            period: millis("period", period_ms)?,
+        };
+        schedule_job(env, &name, schedule, max_runs(max_run_count))
+    })
 }
 
-#[no_mangle]
-pub extern "C" fn Java_com_example_modularandroidapp_RustBridge_rustProcessData(
+extern "system" fn schedule_fixed_delay(
+    mut env: JNIEnv,
+    _class: JClass,
+    name: JString,
+    initial_delay_ms: jlong,
+    delay_ms: jlong,
+    max_run_count: jint,
+) -> jlong {
+    jni_call(&mut env, "scheduleFixedDelay", |env| {
+        let schedule = Schedule::FixedDelay {
+            initial_delay: millis("initial delay", initial_delay_ms)?,
+            delay: millis("delay", delay_ms)?,
+        };
+        schedule_job(env, &name, schedule, max_runs(max_run_count))
+    })
+}
+
+extern "system" fn schedule_cron(mut env: JNIEnv, _class: JClass, name: JString, expression: JString) -> jlong {
+    jni_call(&mut env, "scheduleCron", |env| {
+        let expression = get_string(env, &expression)?;
+        schedule_job(env, &name, Schedule::cron(&expression)?, None)
+    })
+}
+
+extern "system" fn pause_job(mut env: JNIEnv, _class: JClass, job_id: jlong) -> jboolean {
+    jni_call(&mut env, "pauseJob", |_env| {
+        let paused = runtime::get()?.scheduler().pause(JobId(job_id as u64));
+        Ok(if paused { JNI_TRUE } else { JNI_FALSE })
+    })
+}
+
+extern "system" fn resume_job(mut env: JNIEnv, _class: JClass, job_id: jlong) -> jboolean {
+    jni_call(&mut env, "resumeJob", |_env| {
+        let resumed = runtime::get()?.scheduler().resume(JobId(job_id as u64));
+        Ok(if resumed { JNI_TRUE } else { JNI_FALSE })
+    })
+}
+
+extern "system" fn cancel_job(mut env: JNIEnv, _class: JClass, job_id: jlong) -> jboolean {
+    jni_call(&mut env, "cancelJob", |_env| {
+        let cancelled = runtime::get()?.scheduler().cancel(JobId(job_id as u64));
+        Ok(if cancelled { JNI_TRUE } else { JNI_FALSE })
+    })
+}
+
+extern "system" fn get_job_state(mut env: JNIEnv, _class: JClass, job_id: jlong) -> jstring {
+    jni_call(&mut env, "getJobState", |env| {
+        let info = runtime::get()?
+            .scheduler()
+            .info(JobId(job_id as u64))
+            .ok_or_else(|| Error::InvalidArgument(format!("unknown job id: {}", job_id)))?;
+        new_string(env, info.state.as_str())
+    })
+}
+
+extern "system" fn get_worker_pool_metrics(mut env: JNIEnv, _class: JClass) -> jstring {
+    jni_call(&mut env, "getWorkerPoolMetrics", |env| {
+        let metrics = runtime::get()?.pool().metrics();
+        let json = serde_json::to_string(&metrics)
+            .map_err(|e| Error::Internal(format!("failed to encode pool metrics: {}", e)))?;
+        new_string(env, &json)
+    })
+}
+
+extern "system" fn rust_get_version(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    jni_call(&mut env, "rustGetVersion", |env| {
+        let version = "1.0.0";
+        log::info!("Rust: rustGetVersion called, returning: {}", version);
+        new_string(env, version)
+    })
+}
+
+extern "system" fn rust_process_data(
     mut env: JNIEnv,
     _class: JClass,
     input: JString,
-) -> jni::sys::jstring {
-    let input_str: String = env.get_string(&input).unwrap().into();
-    log::info!("Rust: rustProcessData called with input: {}", input_str);
-    
-    // Process the input data
-    let processed = utils::process_input_data(&input_str);
-    
-    env.new_string(&processed).unwrap().into_raw()
+) -> jstring {
+    jni_call(&mut env, "rustProcessData", |env| {
+        let input_str = get_string(env, &input)?;
+        log::info!("Rust: rustProcessData called with input: {}", input_str);
+
+        // Process the input data
+        let processed = utils::process_input_data(&input_str);
+
+        new_string(env, &processed)
+    })
 }
 
 // New functions for native activity
 
-#[no_mangle]
-pub extern "C" fn Java_com_example_modularandroidapp_RustBridge_initializeNativeActivity(
-    env: JNIEnv,
+extern "system" fn initialize_native_activity(
+    mut env: JNIEnv,
     _class: JClass,
-) -> jni::sys::jboolean {
-    log::info!("Rust: initializeNativeActivity called");
-    
-    let java_vm = match env.get_java_vm() {
-        Ok(vm) => vm,
-        Err(e) => {
-            log::error!("Failed to get JavaVM: {}", e);
-            return 0;
-        }
-    };
-    
-    match NativeActivity::new(java_vm) {
-        Ok(native_activity) => {
-            unsafe {
-                NATIVE_ACTIVITY = Some(Arc::new(Mutex::new(native_activity)));
-            }
-            
-            // Initialize the native activity
-            if let Some(activity_guard) = unsafe { NATIVE_ACTIVITY.as_ref() } {
-                if let Ok(activity) = activity_guard.lock() {
-                    if let Err(e) = activity.initialize() {
-                        log::error!("Failed to initialize native activity: {}", e);
-                        return 0;
-                    }
-                    
-                    log::info!("Native activity initialized successfully");
-                    return 1;
-                }
-            }
-        }
-        Err(e) => {
-            log::error!("Failed to create native activity: {}", e);
-        }
-    }
-    
-    0
+) -> jboolean {
+    jni_call(&mut env, "initializeNativeActivity", |_env| {
+        log::info!("Rust: initializeNativeActivity called");
+
+        runtime::get()?.initialize_native_activity()?;
+        log::info!("Native activity initialized successfully");
+        Ok(JNI_TRUE)
+    })
 }
 
-#[no_mangle]
-pub extern "C" fn Java_com_example_modularandroidapp_RustBridge_sendTestNotification(
-    _env: JNIEnv,
+extern "system" fn send_test_notification(
+    mut env: JNIEnv,
     _class: JClass,
 ) {
-    log::info!("Rust: sendTestNotification called");
-    
-    unsafe {
-        if let Some(activity_guard) = NATIVE_ACTIVITY.as_ref() {
-            if let Ok(activity) = activity_guard.lock() {
-                activity.send_test_notification();
-            } else {
-                log::error!("Failed to lock native activity for notification");
-            }
-        } else {
-            log::error!("Native activity not initialized");
-        }
-    }
+    jni_call(&mut env, "sendTestNotification", |_env| {
+        log::info!("Rust: sendTestNotification called");
+
+        let activity = runtime::get()?.native_activity()?;
+        activity.lock()?.send_test_notification();
+        Ok(())
+    })
+}
+
+/// Posts a notification given as `NotificationContent` JSON and returns
+/// its id.
+extern "system" fn post_notification(mut env: JNIEnv, _class: JClass, content: JString) -> jint {
+    jni_call(&mut env, "postNotification", |env| {
+        let content = get_string(env, &content)?;
+        log::info!("Rust: postNotification called with {}", content);
+        let content: NotificationContent = serde_json::from_str(&content)
+            .map_err(|e| Error::InvalidArgument(format!("invalid notification: {}", e)))?;
+
+        let activity = runtime::get()?.native_activity()?;
+        let id = activity.lock()?.post_notification(content)?;
+        Ok(id)
+    })
 }
 
-#[no_mangle]
-pub extern "C" fn Java_com_example_modularandroidapp_RustBridge_updateStatus(
+/// Posted, updated, coalesced and suppressed counts as a JSON object keyed
+/// by channel.
+extern "system" fn get_notification_stats(mut env: JNIEnv, _class: JClass) -> jstring {
+    jni_call(&mut env, "getNotificationStats", |env| {
+        let activity = runtime::get()?.native_activity()?;
+        let stats = activity.lock()?.notification_stats()?;
+        let json = serde_json::to_string(&stats)
+            .map_err(|e| Error::Internal(format!("failed to encode notification stats: {}", e)))?;
+        new_string(env, &json)
+    })
+}
+
+/// Called by Kotlin when an action button is tapped; returns whether a
+/// Rust handler ran.
+extern "system" fn on_notification_action(
+    mut env: JNIEnv,
+    _class: JClass,
+    notification_id: jint,
+    action_id: JString,
+) -> jboolean {
+    jni_call(&mut env, "onNotificationAction", |env| {
+        let action_id = get_string(env, &action_id)?;
+        log::info!("Rust: onNotificationAction called for {} on {}", action_id, notification_id);
+
+        // The handler may lock the activity again, e.g. to post
+        let activity = runtime::get()?.native_activity()?;
+        let notifications = activity.lock()?.notifications()?;
+        let handled = notifications.handle_action(notification_id, &action_id);
+        Ok(if handled { JNI_TRUE } else { JNI_FALSE })
+    })
+}
+
+extern "system" fn on_notification_dismissed(mut env: JNIEnv, _class: JClass, notification_id: jint) -> jboolean {
+    jni_call(&mut env, "onNotificationDismissed", |_env| {
+        log::info!("Rust: onNotificationDismissed called for {}", notification_id);
+        let activity = runtime::get()?.native_activity()?;
+        let cleared = activity.lock()?.clear_notification(notification_id)?;
+        Ok(if cleared { JNI_TRUE } else { JNI_FALSE })
+    })
+}
+
+extern "system" fn register_notification_channel(mut env: JNIEnv, _class: JClass, channel: JString) {
+    jni_call(&mut env, "registerNotificationChannel", |env| {
+        let channel = get_string(env, &channel)?;
+        log::info!("Rust: registerNotificationChannel called with {}", channel);
+        let channel: NotificationChannel = serde_json::from_str(&channel)
+            .map_err(|e| Error::InvalidArgument(format!("invalid notification channel: {}", e)))?;
+
+        let activity = runtime::get()?.native_activity()?;
+        let activity = activity.lock()?;
+        activity.register_notification_channel(channel)
+    })
+}
+
+extern "system" fn get_notification_channels(mut env: JNIEnv, _class: JClass) -> jstring {
+    jni_call(&mut env, "getNotificationChannels", |env| {
+        let activity = runtime::get()?.native_activity()?;
+        let channels = activity.lock()?.notification_channels()?;
+        let json = serde_json::to_string(&channels)
+            .map_err(|e| Error::Internal(format!("failed to encode notification channels: {}", e)))?;
+        new_string(env, &json)
+    })
+}
+
+extern "system" fn set_notification_store(mut env: JNIEnv, _class: JClass, store: JString) {
+    jni_call(&mut env, "setNotificationStore", |env| {
+        let store = get_string(env, &store)?;
+        log::info!("Rust: setNotificationStore called with {}", store);
+        let store: StoreConfig = store.parse()?;
+        runtime::get()?.set_notification_store(store)
+    })
+}
+
+extern "system" fn get_notification_history(mut env: JNIEnv, _class: JClass) -> jstring {
+    jni_call(&mut env, "getNotificationHistory", |env| {
+        let activity = runtime::get()?.native_activity()?;
+        let history = activity.lock()?.notification_history()?;
+        let json = serde_json::to_string(&history)
+            .map_err(|e| Error::Internal(format!("failed to encode notifications: {}", e)))?;
+        new_string(env, &json)
+    })
+}
+
+/// Schedules a notification given as `ScheduleRequest` JSON and returns
+/// its schedule id.
+extern "system" fn schedule_notification(mut env: JNIEnv, _class: JClass, request: JString) -> jlong {
+    jni_call(&mut env, "scheduleNotification", |env| {
+        let request = get_string(env, &request)?;
+        log::info!("Rust: scheduleNotification called with {}", request);
+        let request: ScheduleRequest = serde_json::from_str(&request)
+            .map_err(|e| Error::InvalidArgument(format!("invalid notification schedule: {}", e)))?;
+
+        let activity = runtime::get()?.native_activity()?;
+        let id = activity.lock()?.schedule_notification(request)?;
+        Ok(id.0 as jlong)
+    })
+}
+
+extern "system" fn cancel_scheduled_notification(mut env: JNIEnv, _class: JClass, schedule_id: jlong) -> jboolean {
+    jni_call(&mut env, "cancelScheduledNotification", |_env| {
+        let activity = runtime::get()?.native_activity()?;
+        let cancelled = activity
+            .lock()?
+            .cancel_scheduled_notification(ScheduleId(schedule_id as u64))?;
+        Ok(if cancelled { JNI_TRUE } else { JNI_FALSE })
+    })
+}
+
+/// Pending schedules as a JSON array of `ScheduledNotification`s.
+extern "system" fn list_scheduled_notifications(mut env: JNIEnv, _class: JClass) -> jstring {
+    jni_call(&mut env, "listScheduledNotifications", |env| {
+        let activity = runtime::get()?.native_activity()?;
+        let schedules = activity.lock()?.scheduled_notifications()?;
+        let json = serde_json::to_string(&schedules)
+            .map_err(|e| Error::Internal(format!("failed to encode scheduled notifications: {}", e)))?;
+        new_string(env, &json)
+    })
+}
+
+extern "system" fn update_status(
     mut env: JNIEnv,
     _class: JClass,
     message: JString,
 ) {
-    let message_str: String = env.get_string(&message).unwrap().into();
-    log::info!("Rust: updateStatus called with message: {}", message_str);
-    
-    unsafe {
-        if let Some(activity_guard) = NATIVE_ACTIVITY.as_ref() {
-            if let Ok(activity) = activity_guard.lock() {
-                activity.update_status(message_str);
-            } else {
-                log::error!("Failed to lock native activity for status update");
-            }
-        } else {
-            log::error!("Native activity not initialized");
-        }
-    }
+    jni_call(&mut env, "updateStatus", |env| {
+        let message_str = get_string(env, &message)?;
+        log::info!("Rust: updateStatus called with message: {}", message_str);
+
+        let activity = runtime::get()?.native_activity()?;
+        activity.lock()?.update_status(message_str);
+        Ok(())
+    })
 }
 
-#[no_mangle]
-pub extern "C" fn Java_com_example_modularandroidapp_RustBridge_getSensorData(
+extern "system" fn get_sensor_data(
     mut env: JNIEnv,
     _class: JClass,
-) -> jni::sys::jstring {
-    log::info!("Rust: getSensorData called");
-    
-    unsafe {
-        if let Some(activity_guard) = NATIVE_ACTIVITY.as_ref() {
-            if let Ok(activity) = activity_guard.lock() {
-                let sensor_data = activity.get_sensor_data();
-                env.new_string(&sensor_data).unwrap().into_raw()
-            } else {
-                log::error!("Failed to lock native activity for sensor data");
-                env.new_string("Error: Failed to access sensor data").unwrap().into_raw()
-            }
-        } else {
-            log::error!("Native activity not initialized");
-            env.new_string("Error: Native activity not initialized").unwrap().into_raw()
+) -> jstring {
+    jni_call(&mut env, "getSensorData", |env| {
+        log::info!("Rust: getSensorData called");
+
+        let activity = runtime::get()?.native_activity()?;
+        let sensor_data = activity.lock()?.get_sensor_data();
+        new_string(env, &sensor_data)
+    })
+}
+
+extern "system" fn start_sensors(mut env: JNIEnv, _class: JClass, config: JString) {
+    jni_call(&mut env, "startSensors", |env| {
+        let config = get_string(env, &config)?;
+        log::info!("Rust: startSensors called with config: {}", config);
+        let config: SensorConfig = serde_json::from_str(&config)
+            .map_err(|e| Error::InvalidArgument(format!("invalid sensor config: {}", e)))?;
+
+        let activity = runtime::get()?.native_activity()?;
+        let activity = activity.lock()?;
+        activity.start_sensors(config)
+    })
+}
+
+extern "system" fn stop_sensors(mut env: JNIEnv, _class: JClass) {
+    jni_call(&mut env, "stopSensors", |_env| {
+        log::info!("Rust: stopSensors called");
+
+        let activity = runtime::get()?.native_activity()?;
+        let activity = activity.lock()?;
+        activity.stop_sensors()
+    })
+}
+
+extern "system" fn set_sensor_filters(mut env: JNIEnv, _class: JClass, config: JString) {
+    jni_call(&mut env, "setSensorFilters", |env| {
+        let config = get_string(env, &config)?;
+        log::info!("Rust: setSensorFilters called with config: {}", config);
+        let config: FilterConfig = serde_json::from_str(&config)
+            .map_err(|e| Error::InvalidArgument(format!("invalid filter config: {}", e)))?;
+
+        let activity = runtime::get()?.native_activity()?;
+        let activity = activity.lock()?;
+        activity.set_sensor_filters(&config)
+    })
+}
+
+/// Switches the sensor source: `auto`, `android`, `simulator` or
+/// `replay:<path>`. `speed` only applies to replays.
+extern "system" fn set_sensor_backend(mut env: JNIEnv, _class: JClass, backend: JString, speed: jfloat) {
+    jni_call(&mut env, "setSensorBackend", |env| {
+        let backend = get_string(env, &backend)?;
+        log::info!("Rust: setSensorBackend called with {} at speed {}", backend, speed);
+        let mut backend: SensorBackend = backend.parse()?;
+        if let SensorBackend::Replay(replay) = &mut backend {
+            replay.speed = speed;
         }
+
+        let activity = runtime::get()?.native_activity()?;
+        let activity = activity.lock()?;
+        activity.set_sensor_backend(backend)
+    })
+}
+
+/// Records raw readings to `path`. An empty `format` picks it from the
+/// extension.
+extern "system" fn start_sensor_recording(mut env: JNIEnv, _class: JClass, path: JString, format: JString) {
+    jni_call(&mut env, "startSensorRecording", |env| {
+        let path = PathBuf::from(get_string(env, &path)?);
+        let format = get_string(env, &format)?;
+        let format = if format.is_empty() {
+            RecordingFormat::from_path(&path)?
+        } else {
+            format.parse()?
+        };
+        log::info!("Rust: startSensorRecording called with {} ({:?})", path.display(), format);
+
+        let activity = runtime::get()?.native_activity()?;
+        let activity = activity.lock()?;
+        activity.start_sensor_recording(&path, format)
+    })
+}
+
+/// Returns the number of recorded samples, or -1 if nothing was recording.
+extern "system" fn stop_sensor_recording(mut env: JNIEnv, _class: JClass) -> jlong {
+    jni_call(&mut env, "stopSensorRecording", |_env| {
+        let activity = runtime::get()?.native_activity()?;
+        let count = activity.lock()?.stop_sensor_recording()?;
+        Ok(count.map_or(-1, |count| count as jlong))
+    })
+}
+
+/// Registers a threshold rule given as `RuleSpec` JSON and returns its id.
+extern "system" fn add_sensor_rule(mut env: JNIEnv, _class: JClass, spec: JString) -> jlong {
+    jni_call(&mut env, "addSensorRule", |env| {
+        let spec = get_string(env, &spec)?;
+        log::info!("Rust: addSensorRule called with rule: {}", spec);
+        let spec: RuleSpec = serde_json::from_str(&spec)
+            .map_err(|e| Error::InvalidArgument(format!("invalid sensor rule: {}", e)))?;
+
+        let activity = runtime::get()?.native_activity()?;
+        let id = activity.lock()?.add_sensor_rule(spec)?;
+        Ok(id.0 as jlong)
+    })
+}
+
+extern "system" fn remove_sensor_rule(mut env: JNIEnv, _class: JClass, rule_id: jlong) -> jboolean {
+    jni_call(&mut env, "removeSensorRule", |_env| {
+        let activity = runtime::get()?.native_activity()?;
+        let removed = activity.lock()?.remove_sensor_rule(RuleId(rule_id as u64))?;
+        Ok(if removed { JNI_TRUE } else { JNI_FALSE })
+    })
+}
+
+/// Registered rules as a JSON array of `RuleSpec`s with their `id`.
+extern "system" fn list_sensor_rules(mut env: JNIEnv, _class: JClass) -> jstring {
+    jni_call(&mut env, "listSensorRules", |env| {
+        let activity = runtime::get()?.native_activity()?;
+        let rules = activity.lock()?.sensor_rules()?;
+        let json = serde_json::to_string(&rules)
+            .map_err(|e| Error::Internal(format!("failed to encode sensor rules: {}", e)))?;
+        new_string(env, &json)
+    })
+}
+
+/// Changes how much history each sensor keeps; a negative window is rejected.
+extern "system" fn set_sensor_history_window(mut env: JNIEnv, _class: JClass, window_ms: jlong) {
+    jni_call(&mut env, "setSensorHistoryWindow", |_env| {
+        log::info!("Rust: setSensorHistoryWindow called with {} ms", window_ms);
+        let window_ms = u64::try_from(window_ms)
+            .map_err(|_| Error::InvalidArgument(format!("window_ms must not be negative, got {}", window_ms)))?;
+
+        let activity = runtime::get()?.native_activity()?;
+        let activity = activity.lock()?;
+        activity.set_sensor_history_window(Duration::from_millis(window_ms))
+    })
+}
+
+/// A `window_ms` of 0 or less selects everything kept.
+fn history_window(window_ms: jlong) -> Option<Duration> {
+    u64::try_from(window_ms)
+        .ok()
+        .filter(|ms| *ms > 0)
+        .map(Duration::from_millis)
+}
+
+extern "system" fn get_sensor_history(
+    mut env: JNIEnv,
+    _class: JClass,
+    sensor: JString,
+    window_ms: jlong,
+) -> jfloatArray {
+    jni_call(&mut env, "getSensorHistory", |env| {
+        let sensor: SensorKind = get_string(env, &sensor)?.parse()?;
+        let activity = runtime::get()?.native_activity()?;
+        let history = activity.lock()?.sensor_history(sensor, history_window(window_ms))?;
+
+        let len = i32::try_from(history.len())
+            .map_err(|_| Error::Internal(format!("sensor history too long: {}", history.len())))?;
+        let array = env.new_float_array(len)?;
+        env.set_float_array_region(&array, 0, &history)?;
+        Ok(array.into_raw())
+    })
+}
+
+extern "system" fn get_sensor_stats(mut env: JNIEnv, _class: JClass, sensor: JString, window_ms: jlong) -> jstring {
+    jni_call(&mut env, "getSensorStats", |env| {
+        let sensor: SensorKind = get_string(env, &sensor)?.parse()?;
+        let activity = runtime::get()?.native_activity()?;
+        let stats = activity.lock()?.sensor_stats(sensor, history_window(window_ms))?;
+        let json = serde_json::to_string(&stats)
+            .map_err(|e| Error::Internal(format!("failed to encode sensor stats: {}", e)))?;
+        new_string(env, &json)
+    })
+}
+
+/// Latest fused orientation as JSON, or `null` before the first gyroscope
+/// sample.
+extern "system" fn get_orientation(mut env: JNIEnv, _class: JClass) -> jstring {
+    jni_call(&mut env, "getOrientation", |env| {
+        let activity = runtime::get()?.native_activity()?;
+        let orientation = activity.lock()?.orientation()?;
+        let json = serde_json::to_string(&orientation)
+            .map_err(|e| Error::Internal(format!("failed to encode orientation: {}", e)))?;
+        new_string(env, &json)
+    })
+}
+
+extern "system" fn set_fusion_config(mut env: JNIEnv, _class: JClass, config: JString) {
+    jni_call(&mut env, "setFusionConfig", |env| {
+        let config = get_string(env, &config)?;
+        log::info!("Rust: setFusionConfig called with config: {}", config);
+        let config: FusionConfig = serde_json::from_str(&config)
+            .map_err(|e| Error::InvalidArgument(format!("invalid fusion config: {}", e)))?;
+
+        let activity = runtime::get()?.native_activity()?;
+        let activity = activity.lock()?;
+        activity.set_fusion_config(config)
+    })
+}
+
+extern "system" fn set_gesture_config(mut env: JNIEnv, _class: JClass, config: JString) {
+    jni_call(&mut env, "setGestureConfig", |env| {
+        let config = get_string(env, &config)?;
+        log::info!("Rust: setGestureConfig called with config: {}", config);
+        let config: GestureConfig = serde_json::from_str(&config)
+            .map_err(|e| Error::InvalidArgument(format!("invalid gesture config: {}", e)))?;
+
+        let activity = runtime::get()?.native_activity()?;
+        let activity = activity.lock()?;
+        activity.set_gesture_config(config)
+    })
+}
+
+/// Runs `f` on the native activity if it has been initialized. Lifecycle
+/// callbacks arrive before `initializeNativeActivity`, and there is nothing
+/// to pause then.
+fn with_native_activity(f: impl FnOnce(&NativeActivity) -> Result<()>) -> Result<()> {
+    match runtime::get()?.native_activity() {
+        Ok(activity) => f(&*activity.lock()?),
+        Err(Error::Runtime(RuntimeError::NativeActivityNotInitialized)) => Ok(()),
+        Err(e) => Err(e),
     }
 }
 
-// Helper function to call Java from Rust
-pub fn call_java_method(java_vm: &JavaVM, method_name: &str, data: &str) {
-    if let Ok(mut env) = java_vm.attach_current_thread_as_daemon() {
-        if let Ok(class) = env.find_class("com/example/modularandroidapp/RustBridge") {
-            if let Ok(java_data) = env.new_string(data) {
-                let _ = env.call_static_method(
-                    class,
-                    method_name,
-                    "(Ljava/lang/String;)V",
-                    &[(&java_data).into()],
-                );
-            }
+extern "system" fn on_activity_pause(mut env: JNIEnv, _class: JClass) {
+    jni_call(&mut env, "onActivityPause", |_env| {
+        log::info!("Rust: onActivityPause called");
+        with_native_activity(NativeActivity::on_pause)
+    })
+}
+
+extern "system" fn on_activity_resume(mut env: JNIEnv, _class: JClass) {
+    jni_call(&mut env, "onActivityResume", |_env| {
+        log::info!("Rust: onActivityResume called");
+        with_native_activity(NativeActivity::on_resume)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jni::signature::TypeSignature;
+
+    #[test]
+    fn native_method_signatures_match_rust_arity() {
+        for method in native_methods() {
+            let sig = TypeSignature::from_str(method.sig)
+                .unwrap_or_else(|e| panic!("{}: invalid signature {}: {}", method.name, method.sig, e));
+            assert_eq!(
+                sig.args.len(),
+                method.arity,
+                "{} is registered as {} but its Rust function takes {} arguments",
+                method.name,
+                method.sig,
+                method.arity
+            );
         }
     }
-} 
\ No newline at end of file
+}
@@ -4,34 +4,44 @@ pub mod utils;
 pub mod sensors;
 pub mod notifications;
 pub mod native_activity;
+pub mod runtime;
+pub mod error;
+pub mod callbacks;
+pub mod config;
+pub mod threads;
+pub mod events;
+pub mod bus;
+pub mod tasks;
+pub mod pool;
+pub mod scheduler;
 
-use std::sync::Once;
-
-#[cfg(target_os = "android")]
-use log::LevelFilter;
-
-static INIT: Once = Once::new();
+use jni::JavaVM;
 
+/// # Safety
+///
+/// Called by the Java runtime from `System.loadLibrary` with a valid `JavaVM` pointer.
 #[no_mangle]
-pub extern "C" fn JNI_OnLoad(_vm: *mut std::ffi::c_void, _reserved: *mut std::ffi::c_void) -> std::ffi::c_int {
-    INIT.call_once(|| {
-        // Initialize logging for Android
-        #[cfg(target_os = "android")]
-        {
-            android_logger::init_once(
-                android_logger::Config::default()
-                    .with_tag("mainlogic")
-                    .with_max_level(LevelFilter::Debug),
-            );
+pub unsafe extern "C" fn JNI_OnLoad(vm: *mut jni::sys::JavaVM, _reserved: *mut std::ffi::c_void) -> jni::sys::jint {
+    // The VM outlives the library, so it is safe to keep it for the whole process
+    let java_vm = match unsafe { JavaVM::from_raw(vm) } {
+        Ok(java_vm) => java_vm,
+        Err(e) => {
+            log::error!("JNI_OnLoad received an invalid JavaVM: {}", e);
+            return jni::sys::JNI_ERR;
         }
-    });
+    };
+    
+    if let Err(e) = runtime::on_load(java_vm) {
+        log::error!("JNI_OnLoad failed: {}", e);
+        return jni::sys::JNI_ERR;
+    }
     
     // Return JNI version
     jni::JNIVersion::V6.into()
 }
 
 #[no_mangle]
-pub extern "C" fn JNI_OnUnload(_vm: *mut std::ffi::c_void, _reserved: *mut std::ffi::c_void) {
-    #[cfg(target_os = "android")]
+pub extern "C" fn JNI_OnUnload(_vm: *mut jni::sys::JavaVM, _reserved: *mut std::ffi::c_void) {
     log::info!("Rust library unloading");
-} 
\ No newline at end of file
+    runtime::on_unload();
+}
@@ -1,101 +1,274 @@
+use std::collections::BTreeMap;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
-use jni::JavaVM;
-use crate::sensors::SensorManager;
-use crate::notifications::NotificationManager;
+use std::time::Duration;
+use crate::events::{Event, EventSender};
+use crate::sensors::filters::FilterConfig;
+use crate::sensors::recording::RecordingFormat;
+use crate::sensors::fusion::{FusionConfig, FusionOutput, SensorFusion};
+use crate::sensors::gestures::{GestureConfig, GestureDetectors};
+use crate::sensors::history::SensorStats;
+use crate::sensors::rules::{RuleEngine, RuleId, RuleInfo, RuleSpec};
+use crate::sensors::{SensorBackend, SensorConfig, SensorKind, SensorManager};
+use crate::notifications::channels::NotificationChannel;
+use crate::notifications::content::NotificationContent;
+use crate::notifications::limits::ChannelStats;
+use crate::notifications::schedule::{ScheduleId, ScheduleRequest, ScheduledNotification};
+use crate::notifications::store::StoreConfig;
+use crate::notifications::{NotificationData, NotificationManager};
+use crate::scheduler::Scheduler;
+use crate::error::Result;
 
 pub struct NativeActivity {
     sensor_manager: Arc<Mutex<SensorManager>>,
+    fusion: Arc<Mutex<SensorFusion>>,
+    rules: Arc<Mutex<RuleEngine>>,
+    gestures: Arc<Mutex<GestureDetectors>>,
     notification_manager: Arc<Mutex<NotificationManager>>,
-    java_vm: Arc<Mutex<JavaVM>>,
+    /// Runs scheduled notifications once `initialize_notifications` set the
+    /// callbacks they post through.
+    scheduler: Scheduler,
+    events: EventSender,
     status_callback: Option<Box<dyn Fn(String) + Send + Sync>>,
 }
 
 impl NativeActivity {
-    pub fn new(java_vm: JavaVM) -> Result<Self, Box<dyn std::error::Error>> {
-        let sensor_manager = Arc::new(Mutex::new(SensorManager::new()));
-        let notification_manager = Arc::new(Mutex::new(NotificationManager::new()));
-        let java_vm = Arc::new(Mutex::new(java_vm));
+    pub fn new(
+        events: EventSender,
+        sensor_backend: SensorBackend,
+        store: &StoreConfig,
+        scheduler: Scheduler,
+    ) -> Result<Self> {
+        let sensor_manager = Arc::new(Mutex::new(SensorManager::with_backend(sensor_backend)));
+        let fusion = Arc::new(Mutex::new(SensorFusion::new(FusionConfig::default())?));
+        
+        // Notifications still work without history if the store is unusable
+        let notification_manager = NotificationManager::new();
+        if let Err(e) = store.open().and_then(|store| notification_manager.set_store(store)) {
+            log::error!("Failed to open notification store {:?}, keeping notifications in memory: {}", store, e);
+        }
+        let notification_manager = Arc::new(Mutex::new(notification_manager));
         
         Ok(Self {
             sensor_manager,
+            fusion,
+            rules: Arc::new(Mutex::new(RuleEngine::new())),
+            gestures: Arc::new(Mutex::new(GestureDetectors::default())),
             notification_manager,
-            java_vm,
+            scheduler,
+            events,
             status_callback: None,
         })
     }
     
-    pub fn initialize(&self) -> Result<(), Box<dyn std::error::Error>> {
-        #[cfg(target_os = "android")]
+    pub fn initialize(&self) -> Result<()> {
         log::info!("Initializing native activity");
         
         // Start sensor monitoring
-        self.start_sensor_monitoring();
+        self.start_sensor_monitoring()?;
         
         // Initialize notifications
         self.initialize_notifications();
         
-        #[cfg(target_os = "android")]
         log::info!("Native activity initialized successfully");
         Ok(())
     }
     
-    pub fn start_sensor_monitoring(&self) {
+    pub fn start_sensor_monitoring(&self) -> Result<()> {
         let sensor_manager = self.sensor_manager.clone();
-        let java_vm = self.java_vm.clone();
+        let fusion = self.fusion.clone();
+        let rules = self.rules.clone();
+        let gestures = self.gestures.clone();
+        let notification_manager = self.notification_manager.clone();
+        let events = self.events.clone();
         
         let mut sensor_manager = sensor_manager.lock().unwrap();
-        sensor_manager.set_callback(move |sensor_index, value| {
-            let sensor_names = ["Accelerometer", "Gyroscope", "Magnetometer"];
-            let sensor_name = sensor_names[sensor_index as usize];
-            #[cfg(target_os = "android")]
-            log::info!("{}: {:.2}", sensor_name, value);
+        sensor_manager.set_callback(move |samples| {
+            log::trace!("Delivering {} sensor samples", samples.len());
             
-            // Call back to Java with sensor data
-            if let Ok(java_vm_guard) = java_vm.lock() {
-                if let Ok(mut env) = java_vm_guard.attach_current_thread_as_daemon() {
-                    if let Ok(class) = env.find_class("com/example/modularandroidapp/RustBridge") {
-                        if let Ok(message) = env.new_string(format!("{}: {:.2}", sensor_name, value)) {
-                            let _ = env.call_static_method(
-                                class,
-                                "onSensorData",
-                                "(Ljava/lang/String;)V",
-                                &[(&message).into()],
-                            );
-                        }
+            // Report the samples, the fused orientation and gestures to Java
+            let mut fusion = fusion.lock().unwrap();
+            let mut alerts = Vec::new();
+            {
+                let mut rules = rules.lock().unwrap();
+                let mut gestures = gestures.lock().unwrap();
+                for event in samples {
+                    events.send(Event::SensorSample(*event));
+                    if let Some(orientation) = fusion.update(event) {
+                        events.send(Event::Orientation(orientation));
                     }
+                    for gesture in gestures.update(event) {
+                        events.send(Event::Gesture(gesture));
+                    }
+                    alerts.extend(rules.evaluate(event));
+                }
+            }
+            
+            if !alerts.is_empty() {
+                let notification_manager = notification_manager.lock().unwrap();
+                for alert in alerts {
+                    log::info!("Sensor rule {} fired: {} = {}", alert.rule.0, alert.description, alert.value);
+                    notification_manager.send_sensor_alert(&alert.description, alert.value, alert.threshold);
                 }
             }
         });
-        sensor_manager.start_sensor_monitoring();
+        sensor_manager.start_sensor_monitoring()
+    }
+    
+    /// Applies `config` and makes sure monitoring runs.
+    pub fn start_sensors(&self, config: SensorConfig) -> Result<()> {
+        let mut sensor_manager = self.sensor_manager.lock()?;
+        sensor_manager.set_config(config)?;
+        sensor_manager.start_sensor_monitoring()
+    }
+    
+    pub fn set_sensor_filters(&self, config: &FilterConfig) -> Result<()> {
+        self.sensor_manager.lock()?.set_filters(config)
+    }
+    
+    pub fn set_sensor_backend(&self, backend: SensorBackend) -> Result<()> {
+        self.sensor_manager.lock()?.set_backend(backend)
+    }
+    
+    pub fn start_sensor_recording(&self, path: &Path, format: RecordingFormat) -> Result<()> {
+        self.sensor_manager.lock()?.start_recording(path, format)
+    }
+    
+    /// Returns the number of recorded samples, `None` if nothing was recording.
+    pub fn stop_sensor_recording(&self) -> Result<Option<u64>> {
+        self.sensor_manager.lock()?.stop_recording()
+    }
+    
+    pub fn stop_sensors(&self) -> Result<()> {
+        self.sensor_manager.lock()?.stop_sensor_monitoring();
+        Ok(())
+    }
+    
+    pub fn set_sensor_history_window(&self, window: Duration) -> Result<()> {
+        self.sensor_manager.lock()?.set_history_window(window)
+    }
+    
+    pub fn sensor_history(&self, kind: SensorKind, window: Option<Duration>) -> Result<Vec<f32>> {
+        self.sensor_manager.lock()?.history(kind, window)
+    }
+    
+    pub fn sensor_stats(&self, kind: SensorKind, window: Option<Duration>) -> Result<Option<SensorStats>> {
+        self.sensor_manager.lock()?.stats(kind, window)
+    }
+    
+    /// Latest fused orientation, if the gyroscope has reported yet.
+    pub fn orientation(&self) -> Result<Option<FusionOutput>> {
+        Ok(self.fusion.lock()?.latest())
+    }
+    
+    pub fn set_fusion_config(&self, config: FusionConfig) -> Result<()> {
+        self.fusion.lock()?.set_config(config)
+    }
+    
+    /// Replaces the gesture detectors; their state, including the step
+    /// count, starts over.
+    pub fn set_gesture_config(&self, config: GestureConfig) -> Result<()> {
+        *self.gestures.lock()? = GestureDetectors::new(config)?;
+        Ok(())
+    }
+    
+    pub fn add_sensor_rule(&self, spec: RuleSpec) -> Result<RuleId> {
+        self.rules.lock()?.add(spec)
+    }
+    
+    pub fn remove_sensor_rule(&self, id: RuleId) -> Result<bool> {
+        Ok(self.rules.lock()?.remove(id))
+    }
+    
+    pub fn sensor_rules(&self) -> Result<Vec<RuleInfo>> {
+        Ok(self.rules.lock()?.list())
+    }
+    
+    /// Activity went to the background: release the sensors.
+    pub fn on_pause(&self) -> Result<()> {
+        self.sensor_manager.lock()?.pause_monitoring();
+        Ok(())
+    }
+    
+    /// Activity is back in the foreground: restart what `on_pause` stopped.
+    pub fn on_resume(&self) -> Result<()> {
+        self.sensor_manager.lock()?.resume_monitoring()
     }
     
     pub fn initialize_notifications(&self) {
         let notification_manager = self.notification_manager.clone();
-        let java_vm = self.java_vm.clone();
+        let events = self.events.clone();
         
         let mut notification_manager = notification_manager.lock().unwrap();
-        notification_manager.set_callback(move |id, title, message, timestamp| {
-            #[cfg(target_os = "android")]
-            log::info!("Notification: [{}] {}: {}", timestamp, title, message);
+        notification_manager.set_callback(move |notification| {
+            log::info!(
+                "Notification: [{}] {}: {}",
+                notification.timestamp, notification.title, notification.message
+            );
             
-            // Call back to Java with notification data
-            if let Ok(java_vm_guard) = java_vm.lock() {
-                if let Ok(mut env) = java_vm_guard.attach_current_thread_as_daemon() {
-                    if let Ok(class) = env.find_class("com/example/modularandroidapp/RustBridge") {
-                        if let Ok(title_str) = env.new_string(&title) {
-                            if let Ok(message_str) = env.new_string(&message) {
-                                let _ = env.call_static_method(
-                                    class,
-                                    "onNotification",
-                                    "(ILjava/lang/String;Ljava/lang/String;)V",
-                                    &[id.into(), (&title_str).into(), (&message_str).into()],
-                                );
-                            }
-                        }
-                    }
-                }
-            }
+            // Report the notification to Java
+            events.send(Event::Notification(notification.clone()));
         });
+        
+        // Java creates the Android channels, starting with those declared so far
+        let events = self.events.clone();
+        notification_manager.set_channel_callback(move |channel| {
+            events.send(Event::NotificationChannel(channel.clone()));
+        });
+        
+        // Posts schedules that came due while the app was not running
+        notification_manager.set_scheduler(self.scheduler.clone());
+    }
+    
+    pub fn register_notification_channel(&self, channel: NotificationChannel) -> Result<()> {
+        self.notification_manager.lock()?.register_channel(channel)
+    }
+    
+    pub fn notification_channels(&self) -> Result<Vec<NotificationChannel>> {
+        Ok(self.notification_manager.lock()?.channels())
+    }
+    
+    /// Switches to `store` and loads its history. Returns the number of
+    /// notifications loaded.
+    pub fn set_notification_store(&self, store: &StoreConfig) -> Result<usize> {
+        let store = store.open()?;
+        self.notification_manager.lock()?.set_store(store)
+    }
+    
+    /// Stored notifications, oldest first.
+    pub fn notification_history(&self) -> Result<Vec<NotificationData>> {
+        Ok(self.notification_manager.lock()?.get_all_notifications())
+    }
+    
+    /// A handle for posting from other threads, e.g. a task's progress.
+    pub fn notifications(&self) -> Result<NotificationManager> {
+        Ok(self.notification_manager.lock()?.clone())
+    }
+    
+    pub fn post_notification(&self, content: NotificationContent) -> Result<i32> {
+        self.notification_manager.lock()?.post_notification(content)
+    }
+    
+    /// Forgets a notification the user dismissed, so its dedupe key posts
+    /// anew. Returns `false` if it was not known.
+    pub fn clear_notification(&self, id: i32) -> Result<bool> {
+        Ok(self.notification_manager.lock()?.clear_notification(id))
+    }
+    
+    pub fn notification_stats(&self) -> Result<BTreeMap<String, ChannelStats>> {
+        Ok(self.notification_manager.lock()?.stats())
+    }
+    
+    pub fn schedule_notification(&self, request: ScheduleRequest) -> Result<ScheduleId> {
+        self.notification_manager.lock()?.schedule_notification(request)
+    }
+    
+    pub fn cancel_scheduled_notification(&self, id: ScheduleId) -> Result<bool> {
+        Ok(self.notification_manager.lock()?.cancel_scheduled_notification(id))
+    }
+    
+    pub fn scheduled_notifications(&self) -> Result<Vec<ScheduledNotification>> {
+        Ok(self.notification_manager.lock()?.scheduled_notifications())
     }
     
     pub fn send_test_notification(&self) {
@@ -107,24 +280,10 @@ impl NativeActivity {
     }
     
     pub fn update_status(&self, message: String) {
-        #[cfg(target_os = "android")]
         log::info!("Status update: {}", message);
         
-        // Call back to Java with status update
-        if let Ok(java_vm_guard) = self.java_vm.lock() {
-            if let Ok(mut env) = java_vm_guard.attach_current_thread_as_daemon() {
-                if let Ok(class) = env.find_class("com/example/modularandroidapp/RustBridge") {
-                    if let Ok(message_str) = env.new_string(&message) {
-                        let _ = env.call_static_method(
-                            class,
-                            "onStatusUpdate",
-                            "(Ljava/lang/String;)V",
-                            &[(&message_str).into()],
-                        );
-                    }
-                }
-            }
-        }
+        // Report the status update to Java
+        self.events.send(Event::Status { message });
     }
     
     pub fn set_status_callback(&mut self, callback: impl Fn(String) + Send + Sync + 'static) {
@@ -1,101 +1,1075 @@
-use std::sync::{Arc, Mutex};
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::collections::{hash_map, BTreeMap, HashMap};
+use std::time::Duration;
 
-pub struct NotificationManager {
-    notifications: Arc<Mutex<HashMap<i32, NotificationData>>>,
-    next_id: Arc<Mutex<i32>>,
-    callback: Option<Box<dyn Fn(i32, String, String, String) + Send + Sync>>,
+use crate::error::{Error, Result};
+use crate::scheduler::{Clock, JobId, JobRun, Schedule, Scheduler, SystemClock};
+
+pub mod channels;
+pub mod content;
+pub mod limits;
+pub mod progress;
+pub mod schedule;
+pub mod store;
+
+use channels::{ChannelRegistry, NotificationChannel, DEFAULT_CHANNEL, SENSOR_ALERTS_CHANNEL};
+use content::{ActionInvocation, NotificationAction, NotificationContent, NotificationStyle, Progress};
+use limits::{ChannelStats, RateLimit, TokenBucket};
+use schedule::{ScheduleId, ScheduleRequest, ScheduledNotification};
+use store::{MemoryStore, NotificationStore};
+
+type NotificationCallback = Box<dyn Fn(&NotificationData) + Send + Sync>;
+type ChannelCallback = Box<dyn Fn(&NotificationChannel) + Send + Sync>;
+type ActionHandler = Arc<dyn Fn(&ActionInvocation) + Send + Sync>;
+
+/// Lines listed in a group summary; Android shows about this many.
+const MAX_SUMMARY_LINES: usize = 5;
+
+/// Notifications kept in the history and the store; the oldest are
+/// forgotten beyond this, though Android may still be showing them.
+const MAX_NOTIFICATIONS: usize = 256;
+
+/// Shared with the scheduler jobs that post scheduled notifications.
+struct Inner {
+    notifications: Mutex<BTreeMap<i32, NotificationData>>,
+    next_id: Mutex<i32>,
+    /// Receives every change to `notifications` and `schedules`.
+    store: Mutex<Box<dyn NotificationStore>>,
+    channels: Mutex<ChannelRegistry>,
+    callback: RwLock<Option<NotificationCallback>>,
+    /// Creates declared channels on the Kotlin side.
+    channel_callback: RwLock<Option<ChannelCallback>>,
+    schedules: Mutex<BTreeMap<ScheduleId, ScheduledNotification>>,
+    next_schedule_id: Mutex<u64>,
+    /// Schedules wait unarmed until a scheduler is set.
+    scheduler: Mutex<Option<Scheduler>>,
+    /// The job posting each schedule's next occurrence.
+    timers: Mutex<HashMap<ScheduleId, JobId>>,
+    /// By `NotificationAction::id`.
+    actions: RwLock<HashMap<String, ActionHandler>>,
+    /// ID of the notification shown under each dedupe key.
+    dedupe: Mutex<HashMap<String, i32>>,
+    /// Rate limits by channel, created on the channel's first post.
+    buckets: Mutex<HashMap<String, TokenBucket>>,
+    /// Updates held back by dedupe key, on channels with a rate limit.
+    throttled: Mutex<HashMap<String, Throttled>>,
+    stats: Mutex<BTreeMap<String, ChannelStats>>,
+    /// The scheduler's clock once set, so tests control rate limits too.
+    clock: RwLock<Arc<dyn Clock>>,
 }
 
+/// Updates of one dedupe key on a rate-limited channel: shown at most once
+/// per refill interval of the limit, the newest one winning.
+struct Throttled {
+    /// When the key was last shown, by `Clock::monotonic`.
+    shown_at: Duration,
+    /// The newest update not shown yet. Posted by a scheduler job once the
+    /// interval is over or, without a scheduler, by the next update after
+    /// that.
+    pending: Option<NotificationContent>,
+}
+
+/// Clones share the same notifications, like handles.
 #[derive(Clone)]
+pub struct NotificationManager {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct NotificationData {
     pub id: i32,
+    /// Id of a registered `NotificationChannel`.
+    #[serde(default = "default_channel")]
+    pub channel: String,
     pub title: String,
     pub message: String,
     pub timestamp: String,
     pub priority: NotificationPriority,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub style: Option<NotificationStyle>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progress: Option<Progress>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<NotificationAction>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deep_link: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dedupe_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// The summary Android shows on top of a bundled `group`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub group_summary: bool,
 }
 
-#[derive(Clone)]
+/// Notifications stored before channels existed went to the one channel.
+fn default_channel() -> String {
+    DEFAULT_CHANNEL.to_string()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum NotificationPriority {
     Low,
+    #[default]
     Normal,
     High,
     Urgent,
 }
 
+impl Default for NotificationManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl NotificationManager {
+    /// A manager that forgets everything when the process ends.
     pub fn new() -> Self {
         Self {
-            notifications: Arc::new(Mutex::new(HashMap::new())),
-            next_id: Arc::new(Mutex::new(1)),
-            callback: None,
+            inner: Arc::new(Inner {
+                notifications: Mutex::new(BTreeMap::new()),
+                next_id: Mutex::new(1),
+                store: Mutex::new(Box::new(MemoryStore)),
+                channels: Mutex::new(ChannelRegistry::new()),
+                callback: RwLock::new(None),
+                channel_callback: RwLock::new(None),
+                schedules: Mutex::new(BTreeMap::new()),
+                next_schedule_id: Mutex::new(1),
+                scheduler: Mutex::new(None),
+                timers: Mutex::new(HashMap::new()),
+                actions: RwLock::new(HashMap::new()),
+                dedupe: Mutex::new(HashMap::new()),
+                buckets: Mutex::new(HashMap::new()),
+                throttled: Mutex::new(HashMap::new()),
+                stats: Mutex::new(BTreeMap::new()),
+                clock: RwLock::new(Arc::new(SystemClock::default())),
+            }),
         }
     }
     
-    pub fn set_callback(&mut self, callback: impl Fn(i32, String, String, String) + Send + Sync + 'static) {
-        self.callback = Some(Box::new(callback));
+    /// A manager that starts with the history in `store` and keeps it there.
+    pub fn with_store(store: Box<dyn NotificationStore>) -> Result<Self> {
+        let manager = Self::new();
+        manager.set_store(store)?;
+        Ok(manager)
     }
     
-    pub fn send_notification(&self, title: String, message: String, priority: NotificationPriority) -> i32 {
-        let id = {
-            let mut next_id = self.next_id.lock().unwrap();
-            let current_id = *next_id;
-            *next_id += 1;
-            current_id
+    /// Switches to `store` and replaces the notifications and schedules
+    /// with its contents, re-arming the schedules. IDs continue after both
+    /// the store's last ID and any already handed out. Returns the number
+    /// of notifications loaded.
+    pub fn set_store(&self, mut store: Box<dyn NotificationStore>) -> Result<usize> {
+        let stored = store.load()?;
+        let count = stored.notifications.len();
+        {
+            let mut next_id = self.inner.next_id.lock()?;
+            *next_id = (*next_id).max(stored.last_id + 1);
+        }
+        {
+            let mut next_schedule_id = self.inner.next_schedule_id.lock()?;
+            *next_schedule_id = (*next_schedule_id).max(stored.last_schedule_id + 1);
+        }
+        *self.inner.dedupe.lock()? = stored
+            .notifications
+            .iter()
+            .filter_map(|notification| Some((notification.dedupe_key.clone()?, notification.id)))
+            .collect();
+        *self.inner.notifications.lock()? = stored
+            .notifications
+            .into_iter()
+            .map(|notification| (notification.id, notification))
+            .collect();
+        let schedule_count = stored.schedules.len();
+        *self.inner.schedules.lock()? = stored
+            .schedules
+            .into_iter()
+            .map(|schedule| (schedule.id, schedule))
+            .collect();
+        *self.inner.store.lock()? = store;
+        log::info!("Loaded {} stored notifications and {} schedules", count, schedule_count);
+        self.arm_all();
+        Ok(count)
+    }
+    
+    pub fn set_callback(&mut self, callback: impl Fn(&NotificationData) + Send + Sync + 'static) {
+        *self.inner.callback.write().unwrap() = Some(Box::new(callback));
+    }
+    
+    /// Sets the callback that creates channels on the Kotlin side and
+    /// passes it every channel registered so far.
+    pub fn set_channel_callback(&mut self, callback: impl Fn(&NotificationChannel) + Send + Sync + 'static) {
+        for channel in self.inner.channels.lock().unwrap().list() {
+            callback(&channel);
+        }
+        *self.inner.channel_callback.write().unwrap() = Some(Box::new(callback));
+    }
+    
+    /// Arms every pending schedule on `scheduler`. Occurrences that came
+    /// due while no scheduler was set are posted right away; Kotlin holds
+    /// them until its `NotificationService` is attached.
+    pub fn set_scheduler(&self, scheduler: Scheduler) {
+        *self.inner.clock.write().unwrap() = scheduler.clock();
+        // Readings of the old clock mean nothing to the new one
+        self.inner.buckets.lock().unwrap().clear();
+        *self.inner.scheduler.lock().unwrap() = Some(scheduler);
+        self.arm_all();
+    }
+    
+    /// Declares a channel, or updates one with the same id, and announces
+    /// it if anything changed.
+    pub fn register_channel(&self, channel: NotificationChannel) -> Result<()> {
+        if !self.inner.channels.lock()?.register(channel.clone())? {
+            return Ok(());
+        }
+        log::info!("Registered notification channel {}", channel.id);
+        if let Some(callback) = self.inner.channel_callback.read()?.as_ref() {
+            callback(&channel);
+        }
+        Ok(())
+    }
+    
+    pub fn channels(&self) -> Vec<NotificationChannel> {
+        self.inner.channels.lock().unwrap().list()
+    }
+    
+    /// Posts a notification on `channel`, or on the default channel if no
+    /// such channel is registered. Returns `None` if the channel's rate
+    /// limit suppressed it.
+    pub fn send_notification(
+        &self,
+        channel: &str,
+        title: String,
+        message: String,
+        priority: NotificationPriority,
+    ) -> Option<i32> {
+        self.post(NotificationContent::new(channel, title, message).with_priority(priority))
+    }
+    
+    /// Posts a notification with actions, progress, a style, a deep link, a
+    /// dedupe key or a group. Like `send_notification`, unknown channels
+    /// fall back to the default; a suppressed post is `Error::Rejected`.
+    pub fn post_notification(&self, content: NotificationContent) -> Result<i32> {
+        content.validate()?;
+        let channel = content.channel.clone();
+        self.post(content)
+            .ok_or_else(|| Error::Rejected(format!("rate limit of channel {} reached", channel)))
+    }
+    
+    /// Shows `content`, as an update if its dedupe key is still shown, and
+    /// refreshes its group's summary. New notifications take a token from
+    /// the channel's rate limit. Updates do not, since dropping them would
+    /// leave a stale value shown; instead each key shows at most one per
+    /// refill interval and the newest waiting one is posted after it.
+    fn post(&self, mut content: NotificationContent) -> Option<i32> {
+        if !self.inner.channels.lock().unwrap().contains(&content.channel) {
+            log::warn!("Unknown notification channel {}, using {}", content.channel, DEFAULT_CHANNEL);
+            content.channel = DEFAULT_CHANNEL.to_string();
+        }
+        let channel = content.channel.clone();
+        let limit = self.rate_limit(&channel);
+        let shown = content
+            .dedupe_key
+            .as_ref()
+            .and_then(|key| self.inner.dedupe.lock().unwrap().get(key).copied());
+        match (shown, limit) {
+            (Some(id), Some(limit)) => match self.throttle(limit, content) {
+                Some(now) => content = now,
+                None => return Some(id),
+            },
+            (Some(_), None) => {}
+            (None, _) => {
+                if !self.take_token(&channel) {
+                    self.count(&channel, |stats| stats.suppressed += 1);
+                    log::debug!("Rate limit of channel {} suppressed {:?}", channel, content.title);
+                    return None;
+                }
+                if let (Some(key), Some(_)) = (&content.dedupe_key, limit) {
+                    let throttled = Throttled {
+                        shown_at: self.monotonic(),
+                        pending: None,
+                    };
+                    self.inner.throttled.lock().unwrap().insert(key.clone(), throttled);
+                }
+            }
+        }
+        Some(self.post_now(content))
+    }
+    
+    /// Shows `content` and counts it, then refreshes its group's summary.
+    fn post_now(&self, content: NotificationContent) -> i32 {
+        let channel = content.channel.clone();
+        let group = content.group.clone();
+        let (id, updated) = self.show(content, false);
+        self.count(&channel, |stats| {
+            if updated {
+                stats.updated += 1;
+            } else {
+                stats.posted += 1;
+            }
+        });
+        if let Some(group) = group {
+            self.summarize(&group, &channel);
+        }
+        id
+    }
+    
+    /// Returns the update of a shown dedupe key if it may be shown now, or
+    /// holds it back until the refill interval since the key was last shown
+    /// is over, replacing any update already waiting.
+    fn throttle(&self, limit: RateLimit, content: NotificationContent) -> Option<NotificationContent> {
+        let key = content.dedupe_key.clone()?;
+        let now = self.monotonic();
+        let interval = Duration::from_millis(limit.refill_ms);
+        let mut throttled = self.inner.throttled.lock().unwrap();
+        let entry = match throttled.entry(key.clone()) {
+            hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            // Shown before the channel had its limit
+            hash_map::Entry::Vacant(entry) => {
+                entry.insert(Throttled {
+                    shown_at: now,
+                    pending: None,
+                });
+                return Some(content);
+            }
+        };
+        if now.saturating_sub(entry.shown_at) >= interval || entry.shown_at > now {
+            entry.shown_at = now;
+            if entry.pending.take().is_some() {
+                self.count(&content.channel, |stats| stats.coalesced += 1);
+            }
+            return Some(content);
+        }
+        
+        let channel = content.channel.clone();
+        let due = entry.shown_at + interval - now;
+        if entry.pending.replace(content).is_some() {
+            self.count(&channel, |stats| stats.coalesced += 1);
+            return None;
+        }
+        drop(throttled);
+        // First update held back in this interval: post it once it is over
+        if let Some(scheduler) = self.inner.scheduler.lock().unwrap().clone() {
+            let manager = Arc::downgrade(&self.inner);
+            let name = format!("notification-update:{}", key);
+            let flush = move |_: &JobRun| flush_update(&manager, &key);
+            if let Err(e) = scheduler.schedule(&name, Schedule::Once { delay: due }, None, flush) {
+                log::error!("Failed to schedule held back update of {}: {}", name, e);
+            }
+        }
+        None
+    }
+    
+    /// Posts the update of `key` held back by `throttle`, unless it was
+    /// dismissed meanwhile.
+    fn post_pending(&self, key: &str) {
+        let content = {
+            let mut throttled = self.inner.throttled.lock().unwrap();
+            let Some(entry) = throttled.get_mut(key) else {
+                return;
+            };
+            let Some(content) = entry.pending.take() else {
+                return;
+            };
+            entry.shown_at = self.monotonic();
+            content
+        };
+        if !self.inner.dedupe.lock().unwrap().contains_key(key) {
+            self.count(&content.channel, |stats| stats.coalesced += 1);
+            return;
+        }
+        self.post_now(content);
+    }
+    
+    /// Stores `content` and hands it to Kotlin. Returns its ID and whether
+    /// it replaced the notification under its dedupe key.
+    fn show(&self, content: NotificationContent, group_summary: bool) -> (i32, bool) {
+        let (id, updated) = {
+            // Held while allocating so concurrent posts of a key share one ID
+            let mut dedupe = self.inner.dedupe.lock().unwrap();
+            match content.dedupe_key.as_ref().and_then(|key| dedupe.get(key)) {
+                Some(id) => (*id, true),
+                None => {
+                    let mut next_id = self.inner.next_id.lock().unwrap();
+                    let current_id = *next_id;
+                    *next_id += 1;
+                    if let Some(key) = &content.dedupe_key {
+                        dedupe.insert(key.clone(), current_id);
+                    }
+                    (current_id, false)
+                }
+            }
         };
         
         let timestamp = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
         
         let notification = NotificationData {
             id,
-            title: title.clone(),
-            message: message.clone(),
-            timestamp: timestamp.clone(),
-            priority,
+            channel: content.channel,
+            title: content.title,
+            message: content.message,
+            timestamp,
+            priority: content.priority,
+            style: content.style,
+            progress: content.progress,
+            actions: content.actions,
+            deep_link: content.deep_link,
+            dedupe_key: content.dedupe_key,
+            group: content.group,
+            group_summary,
         };
         
-        // Store notification
-        {
-            let mut notifications = self.notifications.lock().unwrap();
-            notifications.insert(id, notification);
+        // Store notification; it is still shown if persisting it fails
+        if let Err(e) = self.inner.store.lock().unwrap().insert(&notification) {
+            log::error!("Failed to store notification {}: {}", id, e);
         }
         
         // Call callback to notify Android
-        if let Some(callback) = &self.callback {
-            callback(id, title, message, timestamp);
+        if let Some(callback) = self.inner.callback.read().unwrap().as_ref() {
+            callback(&notification);
         }
         
-        id
+        let evicted: Vec<i32> = {
+            let mut notifications = self.inner.notifications.lock().unwrap();
+            notifications.insert(id, notification);
+            let excess = notifications.len().saturating_sub(MAX_NOTIFICATIONS);
+            let oldest: Vec<i32> = notifications.keys().take(excess).copied().collect();
+            for old in &oldest {
+                notifications.remove(old);
+            }
+            oldest
+        };
+        // Dedupe keys still map to evicted IDs, so an update of one that is
+        // still on screen replaces it rather than posting a duplicate
+        for old in evicted {
+            if let Err(e) = self.inner.store.lock().unwrap().remove(old) {
+                log::error!("Failed to remove stored notification {}: {}", old, e);
+            }
+        }
+        
+        (id, updated)
+    }
+    
+    /// Posts or updates the summary of `group` once it has two
+    /// notifications. Summaries bypass the rate limit: they replace one
+    /// another rather than adding to the tray.
+    fn summarize(&self, group: &str, channel: &str) {
+        let lines: Vec<String> = {
+            let notifications = self.inner.notifications.lock().unwrap();
+            notifications
+                .values()
+                .filter(|notification| notification.group.as_deref() == Some(group) && !notification.group_summary)
+                .map(|notification| format!("{}: {}", notification.title, notification.message))
+                .collect()
+        };
+        if lines.len() < 2 {
+            return;
+        }
+        let title = self
+            .inner
+            .channels
+            .lock()
+            .unwrap()
+            .get(channel)
+            .map_or_else(|| group.to_string(), |channel| channel.name.clone());
+        let count = format!("{} notifications", lines.len());
+        let newest = lines.iter().rev().take(MAX_SUMMARY_LINES).cloned().collect();
+        let summary = NotificationContent::new(channel, title, count.clone())
+            .with_style(NotificationStyle::Inbox {
+                lines: newest,
+                summary: Some(count),
+            })
+            .with_group(group)
+            .with_dedupe_key(format!("group_summary:{}", group));
+        self.show(summary, true);
     }
     
+    fn rate_limit(&self, channel: &str) -> Option<RateLimit> {
+        self.inner.channels.lock().unwrap().get(channel).and_then(|channel| channel.rate_limit)
+    }
+    
+    fn monotonic(&self) -> Duration {
+        self.inner.clock.read().unwrap().monotonic()
+    }
+    
+    fn take_token(&self, channel: &str) -> bool {
+        let Some(limit) = self.rate_limit(channel) else {
+            return true;
+        };
+        let now = self.monotonic();
+        let mut buckets = self.inner.buckets.lock().unwrap();
+        let bucket = buckets
+            .entry(channel.to_string())
+            .or_insert_with(|| TokenBucket::new(limit, now));
+        // Re-registering the channel with another limit starts over
+        if bucket.limit() != limit {
+            *bucket = TokenBucket::new(limit, now);
+        }
+        bucket.try_take(now)
+    }
+    
+    fn count(&self, channel: &str, update: impl FnOnce(&mut ChannelStats)) {
+        update(self.inner.stats.lock().unwrap().entry(channel.to_string()).or_default());
+    }
+    
+    /// Posted, updated, coalesced and suppressed counts by channel since the process
+    /// started. Channels that never posted are left out.
+    pub fn stats(&self) -> BTreeMap<String, ChannelStats> {
+        self.inner.stats.lock().unwrap().clone()
+    }
+    
+    /// Changes a posted notification in place, e.g. to advance its
+    /// progress, and shows it again under the same ID. Returns `false` if
+    /// there is no such notification.
+    pub fn update_notification(&self, id: i32, update: impl FnOnce(&mut NotificationData)) -> bool {
+        let notification = {
+            let mut notifications = self.inner.notifications.lock().unwrap();
+            let Some(notification) = notifications.get_mut(&id) else {
+                return false;
+            };
+            update(notification);
+            notification.id = id;
+            notification.clone()
+        };
+        if let Err(e) = self.inner.store.lock().unwrap().insert(&notification) {
+            log::error!("Failed to store notification {}: {}", id, e);
+        }
+        if let Some(callback) = self.inner.callback.read().unwrap().as_ref() {
+            callback(&notification);
+        }
+        true
+    }
+    
+    /// Runs `handler` whenever a button with this action id is tapped,
+    /// replacing any handler registered before.
+    pub fn register_action(&self, action_id: &str, handler: impl Fn(&ActionInvocation) + Send + Sync + 'static) {
+        self.inner
+            .actions
+            .write()
+            .unwrap()
+            .insert(action_id.to_string(), Arc::new(handler));
+    }
+    
+    pub fn unregister_action(&self, action_id: &str) -> bool {
+        self.inner.actions.write().unwrap().remove(action_id).is_some()
+    }
+    
+    /// Called when Kotlin reports a tapped button. Returns `false` if no
+    /// handler is registered for the action.
+    pub fn handle_action(&self, notification_id: i32, action_id: &str) -> bool {
+        // Cloned so the handler runs without the lock and may unregister itself
+        let Some(handler) = self.inner.actions.read().unwrap().get(action_id).cloned() else {
+            log::warn!("No handler for action {} of notification {}", action_id, notification_id);
+            return false;
+        };
+        let notification = self.get_notification(notification_id);
+        if notification
+            .as_ref()
+            .is_some_and(|notification| notification.actions.iter().all(|action| action.id != action_id))
+        {
+            log::warn!("Notification {} has no action {}", notification_id, action_id);
+        }
+        handler(&ActionInvocation {
+            notification_id,
+            action_id: action_id.to_string(),
+            notification,
+        });
+        true
+    }
+    
+    /// Repeated alerts for the same sensor update one notification, and
+    /// alerts of different sensors are bundled together.
     pub fn send_sensor_alert(&self, sensor_name: &str, value: f32, threshold: f32) {
         let title = format!("Sensor Alert: {}", sensor_name);
-        let message = format!("Value {} exceeded threshold {}", value, threshold);
-        self.send_notification(title, message, NotificationPriority::High);
+        let message = format!("Value {:.2} crossed threshold {:.2}", value, threshold);
+        let content = NotificationContent::new(SENSOR_ALERTS_CHANNEL, title, message)
+            .with_priority(NotificationPriority::High)
+            .with_dedupe_key(format!("sensor_alert:{}", sensor_name))
+            .with_group(SENSOR_ALERTS_CHANNEL);
+        self.post(content);
     }
     
     pub fn send_system_notification(&self, title: String, message: String) {
-        self.send_notification(title, message, NotificationPriority::Normal);
+        self.send_notification(DEFAULT_CHANNEL, title, message, NotificationPriority::Normal);
     }
     
     pub fn get_notification(&self, id: i32) -> Option<NotificationData> {
-        let notifications = self.notifications.lock().unwrap();
+        let notifications = self.inner.notifications.lock().unwrap();
         notifications.get(&id).cloned()
     }
     
+    /// Oldest first.
     pub fn get_all_notifications(&self) -> Vec<NotificationData> {
-        let notifications = self.notifications.lock().unwrap();
+        let notifications = self.inner.notifications.lock().unwrap();
         notifications.values().cloned().collect()
     }
     
     pub fn clear_notification(&self, id: i32) -> bool {
-        let mut notifications = self.notifications.lock().unwrap();
-        notifications.remove(&id).is_some()
+        let mut notifications = self.inner.notifications.lock().unwrap();
+        let removed = notifications.remove(&id).is_some();
+        // Also for IDs past MAX_NOTIFICATIONS, whose dedupe keys outlive them
+        let mut throttled = self.inner.throttled.lock().unwrap();
+        self.inner.dedupe.lock().unwrap().retain(|key, shown| {
+            if *shown == id {
+                if let Some(pending) = throttled.remove(key).and_then(|entry| entry.pending) {
+                    self.count(&pending.channel, |stats| stats.coalesced += 1);
+                }
+            }
+            *shown != id
+        });
+        drop(throttled);
+        if removed {
+            if let Err(e) = self.inner.store.lock().unwrap().remove(id) {
+                log::error!("Failed to remove stored notification {}: {}", id, e);
+            }
+        }
+        removed
     }
     
     pub fn clear_all_notifications(&self) {
-        let mut notifications = self.notifications.lock().unwrap();
+        let mut notifications = self.inner.notifications.lock().unwrap();
         notifications.clear();
+        self.inner.dedupe.lock().unwrap().clear();
+        self.inner.throttled.lock().unwrap().clear();
+        if let Err(e) = self.inner.store.lock().unwrap().clear() {
+            log::error!("Failed to clear stored notifications: {}", e);
+        }
+    }
+    
+    /// Stores the schedule and arms its first occurrence. A time in the
+    /// past posts the notification as soon as the scheduler runs.
+    pub fn schedule_notification(&self, request: ScheduleRequest) -> Result<ScheduleId> {
+        if let Some(repeat) = &request.repeat {
+            repeat.validate()?;
+        }
+        let next_at = request.when.first(self.now())?;
+        let id = {
+            let mut next_schedule_id = self.inner.next_schedule_id.lock()?;
+            let current_id = ScheduleId(*next_schedule_id);
+            *next_schedule_id += 1;
+            current_id
+        };
+        let schedule = ScheduledNotification {
+            id,
+            channel: request.channel,
+            title: request.title,
+            message: request.message,
+            priority: request.priority,
+            next_at,
+            repeat: request.repeat,
+            fired: 0,
+        };
+        
+        // Unlike posted notifications, a schedule that is not stored would
+        // silently vanish with the process
+        self.inner.store.lock()?.save_schedule(&schedule)?;
+        self.inner.schedules.lock()?.insert(id, schedule.clone());
+        log::info!("Scheduled notification {} for {}", id.0, next_at);
+        self.arm(&schedule);
+        Ok(id)
+    }
+    
+    /// Returns `false` for unknown or already finished schedules.
+    pub fn cancel_scheduled_notification(&self, id: ScheduleId) -> bool {
+        if self.inner.schedules.lock().unwrap().remove(&id).is_none() {
+            return false;
+        }
+        self.disarm(id);
+        if let Err(e) = self.inner.store.lock().unwrap().remove_schedule(id) {
+            log::error!("Failed to remove stored schedule {}: {}", id.0, e);
+        }
+        log::info!("Cancelled scheduled notification {}", id.0);
+        true
+    }
+    
+    /// Pending schedules by ID.
+    pub fn scheduled_notifications(&self) -> Vec<ScheduledNotification> {
+        self.inner.schedules.lock().unwrap().values().cloned().collect()
+    }
+    
+    fn now(&self) -> chrono::DateTime<chrono::Utc> {
+        self.inner.clock.read().unwrap().wall()
+    }
+    
+    fn arm_all(&self) {
+        let schedules = self.scheduled_notifications();
+        let stale: Vec<ScheduleId> = self.inner.timers.lock().unwrap().keys().copied().collect();
+        for id in stale {
+            self.disarm(id);
+        }
+        for schedule in &schedules {
+            self.arm(schedule);
+        }
+    }
+    
+    /// Replaces the schedule's job with one due at `next_at`.
+    fn arm(&self, schedule: &ScheduledNotification) {
+        let Some(scheduler) = self.inner.scheduler.lock().unwrap().clone() else {
+            return;
+        };
+        let delay = (schedule.next_at - scheduler.now()).to_std().unwrap_or_default();
+        let id = schedule.id;
+        let manager = Arc::downgrade(&self.inner);
+        // Held while scheduling so a job that runs right away re-arms after
+        // this one is recorded
+        let mut timers = self.inner.timers.lock().unwrap();
+        if let Some(previous) = timers.remove(&id) {
+            scheduler.cancel(previous);
+        }
+        let name = format!("notification:{}", id.0);
+        match scheduler.schedule(&name, Schedule::Once { delay }, None, move |_| fire(&manager, id)) {
+            Ok(job) => {
+                timers.insert(id, job);
+            }
+            Err(e) => log::error!("Failed to arm scheduled notification {}: {}", id.0, e),
+        }
+    }
+    
+    fn disarm(&self, id: ScheduleId) {
+        let job = self.inner.timers.lock().unwrap().remove(&id);
+        if let (Some(job), Some(scheduler)) = (job, self.inner.scheduler.lock().unwrap().as_ref()) {
+            scheduler.cancel(job);
+        }
+    }
+    
+    /// Posts a schedule's due occurrence, then arms the next one or drops
+    /// the schedule.
+    fn post_scheduled(&self, id: ScheduleId) {
+        // Cancelled while the job was queued
+        let Some(mut schedule) = self.inner.schedules.lock().unwrap().get(&id).cloned() else {
+            return;
+        };
+        self.send_notification(
+            &schedule.channel,
+            schedule.title.clone(),
+            schedule.message.clone(),
+            schedule.priority,
+        );
+        
+        if schedule.advance(self.now()) {
+            let mut schedules = self.inner.schedules.lock().unwrap();
+            if !schedules.contains_key(&id) {
+                return;
+            }
+            schedules.insert(id, schedule.clone());
+            drop(schedules);
+            if let Err(e) = self.inner.store.lock().unwrap().save_schedule(&schedule) {
+                log::error!("Failed to store schedule {}: {}", id.0, e);
+            }
+            self.arm(&schedule);
+        } else if self.inner.schedules.lock().unwrap().remove(&id).is_some() {
+            self.inner.timers.lock().unwrap().remove(&id);
+            if let Err(e) = self.inner.store.lock().unwrap().remove_schedule(id) {
+                log::error!("Failed to remove stored schedule {}: {}", id.0, e);
+            }
+            log::info!("Scheduled notification {} finished after {} posts", id.0, schedule.fired);
+        }
+    }
+}
+
+/// Scheduler job body; does nothing once the manager is gone.
+fn fire(manager: &Weak<Inner>, id: ScheduleId) {
+    if let Some(inner) = manager.upgrade() {
+        NotificationManager { inner }.post_scheduled(id);
+    }
+}
+
+fn flush_update(manager: &Weak<Inner>, key: &str) {
+    if let Some(inner) = manager.upgrade() {
+        NotificationManager { inner }.post_pending(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scheduler::ManualClock;
+    use chrono::{TimeZone, Utc};
+    use schedule::{Repeat, RepeatRule, Trigger};
+    use std::time::Duration;
+    use store::tests::TempPath;
+    use store::{FileStore, SqliteStore};
+
+    fn manual_scheduler() -> (Scheduler, Arc<ManualClock>) {
+        let clock = Arc::new(ManualClock::new(Utc.with_ymd_and_hms(2026, 3, 2, 8, 0, 0).unwrap()));
+        let scheduler = Scheduler::new(clock.clone(), |run| {
+            run();
+            Ok(())
+        });
+        (scheduler, clock)
     }
-} 
\ No newline at end of file
+
+    fn reminder(when: Trigger, repeat: Option<RepeatRule>) -> ScheduleRequest {
+        ScheduleRequest {
+            when,
+            channel: DEFAULT_CHANNEL.to_string(),
+            title: "Stretch".to_string(),
+            message: "Time to move".to_string(),
+            priority: NotificationPriority::Normal,
+            repeat,
+        }
+    }
+
+    /// Advances the clock a minute at a time for `minutes`.
+    fn run_minutes(scheduler: &Scheduler, clock: &ManualClock, minutes: u64) {
+        for _ in 0..minutes {
+            clock.advance(Duration::from_secs(60));
+            scheduler.run_due();
+        }
+    }
+
+    #[test]
+    fn ids_continue_across_restarts() {
+        let path = TempPath::new("notifications.db");
+        let open = || NotificationManager::with_store(Box::new(SqliteStore::open(&path.0).unwrap())).unwrap();
+
+        let manager = open();
+        let first = manager.send_notification(DEFAULT_CHANNEL, "a".to_string(), "1".to_string(), NotificationPriority::Low);
+        let second = manager.send_notification(DEFAULT_CHANNEL, "b".to_string(), "2".to_string(), NotificationPriority::Urgent);
+        assert_eq!((first, second), (Some(1), Some(2)));
+        assert!(manager.clear_notification(2));
+        drop(manager);
+
+        let manager = open();
+        let history = manager.get_all_notifications();
+        assert_eq!(history.len(), 1);
+        assert_eq!((history[0].id, history[0].title.as_str()), (1, "a"));
+        manager.clear_all_notifications();
+        assert_eq!(manager.send_notification(DEFAULT_CHANNEL, "c".to_string(), "3".to_string(), NotificationPriority::Normal), Some(3));
+    }
+
+    #[test]
+    fn switching_stores_never_reuses_ids() {
+        let path = TempPath::new("notifications.jsonl");
+        let manager = NotificationManager::new();
+        for _ in 0..3 {
+            manager.send_system_notification("t".to_string(), "m".to_string());
+        }
+
+        // The file is empty, but 1..=3 may still be in the tray
+        assert_eq!(manager.set_store(Box::new(FileStore::open(&path.0).unwrap())).unwrap(), 0);
+        assert!(manager.get_all_notifications().is_empty());
+        assert_eq!(manager.send_notification(DEFAULT_CHANNEL, "t".to_string(), "m".to_string(), NotificationPriority::Normal), Some(4));
+
+        let reopened = NotificationManager::with_store(Box::new(FileStore::open(&path.0).unwrap())).unwrap();
+        assert_eq!(reopened.get_notification(4).unwrap().title, "t");
+    }
+
+    #[test]
+    fn keeps_only_the_newest_notifications() {
+        let path = TempPath::new("notifications.jsonl");
+        let manager = NotificationManager::with_store(Box::new(FileStore::open(&path.0).unwrap())).unwrap();
+        let progress = NotificationContent::new(DEFAULT_CHANNEL, "Download", "0%").with_dedupe_key("download");
+        let first = manager.post_notification(progress.clone()).unwrap();
+        for _ in 0..MAX_NOTIFICATIONS {
+            manager.send_system_notification("t".to_string(), "m".to_string());
+        }
+
+        let history = manager.get_all_notifications();
+        assert_eq!(history.len(), MAX_NOTIFICATIONS);
+        assert_eq!(history[0].id, first + 1);
+        let reopened = NotificationManager::with_store(Box::new(FileStore::open(&path.0).unwrap())).unwrap();
+        assert_eq!(reopened.get_all_notifications(), history);
+
+        // A key that may still be on screen keeps its ID until dismissed
+        assert_eq!(manager.post_notification(progress.clone()).unwrap(), first);
+        assert!(!manager.clear_notification(first));
+        assert_ne!(manager.post_notification(progress).unwrap(), first);
+    }
+
+    #[test]
+    fn channels_are_announced_and_referenced() {
+        let announced = Arc::new(Mutex::new(Vec::new()));
+        let posted = Arc::new(Mutex::new(Vec::new()));
+        let mut manager = NotificationManager::new();
+        let sink = announced.clone();
+        manager.set_channel_callback(move |channel| sink.lock().unwrap().push(channel.id.clone()));
+        let sink = posted.clone();
+        manager.set_callback(move |notification| sink.lock().unwrap().push(notification.channel.clone()));
+        assert_eq!(*announced.lock().unwrap(), vec![DEFAULT_CHANNEL, SENSOR_ALERTS_CHANNEL]);
+
+        let downloads = NotificationChannel::new("downloads", "Downloads", channels::Importance::Low);
+        manager.register_channel(downloads.clone()).unwrap();
+        manager.register_channel(downloads).unwrap();
+        assert_eq!(announced.lock().unwrap().len(), 3);
+
+        manager.send_notification("downloads", "t".to_string(), "m".to_string(), NotificationPriority::Low);
+        manager.send_notification("missing", "t".to_string(), "m".to_string(), NotificationPriority::Low);
+        manager.send_sensor_alert("Gyroscope magnitude", 5.0, 4.0);
+        assert_eq!(*posted.lock().unwrap(), vec!["downloads", DEFAULT_CHANNEL, SENSOR_ALERTS_CHANNEL]);
+    }
+
+    #[test]
+    fn actions_route_back_and_progress_updates_in_place() {
+        let path = TempPath::new("notifications.jsonl");
+        let shown = Arc::new(Mutex::new(Vec::new()));
+        let mut manager = NotificationManager::with_store(Box::new(FileStore::open(&path.0).unwrap())).unwrap();
+        let sink = shown.clone();
+        manager.set_callback(move |notification| sink.lock().unwrap().push(notification.clone()));
+        let tapped = Arc::new(Mutex::new(Vec::new()));
+        let sink = tapped.clone();
+        manager.register_action("stop", move |invocation| {
+            sink.lock().unwrap().push((invocation.notification_id, invocation.notification.is_some()));
+        });
+
+        let content = NotificationContent::new(DEFAULT_CHANNEL, "Upload", "Starting")
+            .with_action(NotificationAction::new("stop", "Stop"))
+            .with_deep_link("modularapp://uploads");
+        let progress = progress::ProgressNotification::start(&manager, content).unwrap();
+        progress.update(30, 100, "30%");
+        progress.update(150, 100, "done soon");
+        assert!(manager.handle_action(progress.id(), "stop"));
+        assert!(!manager.handle_action(progress.id(), "pause"));
+        progress.finish("Uploaded");
+
+        let shown = shown.lock().unwrap();
+        assert!(shown.iter().all(|notification| notification.id == progress.id()));
+        let bars: Vec<Option<Progress>> = shown.iter().map(|notification| notification.progress).collect();
+        assert_eq!(
+            bars,
+            vec![
+                Some(Progress::Indeterminate),
+                Some(Progress::Determinate { current: 30, max: 100 }),
+                Some(Progress::Determinate { current: 100, max: 100 }),
+                None,
+            ]
+        );
+        assert_eq!(shown[1].actions.len(), 1);
+        assert!(shown[3].actions.is_empty());
+        assert_eq!(*tapped.lock().unwrap(), vec![(progress.id(), true)]);
+
+        // Stored as last shown, without the empty rich fields
+        let reopened = NotificationManager::with_store(Box::new(FileStore::open(&path.0).unwrap())).unwrap();
+        let stored = reopened.get_notification(progress.id()).unwrap();
+        assert_eq!((stored.message.as_str(), stored.deep_link.as_deref()), ("Uploaded", Some("modularapp://uploads")));
+        assert!(!serde_json::to_string(&stored).unwrap().contains("progress"));
+        assert!(manager
+            .post_notification(NotificationContent::new(DEFAULT_CHANNEL, "t", "m").with_deep_link("nowhere"))
+            .is_err());
+    }
+
+    #[test]
+    fn alerts_are_deduplicated_grouped_and_rate_limited() {
+        let (scheduler, clock) = manual_scheduler();
+        let shown = Arc::new(Mutex::new(Vec::new()));
+        let mut manager = NotificationManager::new();
+        manager.set_scheduler(scheduler.clone());
+        let sink = shown.clone();
+        manager.set_callback(move |notification| sink.lock().unwrap().push(notification.clone()));
+
+        // Repeated alerts update one notification, at most once per refill
+        // interval; the newest one waits for the interval to end
+        for i in 0..20 {
+            manager.send_sensor_alert("Accelerometer magnitude", 20.0 + i as f32, 15.0);
+        }
+        let stats = manager.stats()[SENSOR_ALERTS_CHANNEL];
+        assert_eq!((stats.posted, stats.updated, stats.coalesced, stats.suppressed), (1, 0, 18, 0));
+        assert_eq!(manager.get_notification(1).unwrap().message, "Value 20.00 crossed threshold 15.00");
+        assert_eq!(shown.lock().unwrap().len(), 1);
+
+        // New notifications: burst of 5, one more every 10 s
+        for sensor in ["Gyroscope", "Magnetometer", "Light", "Pressure", "Proximity"] {
+            manager.send_sensor_alert(sensor, 5.0, 4.0);
+        }
+        let stats = manager.stats()[SENSOR_ALERTS_CHANNEL];
+        assert_eq!((stats.posted, stats.suppressed), (5, 1));
+        clock.advance(Duration::from_secs(10));
+        scheduler.run_due();
+        assert_eq!(manager.get_notification(1).unwrap().message, "Value 39.00 crossed threshold 15.00");
+        manager.send_sensor_alert("Proximity", 5.0, 4.0);
+        manager.send_sensor_alert("Gyroscope", 6.0, 4.0);
+        let stats = manager.stats()[SENSOR_ALERTS_CHANNEL];
+        assert_eq!((stats.posted, stats.updated, stats.coalesced, stats.suppressed), (6, 2, 18, 1));
+
+        // Six alerts and their summary, updated in place
+        let history = manager.get_all_notifications();
+        assert_eq!(history.len(), 7);
+        let summary = history.iter().find(|notification| notification.group_summary).unwrap();
+        assert_eq!(summary.group.as_deref(), Some(SENSOR_ALERTS_CHANNEL));
+        assert!(matches!(&summary.style, Some(NotificationStyle::Inbox { lines, .. }) if lines.len() == MAX_SUMMARY_LINES));
+        let mut ids: Vec<i32> = shown.lock().unwrap().iter().map(|notification| notification.id).collect();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids, (1..=7).collect::<Vec<i32>>());
+
+        // A dismissed alert drops its waiting update, posts anew and takes a
+        // token again
+        manager.send_sensor_alert("Accelerometer magnitude", 39.5, 15.0);
+        assert!(manager.clear_notification(1));
+        assert_eq!(manager.stats()[SENSOR_ALERTS_CHANNEL].coalesced, 19);
+        manager.send_sensor_alert("Accelerometer magnitude", 40.0, 15.0);
+        assert_eq!(manager.stats()[SENSOR_ALERTS_CHANNEL].suppressed, 2);
+        clock.advance(Duration::from_secs(10));
+        manager.send_sensor_alert("Accelerometer magnitude", 41.0, 15.0);
+        assert_eq!(manager.stats()[SENSOR_ALERTS_CHANNEL].posted, 7);
+        assert!(manager.get_notification(1).is_none());
+
+        // Unlimited channels are never suppressed; a cleared key posts anew
+        let content = || NotificationContent::new(DEFAULT_CHANNEL, "Sync", "Running").with_dedupe_key("sync");
+        let id = manager.post_notification(content()).unwrap();
+        assert_eq!(manager.post_notification(content()).unwrap(), id);
+        assert!(manager.clear_notification(id));
+        assert_ne!(manager.post_notification(content()).unwrap(), id);
+        assert_eq!(manager.stats()[DEFAULT_CHANNEL].suppressed, 0);
+    }
+
+    #[test]
+    fn repeating_schedules_post_until_done() {
+        let (scheduler, clock) = manual_scheduler();
+        let manager = NotificationManager::new();
+        manager.set_scheduler(scheduler.clone());
+        let every_ten_minutes = RepeatRule {
+            count: Some(3),
+            ..RepeatRule::new(Repeat::Interval { every_ms: 600_000 })
+        };
+        let repeating = manager
+            .schedule_notification(reminder(Trigger::After(Duration::from_secs(300)), Some(every_ten_minutes)))
+            .unwrap();
+        let cancelled = manager
+            .schedule_notification(reminder(Trigger::After(Duration::from_secs(1200)), None))
+            .unwrap();
+
+        run_minutes(&scheduler, &clock, 4);
+        assert!(manager.get_all_notifications().is_empty());
+        run_minutes(&scheduler, &clock, 1);
+        assert_eq!(manager.get_all_notifications().len(), 1);
+        assert_eq!(manager.scheduled_notifications()[0].next_at, Utc.with_ymd_and_hms(2026, 3, 2, 8, 15, 0).unwrap());
+
+        assert!(manager.cancel_scheduled_notification(cancelled));
+        assert!(!manager.cancel_scheduled_notification(cancelled));
+        run_minutes(&scheduler, &clock, 60);
+        assert_eq!(manager.get_all_notifications().len(), 3);
+        assert!(manager.scheduled_notifications().is_empty());
+        assert!(!manager.cancel_scheduled_notification(repeating));
+    }
+
+    #[test]
+    fn schedules_are_rearmed_after_a_restart() {
+        let path = TempPath::new("notifications.db");
+        let (scheduler, _) = manual_scheduler();
+        let manager = NotificationManager::with_store(Box::new(SqliteStore::open(&path.0).unwrap())).unwrap();
+        manager.set_scheduler(scheduler);
+        let at = Utc.with_ymd_and_hms(2026, 3, 2, 9, 0, 0).unwrap();
+        let daily = manager
+            .schedule_notification(reminder(Trigger::At(at), Some(RepeatRule::new(Repeat::Daily))))
+            .unwrap();
+        drop(manager);
+
+        // Restarted at 9:30: the missed 9:00 is posted once, late
+        let (scheduler, clock) = manual_scheduler();
+        clock.advance(Duration::from_secs(90 * 60));
+        let manager = NotificationManager::with_store(Box::new(SqliteStore::open(&path.0).unwrap())).unwrap();
+        manager.set_scheduler(scheduler.clone());
+        scheduler.run_due();
+        assert_eq!(manager.get_all_notifications().len(), 1);
+        let pending = manager.scheduled_notifications();
+        assert_eq!((pending[0].id, pending[0].fired), (daily, 1));
+        assert_eq!(pending[0].next_at, at + chrono::Duration::days(1));
+
+        let next = manager
+            .schedule_notification(reminder(Trigger::After(Duration::from_secs(60)), None))
+            .unwrap();
+        assert_eq!(next, ScheduleId(daily.0 + 1));
+    }
+}
@@ -0,0 +1,187 @@
+//! Notification channels, mirroring Android's `NotificationChannel`.
+//!
+//! Channels are declared in Rust and announced to Kotlin, which creates the
+//! matching Android channel before the first notification that uses it.
+//! Android keeps a channel's importance and sound once the user has seen
+//! it, so re-declaring a channel only updates its name and description there.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::limits::RateLimit;
+use crate::error::{Error, Result};
+
+/// Channel for notifications that do not name one.
+pub const DEFAULT_CHANNEL: &str = "general";
+/// Channel of `NotificationManager::send_sensor_alert`.
+pub const SENSOR_ALERTS_CHANNEL: &str = "sensor_alerts";
+
+/// `NotificationManager.IMPORTANCE_*`, from silent and hidden to heads-up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Importance {
+    None,
+    Min,
+    Low,
+    #[default]
+    Default,
+    High,
+}
+
+/// A channel as declared from Rust or Kotlin, e.g.
+/// `{"id":"downloads","name":"Downloads","importance":"low","sound":false,"group":"transfers"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotificationChannel {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub importance: Importance,
+    #[serde(default = "enabled")]
+    pub sound: bool,
+    #[serde(default = "enabled")]
+    pub vibration: bool,
+    /// Id of the Android channel group shown as a heading in the settings.
+    #[serde(default)]
+    pub group: Option<String>,
+    /// Unlimited if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<RateLimit>,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl NotificationChannel {
+    pub fn new(id: &str, name: &str, importance: Importance) -> Self {
+        Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            description: String::new(),
+            importance,
+            sound: true,
+            vibration: true,
+            group: None,
+            rate_limit: None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.id.trim().is_empty() {
+            return Err(Error::InvalidArgument("channel id must not be empty".to_string()));
+        }
+        if self.name.trim().is_empty() {
+            return Err(Error::InvalidArgument(format!("channel {} needs a name", self.id)));
+        }
+        if self.group.as_deref().is_some_and(|group| group.trim().is_empty()) {
+            return Err(Error::InvalidArgument(format!("channel {} has an empty group", self.id)));
+        }
+        if let Some(limit) = &self.rate_limit {
+            limit.validate()?;
+        }
+        Ok(())
+    }
+}
+
+/// Declared channels by id. Starts with `DEFAULT_CHANNEL` and
+/// `SENSOR_ALERTS_CHANNEL`.
+pub struct ChannelRegistry {
+    channels: BTreeMap<String, NotificationChannel>,
+}
+
+impl ChannelRegistry {
+    pub fn new() -> Self {
+        let mut registry = Self {
+            channels: BTreeMap::new(),
+        };
+        let mut general = NotificationChannel::new(DEFAULT_CHANNEL, "General", Importance::Default);
+        general.description = "Notifications from the modular app".to_string();
+        let mut alerts = NotificationChannel::new(SENSOR_ALERTS_CHANNEL, "Sensor alerts", Importance::High);
+        alerts.description = "Sensor readings crossing a rule's threshold".to_string();
+        alerts.rate_limit = Some(RateLimit {
+            burst: 5,
+            refill_ms: 10_000,
+        });
+        for channel in [general, alerts] {
+            registry.channels.insert(channel.id.clone(), channel);
+        }
+        registry
+    }
+
+    /// Adds or replaces a channel. Returns `false` if an identical one was
+    /// already registered.
+    pub fn register(&mut self, channel: NotificationChannel) -> Result<bool> {
+        channel.validate()?;
+        if self.channels.get(&channel.id) == Some(&channel) {
+            return Ok(false);
+        }
+        self.channels.insert(channel.id.clone(), channel);
+        Ok(true)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&NotificationChannel> {
+        self.channels.get(id)
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.channels.contains_key(id)
+    }
+
+    /// By id.
+    pub fn list(&self) -> Vec<NotificationChannel> {
+        self.channels.values().cloned().collect()
+    }
+}
+
+impl Default for ChannelRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starts_with_the_builtin_channels() {
+        let registry = ChannelRegistry::new();
+        let ids: Vec<String> = registry.list().into_iter().map(|channel| channel.id).collect();
+        assert_eq!(ids, vec![DEFAULT_CHANNEL, SENSOR_ALERTS_CHANNEL]);
+        assert_eq!(registry.get(SENSOR_ALERTS_CHANNEL).unwrap().importance, Importance::High);
+        assert!(registry.get(SENSOR_ALERTS_CHANNEL).unwrap().rate_limit.is_some());
+    }
+
+    #[test]
+    fn registering_reports_changes_only() {
+        let mut registry = ChannelRegistry::new();
+        let channel: NotificationChannel =
+            serde_json::from_str(r#"{"id":"downloads","name":"Downloads","importance":"low","group":"transfers"}"#)
+                .unwrap();
+        assert!(channel.sound && channel.vibration);
+        assert!(registry.register(channel.clone()).unwrap());
+        assert!(!registry.register(channel.clone()).unwrap());
+        assert!(registry
+            .register(NotificationChannel {
+                sound: false,
+                ..channel
+            })
+            .unwrap());
+        assert!(!registry.get("downloads").unwrap().sound);
+    }
+
+    #[test]
+    fn rejects_invalid_channels() {
+        let mut registry = ChannelRegistry::new();
+        assert!(registry.register(NotificationChannel::new(" ", "Name", Importance::Low)).is_err());
+        assert!(registry.register(NotificationChannel::new("id", "", Importance::Low)).is_err());
+        let mut grouped = NotificationChannel::new("id", "Name", Importance::Low);
+        grouped.group = Some(String::new());
+        assert!(registry.register(grouped).is_err());
+        let mut unlimited = NotificationChannel::new("id", "Name", Importance::Low);
+        unlimited.rate_limit = Some(RateLimit { burst: 1, refill_ms: 0 });
+        assert!(registry.register(unlimited).is_err());
+    }
+}
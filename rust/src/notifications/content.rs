@@ -0,0 +1,228 @@
+//! What a notification shows beyond its title and message: action
+//! buttons, a progress bar, an expanded style and a deep link.
+//!
+//! Kotlin builds the Android notification from these fields. A tapped
+//! action comes back through `NotificationManager::handle_action`, which
+//! runs the handler registered under the action's id.
+
+use serde::{Deserialize, Serialize};
+
+use super::{default_channel, NotificationData, NotificationPriority};
+use crate::error::{Error, Result};
+
+/// Android shows at most three action buttons.
+pub const MAX_ACTIONS: usize = 3;
+
+/// A button on a notification. `id` names the Rust handler that runs
+/// when it is tapped.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotificationAction {
+    pub id: String,
+    pub title: String,
+    /// Whether Kotlin removes the notification once the button is tapped.
+    #[serde(default = "dismiss_by_default")]
+    pub dismiss: bool,
+}
+
+fn dismiss_by_default() -> bool {
+    true
+}
+
+impl NotificationAction {
+    pub fn new(id: impl Into<String>, title: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            title: title.into(),
+            dismiss: true,
+        }
+    }
+}
+
+/// Passed to an action handler when its button was tapped.
+#[derive(Debug, Clone)]
+pub struct ActionInvocation {
+    pub notification_id: i32,
+    pub action_id: String,
+    /// `None` if the notification was cleared from the history, e.g. by a
+    /// restart with the memory store, while still shown in the tray.
+    pub notification: Option<NotificationData>,
+}
+
+/// A progress bar, e.g. `{"type":"determinate","current":30,"max":100}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Progress {
+    Determinate { current: u64, max: u64 },
+    /// An animated bar for work of unknown length.
+    Indeterminate,
+}
+
+/// How the notification expands, e.g. `{"type":"big_text","text":"..."}`
+/// or `{"type":"inbox","lines":["a","b"],"summary":"+3 more"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NotificationStyle {
+    /// Long text shown in full when expanded, instead of the message.
+    BigText { text: String },
+    /// One line per item, like an email inbox.
+    Inbox {
+        lines: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        summary: Option<String>,
+    },
+}
+
+/// A notification to post, as passed to `NotificationManager::post_notification`
+/// or from Kotlin as JSON to `postNotification`, e.g.
+/// `{"title":"Upload","message":"3 files","progress":{"type":"indeterminate"},"actions":[{"id":"stop_upload","title":"Stop"}],"deep_link":"modularapp://uploads"}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NotificationContent {
+    #[serde(default = "default_channel")]
+    pub channel: String,
+    pub title: String,
+    pub message: String,
+    #[serde(default)]
+    pub priority: NotificationPriority,
+    #[serde(default)]
+    pub style: Option<NotificationStyle>,
+    #[serde(default)]
+    pub progress: Option<Progress>,
+    #[serde(default)]
+    pub actions: Vec<NotificationAction>,
+    /// URI opened when the notification itself is tapped.
+    #[serde(default)]
+    pub deep_link: Option<String>,
+    /// Posting again with the same key updates the notification still
+    /// shown under it instead of adding another.
+    #[serde(default)]
+    pub dedupe_key: Option<String>,
+    /// Notifications of a group are bundled under a summary once there
+    /// are two of them.
+    #[serde(default)]
+    pub group: Option<String>,
+}
+
+impl NotificationContent {
+    pub fn new(channel: &str, title: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            channel: channel.to_string(),
+            title: title.into(),
+            message: message.into(),
+            priority: NotificationPriority::Normal,
+            style: None,
+            progress: None,
+            actions: Vec::new(),
+            deep_link: None,
+            dedupe_key: None,
+            group: None,
+        }
+    }
+
+    pub fn with_priority(mut self, priority: NotificationPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn with_style(mut self, style: NotificationStyle) -> Self {
+        self.style = Some(style);
+        self
+    }
+
+    pub fn with_progress(mut self, progress: Progress) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    pub fn with_action(mut self, action: NotificationAction) -> Self {
+        self.actions.push(action);
+        self
+    }
+
+    pub fn with_deep_link(mut self, uri: impl Into<String>) -> Self {
+        self.deep_link = Some(uri.into());
+        self
+    }
+
+    pub fn with_dedupe_key(mut self, key: impl Into<String>) -> Self {
+        self.dedupe_key = Some(key.into());
+        self
+    }
+
+    pub fn with_group(mut self, group: impl Into<String>) -> Self {
+        self.group = Some(group.into());
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for (name, value) in [("dedupe key", &self.dedupe_key), ("group", &self.group)] {
+            if value.as_deref().is_some_and(|value| value.trim().is_empty()) {
+                return Err(Error::InvalidArgument(format!("{} must not be empty", name)));
+            }
+        }
+        if self.actions.len() > MAX_ACTIONS {
+            return Err(Error::InvalidArgument(format!(
+                "at most {} actions are shown, got {}",
+                MAX_ACTIONS,
+                self.actions.len()
+            )));
+        }
+        for (i, action) in self.actions.iter().enumerate() {
+            if action.id.trim().is_empty() || action.title.trim().is_empty() {
+                return Err(Error::InvalidArgument("actions need an id and a title".to_string()));
+            }
+            if self.actions[..i].iter().any(|other| other.id == action.id) {
+                return Err(Error::InvalidArgument(format!("duplicate action id {}", action.id)));
+            }
+        }
+        if let Some(Progress::Determinate { current, max }) = self.progress {
+            if max == 0 || current > max {
+                return Err(Error::InvalidArgument(format!("invalid progress {}/{}", current, max)));
+            }
+        }
+        if let Some(uri) = &self.deep_link {
+            // Android needs a scheme to resolve the intent
+            if !uri.split_once(':').is_some_and(|(scheme, rest)| !scheme.is_empty() && !rest.is_empty()) {
+                return Err(Error::InvalidArgument(format!("deep link needs a scheme: {:?}", uri)));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rich_content() {
+        let content: NotificationContent = serde_json::from_str(
+            r#"{"title":"Upload","message":"3 files","style":{"type":"inbox","lines":["a.jpg","b.jpg"]},
+                "progress":{"type":"determinate","current":1,"max":3},
+                "actions":[{"id":"stop_upload","title":"Stop","dismiss":false}],"deep_link":"modularapp://uploads"}"#,
+        )
+        .unwrap();
+        assert_eq!(content.channel, "general");
+        assert_eq!(content.progress, Some(Progress::Determinate { current: 1, max: 3 }));
+        assert!(!content.actions[0].dismiss);
+        assert!(matches!(content.style, Some(NotificationStyle::Inbox { summary: None, .. })));
+        content.validate().unwrap();
+    }
+
+    #[test]
+    fn rejects_invalid_content() {
+        let base = NotificationContent::new("general", "t", "m");
+        let too_many = (0..4).fold(base.clone(), |content, i| {
+            content.with_action(NotificationAction::new(format!("a{}", i), "A"))
+        });
+        assert!(too_many.validate().is_err());
+        let duplicate = base
+            .clone()
+            .with_action(NotificationAction::new("a", "A"))
+            .with_action(NotificationAction::new("a", "B"));
+        assert!(duplicate.validate().is_err());
+        let overshoot = base.clone().with_progress(Progress::Determinate { current: 5, max: 4 });
+        assert!(overshoot.validate().is_err());
+        assert!(base.clone().with_deep_link("/settings").validate().is_err());
+        assert!(base.with_deep_link("https://example.com/a").validate().is_ok());
+    }
+}
@@ -0,0 +1,110 @@
+//! Per-channel rate limits, so a source firing at sensor rate cannot
+//! flood the tray.
+//!
+//! Each channel with a `RateLimit` gets a token bucket: a new notification
+//! takes a token, tokens come back one per `refill_ms`, and at most `burst`
+//! are saved up. Posts without a token are suppressed and counted. Updates
+//! of a notification still shown under its dedupe key need no token, but
+//! are shown at most once per `refill_ms`; the newest one held back is
+//! posted when that time is up and the ones it replaced are counted.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+
+/// E.g. `{"burst":5,"refill_ms":10000}`: five at once, then one every 10 s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub burst: u32,
+    pub refill_ms: u64,
+}
+
+impl RateLimit {
+    pub fn validate(&self) -> Result<()> {
+        if self.burst == 0 || self.refill_ms == 0 {
+            return Err(Error::InvalidArgument(format!(
+                "rate limit needs a positive burst and refill, got {}/{} ms",
+                self.burst, self.refill_ms
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Starts full. Times are `Clock::monotonic` readings.
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    last: Duration,
+}
+
+impl TokenBucket {
+    pub fn new(limit: RateLimit, now: Duration) -> Self {
+        Self {
+            limit,
+            tokens: limit.burst as f64,
+            last: now,
+        }
+    }
+
+    pub fn limit(&self) -> RateLimit {
+        self.limit
+    }
+
+    pub fn try_take(&mut self, now: Duration) -> bool {
+        let elapsed = now.saturating_sub(self.last);
+        self.last = self.last.max(now);
+        let refilled = elapsed.as_secs_f64() * 1000.0 / self.limit.refill_ms as f64;
+        self.tokens = (self.tokens + refilled).min(self.limit.burst as f64);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// What happened to a channel's notifications since the process started,
+/// as returned by `getNotificationStats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ChannelStats {
+    /// New notifications shown.
+    pub posted: u64,
+    /// Notifications replaced in place through their dedupe key.
+    pub updated: u64,
+    /// Updates never shown because a newer one replaced them while the
+    /// rate limit held them back, or their notification was dismissed.
+    pub coalesced: u64,
+    /// New notifications dropped by the rate limit.
+    pub suppressed: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bursts_then_refills_one_token_at_a_time() {
+        let limit = RateLimit {
+            burst: 3,
+            refill_ms: 1000,
+        };
+        let mut bucket = TokenBucket::new(limit, Duration::ZERO);
+        let taken = (0..5).filter(|_| bucket.try_take(Duration::ZERO)).count();
+        assert_eq!(taken, 3);
+
+        assert!(!bucket.try_take(Duration::from_millis(999)));
+        assert!(bucket.try_take(Duration::from_millis(1000)));
+        assert!(!bucket.try_take(Duration::from_millis(1500)));
+
+        // A long pause saves up no more than the burst
+        let later = Duration::from_secs(60);
+        let taken = (0..5).filter(|_| bucket.try_take(later)).count();
+        assert_eq!(taken, 3);
+        assert!(limit.validate().is_ok());
+        assert!(RateLimit { burst: 0, refill_ms: 1 }.validate().is_err());
+    }
+}
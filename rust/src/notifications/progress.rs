@@ -0,0 +1,63 @@
+//! A notification that follows a piece of work, updated in place.
+
+use super::content::{NotificationAction, NotificationContent, Progress};
+use super::NotificationManager;
+use crate::error::Result;
+
+/// Shows the progress of one piece of work under a single notification ID.
+/// Updates replace the bar and message; `finish` leaves a final message
+/// without the bar or buttons, which only made sense while it ran.
+pub struct ProgressNotification {
+    manager: NotificationManager,
+    id: i32,
+}
+
+impl ProgressNotification {
+    /// Posts `content`, with an indeterminate bar unless it has a progress.
+    pub fn start(manager: &NotificationManager, mut content: NotificationContent) -> Result<Self> {
+        content.progress.get_or_insert(Progress::Indeterminate);
+        let id = manager.post_notification(content)?;
+        Ok(Self {
+            manager: manager.clone(),
+            id,
+        })
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    /// Shows `current` of `max`; `current` is clamped to `max`.
+    pub fn update(&self, current: u64, max: u64, message: impl Into<String>) {
+        let progress = if max == 0 {
+            Progress::Indeterminate
+        } else {
+            Progress::Determinate {
+                current: current.min(max),
+                max,
+            }
+        };
+        self.set(Some(progress), message.into(), None);
+    }
+
+    pub fn indeterminate(&self, message: impl Into<String>) {
+        self.set(Some(Progress::Indeterminate), message.into(), None);
+    }
+
+    pub fn finish(&self, message: impl Into<String>) {
+        self.set(None, message.into(), Some(Vec::new()));
+    }
+
+    fn set(&self, progress: Option<Progress>, message: String, actions: Option<Vec<NotificationAction>>) {
+        let updated = self.manager.update_notification(self.id, |notification| {
+            notification.progress = progress;
+            notification.message = message;
+            if let Some(actions) = actions {
+                notification.actions = actions;
+            }
+        });
+        if !updated {
+            log::debug!("Progress notification {} is gone", self.id);
+        }
+    }
+}
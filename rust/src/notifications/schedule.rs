@@ -0,0 +1,333 @@
+//! Notifications posted later, once or repeatedly.
+//!
+//! Times are wall-clock UTC so a schedule means the same moment after the
+//! process restarts. `NotificationManager` keeps every pending schedule in
+//! its store and arms one `Scheduler` job per schedule for the next
+//! occurrence.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::time::Duration;
+
+use super::{default_channel, NotificationPriority};
+use crate::error::{Error, Result};
+
+/// Shortest repeat interval: a second. Anything faster would only flood
+/// the notification shade.
+pub const MIN_INTERVAL_MS: u64 = 1_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ScheduleId(pub u64);
+
+/// When the first occurrence is due.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    At(DateTime<Utc>),
+    After(Duration),
+}
+
+impl Trigger {
+    pub fn first(&self, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+        match self {
+            Trigger::At(at) => Ok(*at),
+            Trigger::After(delay) => chrono::Duration::from_std(*delay)
+                .ok()
+                .and_then(|delay| now.checked_add_signed(delay))
+                .ok_or_else(|| Error::InvalidArgument(format!("delay out of range: {:?}", delay))),
+        }
+    }
+}
+
+/// How a schedule repeats, e.g. `{"type":"interval","every_ms":60000}`,
+/// `{"type":"daily"}` or `{"type":"cron","expression":"0 30 8 * * Mon-Fri"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Repeat {
+    /// Every `every_ms`, at least `MIN_INTERVAL_MS`.
+    Interval { every_ms: u64 },
+    /// Same time of day, in UTC.
+    Daily,
+    Weekly,
+    /// Cron expression with a leading seconds field, evaluated in UTC.
+    Cron { expression: String },
+}
+
+/// A `Repeat` and when it stops: after `count` occurrences in total, or
+/// at `until`, whichever comes first.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepeatRule {
+    #[serde(flatten)]
+    pub repeat: Repeat,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl RepeatRule {
+    pub fn new(repeat: Repeat) -> Self {
+        Self {
+            repeat,
+            count: None,
+            until: None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        match &self.repeat {
+            Repeat::Interval { every_ms } if *every_ms < MIN_INTERVAL_MS => {
+                return Err(Error::InvalidArgument(format!(
+                    "repeat interval must be at least {} ms, got {}",
+                    MIN_INTERVAL_MS, every_ms
+                )));
+            }
+            Repeat::Cron { expression } => {
+                parse_cron(expression)?;
+            }
+            Repeat::Interval { .. } | Repeat::Daily | Repeat::Weekly => {}
+        }
+        if self.count == Some(0) {
+            return Err(Error::InvalidArgument("repeat count must be positive".to_string()));
+        }
+        Ok(())
+    }
+
+    /// First occurrence after `previous` that is later than `now`, so
+    /// occurrences missed while the process was not running collapse.
+    fn next(&self, previous: DateTime<Utc>, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let step_ms = match &self.repeat {
+            Repeat::Interval { every_ms } => i64::try_from(*every_ms).ok().filter(|&ms| ms > 0)?,
+            Repeat::Daily => chrono::Duration::days(1).num_milliseconds(),
+            Repeat::Weekly => chrono::Duration::weeks(1).num_milliseconds(),
+            Repeat::Cron { expression } => {
+                let after = previous.max(now);
+                return parse_cron(expression).ok()?.after(&after).next();
+            }
+        };
+        // Steps from `previous` to the first occurrence later than `now`,
+        // in i64 milliseconds so years of missed short intervals still fit
+        let steps = (now - previous).num_milliseconds().max(0) / step_ms + 1;
+        let offset = chrono::Duration::try_milliseconds(steps.checked_mul(step_ms)?)?;
+        previous.checked_add_signed(offset)
+    }
+}
+
+fn parse_cron(expression: &str) -> Result<cron::Schedule> {
+    cron::Schedule::from_str(expression)
+        .map_err(|e| Error::InvalidArgument(format!("invalid cron expression {:?}: {}", expression, e)))
+}
+
+/// What to post and when. Kotlin passes it to `scheduleNotification` as
+/// JSON with either `at` (RFC 3339) or `after_ms`, e.g.
+/// `{"title":"Stretch","message":"Time to move","after_ms":1800000,"repeat":{"type":"interval","every_ms":1800000,"count":4}}`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "ScheduleJson")]
+pub struct ScheduleRequest {
+    pub when: Trigger,
+    pub channel: String,
+    pub title: String,
+    pub message: String,
+    pub priority: NotificationPriority,
+    pub repeat: Option<RepeatRule>,
+}
+
+#[derive(Deserialize)]
+struct ScheduleJson {
+    #[serde(default = "default_channel")]
+    channel: String,
+    title: String,
+    message: String,
+    #[serde(default)]
+    priority: NotificationPriority,
+    at: Option<DateTime<Utc>>,
+    after_ms: Option<u64>,
+    #[serde(default)]
+    repeat: Option<RepeatRule>,
+}
+
+impl TryFrom<ScheduleJson> for ScheduleRequest {
+    type Error = String;
+
+    fn try_from(json: ScheduleJson) -> std::result::Result<Self, String> {
+        let when = match (json.at, json.after_ms) {
+            (Some(at), None) => Trigger::At(at),
+            (None, Some(after_ms)) => Trigger::After(Duration::from_millis(after_ms)),
+            _ => return Err("exactly one of `at` and `after_ms` is required".to_string()),
+        };
+        Ok(Self {
+            when,
+            channel: json.channel,
+            title: json.title,
+            message: json.message,
+            priority: json.priority,
+            repeat: json.repeat,
+        })
+    }
+}
+
+/// A pending schedule, as stored and as returned by
+/// `listScheduledNotifications`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledNotification {
+    pub id: ScheduleId,
+    pub channel: String,
+    pub title: String,
+    pub message: String,
+    pub priority: NotificationPriority,
+    pub next_at: DateTime<Utc>,
+    #[serde(default)]
+    pub repeat: Option<RepeatRule>,
+    /// Occurrences posted so far.
+    #[serde(default)]
+    pub fired: u64,
+}
+
+impl ScheduledNotification {
+    /// Moves on after an occurrence was posted at `now`. Returns `false`
+    /// when the schedule is done.
+    pub fn advance(&mut self, now: DateTime<Utc>) -> bool {
+        self.fired += 1;
+        let Some(rule) = &self.repeat else {
+            return false;
+        };
+        if rule.count.is_some_and(|count| self.fired >= count) {
+            return false;
+        }
+        match rule.next(self.next_at, now) {
+            Some(next) if rule.until.is_none_or(|until| next <= until) => {
+                self.next_at = next;
+                true
+            }
+            Some(_) => false,
+            None => {
+                log::warn!(
+                    "Schedule {} has no representable occurrence after {}, ending it",
+                    self.id.0,
+                    self.next_at
+                );
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, 2, hour, minute, 0).unwrap()
+    }
+
+    fn scheduled(repeat: Option<RepeatRule>) -> ScheduledNotification {
+        ScheduledNotification {
+            id: ScheduleId(1),
+            channel: "general".to_string(),
+            title: "t".to_string(),
+            message: "m".to_string(),
+            priority: NotificationPriority::Normal,
+            next_at: at(8, 0),
+            repeat,
+            fired: 0,
+        }
+    }
+
+    #[test]
+    fn one_shot_schedules_end_after_firing() {
+        let mut schedule = scheduled(None);
+        assert!(!schedule.advance(at(8, 0)));
+        assert_eq!(schedule.fired, 1);
+    }
+
+    #[test]
+    fn intervals_skip_missed_occurrences() {
+        let mut schedule = scheduled(Some(RepeatRule::new(Repeat::Interval { every_ms: 15 * 60_000 })));
+        assert!(schedule.advance(at(8, 0)));
+        assert_eq!(schedule.next_at, at(8, 15));
+
+        // Process was dead from 8:15 until 9:05
+        assert!(schedule.advance(at(9, 5)));
+        assert_eq!(schedule.next_at, at(9, 15));
+    }
+
+    #[test]
+    fn intervals_catch_up_over_billions_of_missed_occurrences() {
+        let mut schedule = scheduled(Some(RepeatRule::new(Repeat::Interval { every_ms: MIN_INTERVAL_MS })));
+        // More than i32::MAX seconds in the past
+        schedule.next_at = Utc.with_ymd_and_hms(1900, 1, 1, 0, 0, 0).unwrap();
+        assert!(schedule.advance(at(8, 0)));
+        assert_eq!(schedule.next_at, Utc.with_ymd_and_hms(2026, 3, 2, 8, 0, 1).unwrap());
+    }
+
+    #[test]
+    fn count_and_until_end_repeats() {
+        let mut counted = scheduled(Some(RepeatRule {
+            count: Some(2),
+            ..RepeatRule::new(Repeat::Daily)
+        }));
+        assert!(counted.advance(at(8, 0)));
+        assert_eq!(counted.next_at, Utc.with_ymd_and_hms(2026, 3, 3, 8, 0, 0).unwrap());
+        assert!(!counted.advance(counted.next_at));
+
+        let mut until = scheduled(Some(RepeatRule {
+            until: Some(Utc.with_ymd_and_hms(2026, 3, 12, 0, 0, 0).unwrap()),
+            ..RepeatRule::new(Repeat::Weekly)
+        }));
+        assert!(until.advance(at(8, 0)));
+        assert!(!until.advance(until.next_at));
+    }
+
+    #[test]
+    fn cron_repeats_follow_the_expression() {
+        let mut schedule = scheduled(Some(RepeatRule::new(Repeat::Cron {
+            expression: "0 30 8 * * Mon-Fri".to_string(),
+        })));
+        // 2026-03-02 is a Monday
+        assert!(schedule.advance(at(8, 0)));
+        assert_eq!(schedule.next_at, at(8, 30));
+        schedule.next_at = Utc.with_ymd_and_hms(2026, 3, 6, 8, 30, 0).unwrap();
+        assert!(schedule.advance(schedule.next_at));
+        assert_eq!(schedule.next_at, Utc.with_ymd_and_hms(2026, 3, 9, 8, 30, 0).unwrap());
+    }
+
+    #[test]
+    fn parses_requests() {
+        let request: ScheduleRequest = serde_json::from_str(
+            r#"{"title":"Stretch","message":"Move","after_ms":5000,"repeat":{"type":"interval","every_ms":60000,"count":3}}"#,
+        )
+        .unwrap();
+        assert_eq!(request.when, Trigger::After(Duration::from_secs(5)));
+        assert_eq!((request.channel.as_str(), request.priority), ("general", NotificationPriority::Normal));
+        assert_eq!(request.repeat.unwrap().count, Some(3));
+
+        let request: ScheduleRequest =
+            serde_json::from_str(r#"{"title":"t","message":"m","at":"2026-03-02T08:00:00Z","priority":"high"}"#).unwrap();
+        assert_eq!(request.when, Trigger::At(at(8, 0)));
+
+        assert!(serde_json::from_str::<ScheduleRequest>(r#"{"title":"t","message":"m"}"#).is_err());
+        assert!(serde_json::from_str::<ScheduleRequest>(
+            r#"{"title":"t","message":"m","after_ms":1,"at":"2026-03-02T08:00:00Z"}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        assert!(RepeatRule::new(Repeat::Interval { every_ms: 0 }).validate().is_err());
+        assert!(RepeatRule::new(Repeat::Interval { every_ms: MIN_INTERVAL_MS - 1 }).validate().is_err());
+        assert!(RepeatRule::new(Repeat::Interval { every_ms: MIN_INTERVAL_MS }).validate().is_ok());
+        assert!(RepeatRule::new(Repeat::Cron {
+            expression: "every day".to_string()
+        })
+        .validate()
+        .is_err());
+        let rule = RepeatRule {
+            count: Some(0),
+            ..RepeatRule::new(Repeat::Daily)
+        };
+        assert!(rule.validate().is_err());
+    }
+}
@@ -0,0 +1,628 @@
+//! Where `NotificationManager` keeps notifications between process starts.
+//!
+//! A store persists every posted notification and the highest ID it has
+//! ever handed out, so IDs keep increasing across restarts and never collide
+//! with notifications still shown in the Android tray, even after the
+//! records themselves were removed. Pending scheduled notifications are
+//! kept alongside, with their own IDs.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use super::schedule::{ScheduleId, ScheduledNotification};
+use super::NotificationData;
+use crate::error::{Error, Result};
+
+/// What a store holds when it is opened.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StoredNotifications {
+    /// Notifications not yet removed, by ascending ID.
+    pub notifications: Vec<NotificationData>,
+    /// Highest ID ever stored, 0 if none.
+    pub last_id: i32,
+    /// Schedules not yet finished or cancelled, by ascending ID.
+    pub schedules: Vec<ScheduledNotification>,
+    /// Highest schedule ID ever stored, 0 if none.
+    pub last_schedule_id: u64,
+}
+
+pub trait NotificationStore: Send {
+    fn load(&mut self) -> Result<StoredNotifications>;
+    /// Stores a new notification, or replaces the one with the same ID.
+    fn insert(&mut self, notification: &NotificationData) -> Result<()>;
+    fn remove(&mut self, id: i32) -> Result<()>;
+    /// Removes every notification; `last_id` and the schedules are kept.
+    fn clear(&mut self) -> Result<()>;
+    /// Stores a new schedule, or replaces the one with the same ID.
+    fn save_schedule(&mut self, schedule: &ScheduledNotification) -> Result<()>;
+    fn remove_schedule(&mut self, id: ScheduleId) -> Result<()>;
+}
+
+/// Persists nothing: the manager's own map is all there is, and IDs start
+/// over at 1 with every process.
+#[derive(Debug, Default)]
+pub struct MemoryStore;
+
+impl NotificationStore for MemoryStore {
+    fn load(&mut self) -> Result<StoredNotifications> {
+        Ok(StoredNotifications::default())
+    }
+
+    fn insert(&mut self, _notification: &NotificationData) -> Result<()> {
+        Ok(())
+    }
+
+    fn remove(&mut self, _id: i32) -> Result<()> {
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn save_schedule(&mut self, _schedule: &ScheduledNotification) -> Result<()> {
+        Ok(())
+    }
+
+    fn remove_schedule(&mut self, _id: ScheduleId) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// One notification per row, the fields as JSON so new fields need no
+/// migration. `AUTOINCREMENT` makes SQLite remember the highest ID in
+/// `sqlite_sequence` even once its row is deleted. Schedules live in a
+/// second table of the same shape.
+pub struct SqliteStore {
+    connection: Connection,
+}
+
+impl SqliteStore {
+    /// 2 added `scheduled_notifications`.
+    const SCHEMA_VERSION: i32 = 2;
+
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self> {
+        let version: i32 = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version > Self::SCHEMA_VERSION {
+            return Err(Error::InvalidArgument(format!(
+                "notification database has schema version {}, newer than {}",
+                version,
+                Self::SCHEMA_VERSION
+            )));
+        }
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS notifications (
+                 id INTEGER PRIMARY KEY AUTOINCREMENT,
+                 data TEXT NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS scheduled_notifications (
+                 id INTEGER PRIMARY KEY AUTOINCREMENT,
+                 data TEXT NOT NULL
+             );
+             PRAGMA user_version = 2;",
+        )?;
+        Ok(Self { connection })
+    }
+
+    /// Highest ID ever inserted into `table`, 0 if none.
+    fn last_sequence(&self, table: &str) -> Result<i64> {
+        Ok(self
+            .connection
+            .query_row("SELECT seq FROM sqlite_sequence WHERE name = ?1", params![table], |row| {
+                row.get::<_, i64>(0)
+            })
+            .optional()?
+            .unwrap_or(0))
+    }
+}
+
+impl NotificationStore for SqliteStore {
+    fn load(&mut self) -> Result<StoredNotifications> {
+        let mut statement = self.connection.prepare("SELECT id, data FROM notifications ORDER BY id")?;
+        let rows = statement.query_map([], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?)))?;
+        let mut notifications = Vec::new();
+        for row in rows {
+            let (id, data) = row?;
+            match serde_json::from_str::<NotificationData>(&data) {
+                Ok(notification) => notifications.push(NotificationData { id, ..notification }),
+                Err(e) => log::warn!("Skipping unreadable stored notification {}: {}", id, e),
+            }
+        }
+
+        let mut statement = self
+            .connection
+            .prepare("SELECT id, data FROM scheduled_notifications ORDER BY id")?;
+        let rows = statement.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?;
+        let mut schedules = Vec::new();
+        for row in rows {
+            let (id, data) = row?;
+            match serde_json::from_str::<ScheduledNotification>(&data) {
+                Ok(schedule) => schedules.push(ScheduledNotification {
+                    id: ScheduleId(id as u64),
+                    ..schedule
+                }),
+                Err(e) => log::warn!("Skipping unreadable stored schedule {}: {}", id, e),
+            }
+        }
+
+        Ok(StoredNotifications {
+            notifications,
+            last_id: self.last_sequence("notifications")? as i32,
+            schedules,
+            last_schedule_id: self.last_sequence("scheduled_notifications")? as u64,
+        })
+    }
+
+    fn insert(&mut self, notification: &NotificationData) -> Result<()> {
+        let data = serde_json::to_string(notification)
+            .map_err(|e| Error::Internal(format!("failed to encode notification: {}", e)))?;
+        self.connection.execute(
+            "INSERT OR REPLACE INTO notifications (id, data) VALUES (?1, ?2)",
+            params![notification.id, data],
+        )?;
+        Ok(())
+    }
+
+    fn remove(&mut self, id: i32) -> Result<()> {
+        self.connection.execute("DELETE FROM notifications WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        self.connection.execute("DELETE FROM notifications", [])?;
+        Ok(())
+    }
+
+    fn save_schedule(&mut self, schedule: &ScheduledNotification) -> Result<()> {
+        let data = serde_json::to_string(schedule)
+            .map_err(|e| Error::Internal(format!("failed to encode schedule: {}", e)))?;
+        self.connection.execute(
+            "INSERT OR REPLACE INTO scheduled_notifications (id, data) VALUES (?1, ?2)",
+            params![schedule.id.0 as i64, data],
+        )?;
+        Ok(())
+    }
+
+    fn remove_schedule(&mut self, id: ScheduleId) -> Result<()> {
+        self.connection
+            .execute("DELETE FROM scheduled_notifications WHERE id = ?1", params![id.0 as i64])?;
+        Ok(())
+    }
+}
+
+/// A line of a `FileStore` log, e.g. `{"op":"remove","id":3}`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Record {
+    Insert(NotificationData),
+    Remove { id: i32 },
+    Clear,
+    Schedule(ScheduledNotification),
+    Unschedule { schedule_id: ScheduleId },
+    /// Written first by a compaction, whose other records may no longer
+    /// include the highest IDs.
+    LastIds { last_id: i32, last_schedule_id: u64 },
+}
+
+/// Obsolete lines a `FileStore` log may collect before it is rewritten,
+/// as long as they also outnumber the live records.
+const COMPACT_AFTER: usize = 512;
+
+/// What a `FileStore` log adds up to.
+#[derive(Default)]
+struct LogState {
+    notifications: BTreeMap<i32, NotificationData>,
+    last_id: i32,
+    schedules: BTreeMap<ScheduleId, ScheduledNotification>,
+    last_schedule_id: u64,
+}
+
+impl LogState {
+    /// Applies `record`, returning how many lines it made obsolete,
+    /// counting its own line if it is not needed to rebuild the state.
+    fn apply(&mut self, record: Record) -> usize {
+        match record {
+            Record::Insert(notification) => {
+                self.last_id = self.last_id.max(notification.id);
+                usize::from(self.notifications.insert(notification.id, notification).is_some())
+            }
+            Record::Remove { id } => 1 + usize::from(self.notifications.remove(&id).is_some()),
+            Record::Clear => 1 + std::mem::take(&mut self.notifications).len(),
+            Record::Schedule(schedule) => {
+                self.last_schedule_id = self.last_schedule_id.max(schedule.id.0);
+                usize::from(self.schedules.insert(schedule.id, schedule).is_some())
+            }
+            Record::Unschedule { schedule_id } => 1 + usize::from(self.schedules.remove(&schedule_id).is_some()),
+            Record::LastIds {
+                last_id,
+                last_schedule_id,
+            } => {
+                self.last_id = self.last_id.max(last_id);
+                self.last_schedule_id = self.last_schedule_id.max(last_schedule_id);
+                1
+            }
+        }
+    }
+
+    fn live(&self) -> usize {
+        self.notifications.len() + self.schedules.len()
+    }
+}
+
+/// An append-only JSON Lines log of inserts and removals, replayed when
+/// opened. Records that add or remove something are synced before the call
+/// returns, so an ID is durable once `send_notification` hands it out;
+/// replacements of a notification, such as progress updates, are not, and
+/// a crash at worst loses the newest version. The log is rewritten with
+/// only the live records when opened and whenever obsolete lines pile up.
+pub struct FileStore {
+    path: PathBuf,
+    file: File,
+    state: LogState,
+    /// Lines in the log that no longer contribute to `state`.
+    obsolete: usize,
+}
+
+impl FileStore {
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut state = LogState::default();
+        let mut obsolete = 0;
+        match File::open(path) {
+            Ok(file) => {
+                for (number, line) in BufReader::new(file).lines().enumerate() {
+                    let line = line?;
+                    if line.trim().is_empty() {
+                        obsolete += 1;
+                        continue;
+                    }
+                    // A crash mid-write leaves a partial last line; skip what
+                    // cannot be read rather than losing the rest of the history
+                    match serde_json::from_str::<Record>(&line) {
+                        Ok(record) => obsolete += state.apply(record),
+                        Err(e) => {
+                            log::warn!("Skipping line {} of {}: {}", number + 1, path.display(), e);
+                            obsolete += 1;
+                        }
+                    }
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let mut store = Self {
+            path: path.to_path_buf(),
+            file,
+            state,
+            obsolete,
+        };
+        if store.obsolete > 0 {
+            store.compact()?;
+        }
+        Ok(store)
+    }
+
+    fn append(&mut self, record: Record) -> Result<()> {
+        let mut line = serde_json::to_string(&record)
+            .map_err(|e| Error::Internal(format!("failed to encode notification record: {}", e)))?;
+        line.push('\n');
+        self.file.write_all(line.as_bytes())?;
+
+        let replaces = match &record {
+            Record::Insert(notification) => self.state.notifications.contains_key(&notification.id),
+            Record::Schedule(schedule) => self.state.schedules.contains_key(&schedule.id),
+            _ => false,
+        };
+        if !replaces {
+            self.file.sync_data()?;
+        }
+        self.obsolete += self.state.apply(record);
+        if self.obsolete >= COMPACT_AFTER.max(self.state.live()) {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    /// Rewrites the log with one record per live notification and schedule,
+    /// through a temporary file so a crash leaves either log complete.
+    fn compact(&mut self) -> Result<()> {
+        let mut temp = self.path.clone().into_os_string();
+        temp.push(".tmp");
+        let temp = PathBuf::from(temp);
+
+        let records = std::iter::once(Record::LastIds {
+            last_id: self.state.last_id,
+            last_schedule_id: self.state.last_schedule_id,
+        })
+        .chain(self.state.schedules.values().cloned().map(Record::Schedule))
+        .chain(self.state.notifications.values().cloned().map(Record::Insert));
+        let mut writer = BufWriter::new(File::create(&temp)?);
+        for record in records {
+            serde_json::to_writer(&mut writer, &record)
+                .map_err(|e| Error::Internal(format!("failed to encode notification record: {}", e)))?;
+            writer.write_all(b"\n")?;
+        }
+        writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&temp, &self.path)?;
+
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        log::debug!(
+            "Compacted {}: dropped {} obsolete lines, kept {} records",
+            self.path.display(),
+            self.obsolete,
+            self.state.live()
+        );
+        self.obsolete = 0;
+        Ok(())
+    }
+}
+
+impl NotificationStore for FileStore {
+    fn load(&mut self) -> Result<StoredNotifications> {
+        Ok(StoredNotifications {
+            notifications: self.state.notifications.values().cloned().collect(),
+            last_id: self.state.last_id,
+            schedules: self.state.schedules.values().cloned().collect(),
+            last_schedule_id: self.state.last_schedule_id,
+        })
+    }
+
+    fn insert(&mut self, notification: &NotificationData) -> Result<()> {
+        self.append(Record::Insert(notification.clone()))
+    }
+
+    fn remove(&mut self, id: i32) -> Result<()> {
+        self.append(Record::Remove { id })
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        self.append(Record::Clear)
+    }
+
+    fn save_schedule(&mut self, schedule: &ScheduledNotification) -> Result<()> {
+        self.append(Record::Schedule(schedule.clone()))
+    }
+
+    fn remove_schedule(&mut self, id: ScheduleId) -> Result<()> {
+        self.append(Record::Unschedule { schedule_id: id })
+    }
+}
+
+/// Which store to open, parsed from `memory`, `sqlite:<path>` or
+/// `file:<path>`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum StoreConfig {
+    #[default]
+    Memory,
+    Sqlite(PathBuf),
+    File(PathBuf),
+}
+
+impl StoreConfig {
+    pub fn open(&self) -> Result<Box<dyn NotificationStore>> {
+        Ok(match self {
+            StoreConfig::Memory => Box::new(MemoryStore),
+            StoreConfig::Sqlite(path) => Box::new(SqliteStore::open(path)?),
+            StoreConfig::File(path) => Box::new(FileStore::open(path)?),
+        })
+    }
+}
+
+impl FromStr for StoreConfig {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.eq_ignore_ascii_case("memory") {
+            return Ok(StoreConfig::Memory);
+        }
+        let path = |path: &str| {
+            if path.is_empty() {
+                Err(Error::InvalidArgument(format!("notification store needs a path: {}", s)))
+            } else {
+                Ok(PathBuf::from(path))
+            }
+        };
+        if let Some(rest) = s.strip_prefix("sqlite:") {
+            Ok(StoreConfig::Sqlite(path(rest)?))
+        } else if let Some(rest) = s.strip_prefix("file:") {
+            Ok(StoreConfig::File(path(rest)?))
+        } else {
+            Err(Error::InvalidArgument(format!("unknown notification store: {}", s)))
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::notifications::{NotificationPriority, Progress};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A fresh path in the system temp directory, removed on drop.
+    pub(crate) struct TempPath(pub PathBuf);
+
+    impl TempPath {
+        pub(crate) fn new(name: &str) -> Self {
+            static COUNTER: AtomicUsize = AtomicUsize::new(0);
+            let unique = format!(
+                "mainlogic-{}-{}-{}",
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::SeqCst),
+                name
+            );
+            Self(std::env::temp_dir().join(unique))
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn notification(id: i32) -> NotificationData {
+        NotificationData {
+            id,
+            channel: "general".to_string(),
+            title: format!("Title {}", id),
+            message: "Message".to_string(),
+            timestamp: "2026-01-01 00:00:00".to_string(),
+            priority: NotificationPriority::High,
+            style: None,
+            progress: None,
+            actions: Vec::new(),
+            deep_link: None,
+            dedupe_key: None,
+            group: None,
+            group_summary: false,
+        }
+    }
+
+    fn ids(stored: &StoredNotifications) -> Vec<i32> {
+        stored.notifications.iter().map(|n| n.id).collect()
+    }
+
+    /// Inserts 1..=3, removes 3 and 1, and checks what is left.
+    fn exercise(open: impl Fn() -> Box<dyn NotificationStore>) {
+        {
+            let mut store = open();
+            assert_eq!(store.load().unwrap(), StoredNotifications::default());
+            for id in 1..=3 {
+                store.insert(&notification(id)).unwrap();
+            }
+            store.remove(3).unwrap();
+            store.remove(1).unwrap();
+        }
+
+        let mut store = open();
+        let stored = store.load().unwrap();
+        assert_eq!(ids(&stored), vec![2]);
+        assert_eq!(stored.notifications[0], notification(2));
+        assert_eq!(stored.last_id, 3);
+
+        store.clear().unwrap();
+        let stored = open().load().unwrap();
+        assert!(stored.notifications.is_empty());
+        assert_eq!(stored.last_id, 3);
+    }
+
+    #[test]
+    fn sqlite_store_keeps_notifications_and_last_id() {
+        let path = TempPath::new("notifications.db");
+        exercise(|| Box::new(SqliteStore::open(&path.0).unwrap()));
+    }
+
+    #[test]
+    fn file_store_keeps_notifications_and_last_id() {
+        let path = TempPath::new("notifications.jsonl");
+        exercise(|| Box::new(FileStore::open(&path.0).unwrap()));
+    }
+
+    fn schedule(id: u64) -> ScheduledNotification {
+        ScheduledNotification {
+            id: ScheduleId(id),
+            channel: "general".to_string(),
+            title: format!("Reminder {}", id),
+            message: "Message".to_string(),
+            priority: NotificationPriority::Normal,
+            next_at: "2026-01-01T08:00:00Z".parse().unwrap(),
+            repeat: None,
+            fired: 0,
+        }
+    }
+
+    #[test]
+    fn schedules_outlive_clearing_notifications() {
+        let sqlite = TempPath::new("notifications.db");
+        let file = TempPath::new("notifications.jsonl");
+        let opens: [Box<dyn Fn() -> Box<dyn NotificationStore>>; 2] = [
+            Box::new(|| Box::new(SqliteStore::open(&sqlite.0).unwrap())),
+            Box::new(|| Box::new(FileStore::open(&file.0).unwrap())),
+        ];
+        for open in opens {
+            let mut store = open();
+            store.save_schedule(&schedule(1)).unwrap();
+            store.save_schedule(&schedule(2)).unwrap();
+            store.save_schedule(&ScheduledNotification { fired: 3, ..schedule(1) }).unwrap();
+            store.remove_schedule(ScheduleId(2)).unwrap();
+            store.clear().unwrap();
+
+            let stored = open().load().unwrap();
+            assert_eq!(stored.schedules, vec![ScheduledNotification { fired: 3, ..schedule(1) }]);
+            assert_eq!(stored.last_schedule_id, 2);
+        }
+    }
+
+    #[test]
+    fn file_store_skips_a_torn_last_line() {
+        let path = TempPath::new("notifications.jsonl");
+        let mut store = FileStore::open(&path.0).unwrap();
+        store.insert(&notification(1)).unwrap();
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path.0)
+            .unwrap()
+            .write_all(br#"{"op":"insert","id":2,"tit"#)
+            .unwrap();
+
+        let mut store = FileStore::open(&path.0).unwrap();
+        assert_eq!(ids(&store.load().unwrap()), vec![1]);
+        store.insert(&notification(3)).unwrap();
+        assert_eq!(ids(&FileStore::open(&path.0).unwrap().load().unwrap()), vec![1, 3]);
+    }
+
+    fn line_count(path: &Path) -> usize {
+        std::fs::read_to_string(path).unwrap().lines().count()
+    }
+
+    #[test]
+    fn file_store_compacts_superseded_records() {
+        let path = TempPath::new("notifications.jsonl");
+        let mut store = FileStore::open(&path.0).unwrap();
+        for id in 1..=3 {
+            store.insert(&notification(id)).unwrap();
+        }
+        for current in 0..COMPACT_AFTER as u64 {
+            let progress = Some(Progress::Determinate { current, max: 1000 });
+            store.insert(&NotificationData { progress, ..notification(2) }).unwrap();
+        }
+        store.remove(3).unwrap();
+        assert!(line_count(&path.0) < COMPACT_AFTER);
+
+        // Reopening drops the obsolete lines but keeps the highest ID
+        drop(store);
+        let mut store = FileStore::open(&path.0).unwrap();
+        assert_eq!(line_count(&path.0), 3);
+        let stored = store.load().unwrap();
+        assert_eq!(ids(&stored), vec![1, 2]);
+        let current = COMPACT_AFTER as u64 - 1;
+        assert_eq!(stored.notifications[1].progress, Some(Progress::Determinate { current, max: 1000 }));
+        assert_eq!(stored.last_id, 3);
+        assert_eq!(FileStore::open(&path.0).unwrap().load().unwrap(), stored);
+    }
+
+    #[test]
+    fn parses_store_configs() {
+        assert_eq!("memory".parse::<StoreConfig>().unwrap(), StoreConfig::Memory);
+        assert_eq!(
+            "sqlite:/data/n.db".parse::<StoreConfig>().unwrap(),
+            StoreConfig::Sqlite(PathBuf::from("/data/n.db"))
+        );
+        assert_eq!(
+            "file:n.jsonl".parse::<StoreConfig>().unwrap(),
+            StoreConfig::File(PathBuf::from("n.jsonl"))
+        );
+        assert!("sqlite:".parse::<StoreConfig>().is_err());
+        assert!("redis://localhost".parse::<StoreConfig>().is_err());
+    }
+}
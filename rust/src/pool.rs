@@ -0,0 +1,561 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::{panic_message, Error, Result};
+use crate::threads::{self, ThreadHandle};
+
+/// How often an idle worker checks for a crate shutdown.
+const IDLE_POLL: Duration = Duration::from_millis(250);
+
+/// Queue lanes, most urgent first. Workers always drain a more urgent lane
+/// before looking at the next one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    UiCritical = 0,
+    Normal = 1,
+    Background = 2,
+}
+
+const LANES: usize = 3;
+
+/// What `submit` does when the queue already holds `queue_capacity` jobs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectionPolicy {
+    /// Refuse the new job with `Error::Rejected`.
+    Reject,
+    /// Drop the oldest queued job of the same or lower priority to make
+    /// room; refuse the new job if every queued job is more urgent.
+    DropOldest,
+    /// Run the new job synchronously on the submitting thread, which
+    /// blocks until the job returns. For a JNI caller that is the Kotlin
+    /// thread that made the call, possibly the main thread. Callers that
+    /// must not block use `try_submit`, which rejects instead.
+    CallerRuns,
+}
+
+impl FromStr for RejectionPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "reject" => Ok(RejectionPolicy::Reject),
+            "drop_oldest" => Ok(RejectionPolicy::DropOldest),
+            "caller_runs" => Ok(RejectionPolicy::CallerRuns),
+            other => Err(Error::InvalidArgument(format!("unknown rejection policy: {}", other))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PoolConfig {
+    pub workers: usize,
+    pub queue_capacity: usize,
+    pub rejection_policy: RejectionPolicy,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        let cores = std::thread::available_parallelism().map_or(2, |n| n.get());
+        Self {
+            workers: cores.clamp(2, 4),
+            queue_capacity: 64,
+            rejection_policy: RejectionPolicy::Reject,
+        }
+    }
+}
+
+/// A unit of work plus what to do if the pool drops it before it runs.
+pub struct Job {
+    run: Box<dyn FnOnce() + Send>,
+    on_dropped: Option<Box<dyn FnOnce() + Send>>,
+}
+
+impl Job {
+    pub fn new(run: impl FnOnce() + Send + 'static) -> Self {
+        Self {
+            run: Box::new(run),
+            on_dropped: None,
+        }
+    }
+
+    /// Called instead of the job when `RejectionPolicy::DropOldest` evicts
+    /// it from the queue, or when the pool shuts down before it ran.
+    pub fn on_dropped(mut self, f: impl FnOnce() + Send + 'static) -> Self {
+        self.on_dropped = Some(Box::new(f));
+        self
+    }
+}
+
+struct QueuedJob {
+    job: Job,
+    enqueued_at: Instant,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolMetrics {
+    pub workers: usize,
+    pub active_workers: usize,
+    /// Queued jobs per lane: UI-critical, normal, background.
+    pub queue_depth: [usize; LANES],
+    pub queue_capacity: usize,
+    pub completed: u64,
+    /// Jobs that panicked; the worker that ran them keeps going.
+    pub panicked: u64,
+    pub rejected: u64,
+    pub dropped: u64,
+    pub avg_wait_ms: f64,
+    pub max_wait_ms: f64,
+}
+
+struct Shared {
+    config: PoolConfig,
+    lanes: Mutex<[VecDeque<QueuedJob>; LANES]>,
+    job_available: Condvar,
+    stopped: AtomicBool,
+    active: AtomicUsize,
+    started: AtomicU64,
+    completed: AtomicU64,
+    panicked: AtomicU64,
+    rejected: AtomicU64,
+    dropped: AtomicU64,
+    total_wait_us: AtomicU64,
+    max_wait_us: AtomicU64,
+}
+
+/// Fixed-size thread pool with a bounded, prioritized queue.
+pub struct WorkerPool {
+    shared: Arc<Shared>,
+    workers: Vec<ThreadHandle>,
+}
+
+impl WorkerPool {
+    /// Starts `config.workers` threads through `threads::spawn`, so they
+    /// stop with the rest of the crate on `JNI_OnUnload`.
+    pub fn start(config: PoolConfig) -> Result<Self> {
+        let shared = Arc::new(Shared {
+            config,
+            lanes: Mutex::new(Default::default()),
+            job_available: Condvar::new(),
+            stopped: AtomicBool::new(false),
+            active: AtomicUsize::new(0),
+            started: AtomicU64::new(0),
+            completed: AtomicU64::new(0),
+            panicked: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            total_wait_us: AtomicU64::new(0),
+            max_wait_us: AtomicU64::new(0),
+        });
+
+        let mut workers = Vec::new();
+        for index in 0..config.workers.max(1) {
+            let shared = shared.clone();
+            let worker = threads::spawn(&format!("worker-{}", index), move || worker_loop(&shared))
+                .map_err(|e| Error::Internal(format!("failed to start worker: {}", e)))?;
+            workers.push(worker);
+        }
+        log::info!(
+            "Worker pool started: {} workers, queue capacity {}, {:?} when full",
+            config.workers,
+            config.queue_capacity,
+            config.rejection_policy
+        );
+        Ok(Self { shared, workers })
+    }
+
+    /// Queues `job`, applying the rejection policy when the queue is full.
+    /// Under `RejectionPolicy::CallerRuns` this may run the job before
+    /// returning.
+    pub fn submit(&self, priority: Priority, job: Job) -> Result<()> {
+        self.enqueue(priority, job, true)
+    }
+
+    /// Like `submit`, but never runs the job on the calling thread: under
+    /// `RejectionPolicy::CallerRuns` a full queue rejects it instead. For
+    /// callers such as the scheduler's timer thread that must not block.
+    pub fn try_submit(&self, priority: Priority, job: Job) -> Result<()> {
+        self.enqueue(priority, job, false)
+    }
+
+    /// Stops the workers once their current job returns and waits for
+    /// them. Jobs still queued are dropped through their `on_dropped`
+    /// hook; later submissions are rejected.
+    pub fn shutdown(&self) {
+        self.shared.stopped.store(true, Ordering::Relaxed);
+        self.shared.job_available.notify_all();
+        for worker in &self.workers {
+            worker.join();
+        }
+        drain(&self.shared);
+    }
+
+    fn enqueue(&self, priority: Priority, job: Job, caller_may_run: bool) -> Result<()> {
+        let shared = &self.shared;
+        let mut lanes = shared.lanes.lock()?;
+        // Checked under the lock so nothing is queued after `shutdown` drained
+        if shared.stopped.load(Ordering::Relaxed) {
+            shared.rejected.fetch_add(1, Ordering::Relaxed);
+            return Err(Error::Rejected("worker pool is shut down".to_string()));
+        }
+        let queued: usize = lanes.iter().map(VecDeque::len).sum();
+        let mut evicted = None;
+
+        if queued >= shared.config.queue_capacity {
+            match shared.config.rejection_policy {
+                RejectionPolicy::CallerRuns if caller_may_run => {
+                    drop(lanes);
+                    log::warn!("Worker queue full, running job on the calling thread");
+                    run_job(shared, job);
+                    return Ok(());
+                }
+                RejectionPolicy::Reject | RejectionPolicy::CallerRuns => {
+                    shared.rejected.fetch_add(1, Ordering::Relaxed);
+                    return Err(Error::Rejected("worker queue is full".to_string()));
+                }
+                RejectionPolicy::DropOldest => {
+                    // Evict from the least urgent lane that is not more
+                    // urgent than the new job
+                    let victim = (priority as usize..LANES)
+                        .rev()
+                        .find_map(|lane| lanes[lane].pop_front());
+                    match victim {
+                        Some(victim) => {
+                            shared.dropped.fetch_add(1, Ordering::Relaxed);
+                            evicted = Some(victim.job);
+                        }
+                        None => {
+                            shared.rejected.fetch_add(1, Ordering::Relaxed);
+                            return Err(Error::Rejected(
+                                "worker queue is full of more urgent jobs".to_string(),
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        lanes[priority as usize].push_back(QueuedJob {
+            job,
+            enqueued_at: Instant::now(),
+        });
+        drop(lanes);
+        shared.job_available.notify_one();
+
+        // Run and drop it outside the lock: the hook, or whatever the job
+        // owns, may report back through subsystems that submit jobs of
+        // their own
+        if let Some(on_dropped) = evicted.and_then(|job| job.on_dropped) {
+            on_dropped();
+        }
+        Ok(())
+    }
+
+    pub fn metrics(&self) -> PoolMetrics {
+        let shared = &self.shared;
+        let queue_depth = {
+            let lanes = shared.lanes.lock().unwrap();
+            [lanes[0].len(), lanes[1].len(), lanes[2].len()]
+        };
+        let started = shared.started.load(Ordering::Relaxed);
+        let total_wait_us = shared.total_wait_us.load(Ordering::Relaxed);
+
+        PoolMetrics {
+            workers: shared.config.workers,
+            active_workers: shared.active.load(Ordering::Relaxed),
+            queue_depth,
+            queue_capacity: shared.config.queue_capacity,
+            completed: shared.completed.load(Ordering::Relaxed),
+            panicked: shared.panicked.load(Ordering::Relaxed),
+            rejected: shared.rejected.load(Ordering::Relaxed),
+            dropped: shared.dropped.load(Ordering::Relaxed),
+            avg_wait_ms: if started == 0 {
+                0.0
+            } else {
+                total_wait_us as f64 / started as f64 / 1000.0
+            },
+            max_wait_ms: shared.max_wait_us.load(Ordering::Relaxed) as f64 / 1000.0,
+        }
+    }
+}
+
+/// Counts a worker as active while it runs a job, even if the job unwinds.
+struct ActiveGuard<'a>(&'a AtomicUsize);
+
+impl<'a> ActiveGuard<'a> {
+    fn new(active: &'a AtomicUsize) -> Self {
+        active.fetch_add(1, Ordering::Relaxed);
+        Self(active)
+    }
+}
+
+impl Drop for ActiveGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+fn worker_loop(shared: &Shared) {
+    while let Some(queued) = next_job(shared) {
+        let wait_us = queued.enqueued_at.elapsed().as_micros() as u64;
+        shared.total_wait_us.fetch_add(wait_us, Ordering::Relaxed);
+        shared.max_wait_us.fetch_max(wait_us, Ordering::Relaxed);
+        shared.started.fetch_add(1, Ordering::Relaxed);
+
+        let _active = ActiveGuard::new(&shared.active);
+        run_job(shared, queued.job);
+    }
+    drain(shared);
+}
+
+/// Runs `job`, catching a panic so it cannot take the thread down with it.
+fn run_job(shared: &Shared, job: Job) {
+    match panic::catch_unwind(AssertUnwindSafe(job.run)) {
+        Ok(()) => {
+            shared.completed.fetch_add(1, Ordering::Relaxed);
+        }
+        Err(payload) => {
+            shared.panicked.fetch_add(1, Ordering::Relaxed);
+            log::error!("Worker pool job panicked: {}", panic_message(payload));
+        }
+    }
+}
+
+/// Drops every queued job through its `on_dropped` hook.
+fn drain(shared: &Shared) {
+    let drained: Vec<QueuedJob> = {
+        let mut lanes = shared.lanes.lock().unwrap_or_else(|e| e.into_inner());
+        lanes.iter_mut().flat_map(|lane| lane.drain(..)).collect()
+    };
+    if drained.is_empty() {
+        return;
+    }
+    log::info!("Worker pool stopped with {} queued jobs, dropping them", drained.len());
+    shared.dropped.fetch_add(drained.len() as u64, Ordering::Relaxed);
+    for queued in drained {
+        if let Some(on_dropped) = queued.job.on_dropped {
+            on_dropped();
+        }
+    }
+}
+
+/// Blocks until a job is available, most urgent lane first. Returns `None`
+/// once the pool or the crate shuts down.
+fn next_job(shared: &Shared) -> Option<QueuedJob> {
+    let mut lanes = shared.lanes.lock().unwrap();
+    loop {
+        if shared.stopped.load(Ordering::Relaxed) || threads::is_shutting_down() {
+            return None;
+        }
+        if let Some(job) = lanes.iter_mut().find_map(VecDeque::pop_front) {
+            return Some(job);
+        }
+        lanes = shared.job_available.wait_timeout(lanes, IDLE_POLL).unwrap().0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scheduler::{JobState, ManualClock, Schedule, Scheduler};
+    use std::sync::mpsc::{self, Sender};
+
+    fn pool(queue_capacity: usize, rejection_policy: RejectionPolicy) -> WorkerPool {
+        WorkerPool::start(PoolConfig {
+            workers: 1,
+            queue_capacity,
+            rejection_policy,
+        })
+        .unwrap()
+    }
+
+    /// Occupies the only worker until the returned sender is used or dropped.
+    fn block_worker(pool: &WorkerPool) -> Sender<()> {
+        let (started_tx, started) = mpsc::channel();
+        let (release, released) = mpsc::channel::<()>();
+        pool.submit(
+            Priority::UiCritical,
+            Job::new(move || {
+                started_tx.send(()).unwrap();
+                let _ = released.recv();
+            }),
+        )
+        .unwrap();
+        started.recv().unwrap();
+        release
+    }
+
+    fn wait_until(condition: impl Fn() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !condition() {
+            assert!(Instant::now() < deadline, "timed out");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    type RunLog = Arc<Mutex<Vec<&'static str>>>;
+
+    fn record(log: &RunLog, name: &'static str) -> Job {
+        let ran = log.clone();
+        Job::new(move || ran.lock().unwrap().push(name))
+    }
+
+    fn record_dropped(log: &RunLog, dropped: &RunLog, name: &'static str) -> Job {
+        let dropped = dropped.clone();
+        record(log, name).on_dropped(move || dropped.lock().unwrap().push(name))
+    }
+
+    #[test]
+    fn drains_more_urgent_lanes_first() {
+        let pool = pool(8, RejectionPolicy::Reject);
+        let log = RunLog::default();
+        let release = block_worker(&pool);
+        pool.submit(Priority::Background, record(&log, "background")).unwrap();
+        pool.submit(Priority::Normal, record(&log, "normal 1")).unwrap();
+        pool.submit(Priority::UiCritical, record(&log, "ui")).unwrap();
+        pool.submit(Priority::Normal, record(&log, "normal 2")).unwrap();
+        assert_eq!(pool.metrics().queue_depth, [1, 2, 1]);
+        assert_eq!(pool.metrics().active_workers, 1);
+
+        release.send(()).unwrap();
+        wait_until(|| log.lock().unwrap().len() == 4);
+        assert_eq!(*log.lock().unwrap(), vec!["ui", "normal 1", "normal 2", "background"]);
+
+        wait_until(|| pool.metrics().completed == 5);
+        let metrics = pool.metrics();
+        assert_eq!((metrics.active_workers, metrics.queue_depth), (0, [0, 0, 0]));
+        assert!(metrics.max_wait_ms >= metrics.avg_wait_ms);
+        pool.shutdown();
+    }
+
+    #[test]
+    fn reject_refuses_jobs_once_full() {
+        let pool = pool(1, RejectionPolicy::Reject);
+        let log = RunLog::default();
+        let release = block_worker(&pool);
+        pool.submit(Priority::Normal, record(&log, "queued")).unwrap();
+        let result = pool.submit(Priority::UiCritical, record(&log, "refused"));
+        assert!(matches!(result, Err(Error::Rejected(_))));
+
+        drop(release);
+        wait_until(|| pool.metrics().completed == 2);
+        assert_eq!(*log.lock().unwrap(), vec!["queued"]);
+        assert_eq!(pool.metrics().rejected, 1);
+        pool.shutdown();
+    }
+
+    #[test]
+    fn drop_oldest_evicts_the_least_urgent_job() {
+        let pool = pool(2, RejectionPolicy::DropOldest);
+        let (log, dropped) = (RunLog::default(), RunLog::default());
+        let release = block_worker(&pool);
+        pool.submit(Priority::Normal, record_dropped(&log, &dropped, "normal")).unwrap();
+        pool.submit(Priority::Background, record_dropped(&log, &dropped, "background")).unwrap();
+        pool.submit(Priority::Normal, record_dropped(&log, &dropped, "newer normal")).unwrap();
+        assert_eq!(*dropped.lock().unwrap(), vec!["background"]);
+
+        // Only more urgent jobs left to evict
+        let result = pool.submit(Priority::Background, record_dropped(&log, &dropped, "refused"));
+        assert!(matches!(result, Err(Error::Rejected(_))));
+
+        drop(release);
+        wait_until(|| log.lock().unwrap().len() == 2);
+        assert_eq!(*log.lock().unwrap(), vec!["normal", "newer normal"]);
+        let metrics = pool.metrics();
+        assert_eq!((metrics.dropped, metrics.rejected), (1, 1));
+        pool.shutdown();
+    }
+
+    #[test]
+    fn evicted_scheduler_runs_are_retried() {
+        let pool = Arc::new(pool(1, RejectionPolicy::DropOldest));
+        let clock = Arc::new(ManualClock::new(chrono::Utc::now()));
+        let executor = pool.clone();
+        let scheduler = Scheduler::new(clock.clone(), move |run| executor.try_submit(Priority::Normal, Job::new(run)));
+        let log = RunLog::default();
+        let ran = log.clone();
+        let id = scheduler
+            .schedule("evicted", Schedule::Once { delay: Duration::ZERO }, None, move |_| {
+                ran.lock().unwrap().push("scheduled")
+            })
+            .unwrap();
+
+        let release = block_worker(&pool);
+        scheduler.run_due();
+        pool.submit(Priority::Normal, record(&log, "newer")).unwrap();
+        let info = scheduler.info(id).unwrap();
+        assert_eq!((info.state, info.runs), (JobState::Scheduled, 0));
+
+        drop(release);
+        wait_until(|| log.lock().unwrap().len() == 1);
+        clock.advance(Duration::from_secs(1));
+        scheduler.run_due();
+        wait_until(|| scheduler.info(id).unwrap().state == JobState::Finished);
+        assert_eq!(*log.lock().unwrap(), vec!["newer", "scheduled"]);
+        assert_eq!(scheduler.info(id).unwrap().runs, 1);
+        pool.shutdown();
+    }
+
+    #[test]
+    fn caller_runs_uses_the_submitting_thread_unless_it_must_not_block() {
+        let pool = pool(1, RejectionPolicy::CallerRuns);
+        let release = block_worker(&pool);
+        pool.submit(Priority::Normal, Job::new(|| {})).unwrap();
+
+        let ran_on = Arc::new(Mutex::new(None));
+        let record = ran_on.clone();
+        pool.submit(
+            Priority::Normal,
+            Job::new(move || *record.lock().unwrap() = Some(std::thread::current().id())),
+        )
+        .unwrap();
+        assert_eq!(*ran_on.lock().unwrap(), Some(std::thread::current().id()));
+
+        let result = pool.try_submit(Priority::Normal, Job::new(|| panic!("must not run")));
+        assert!(matches!(result, Err(Error::Rejected(_))));
+        drop(release);
+        wait_until(|| pool.metrics().completed == 3);
+        assert_eq!(pool.metrics().rejected, 1);
+        pool.shutdown();
+    }
+
+    #[test]
+    fn panicking_jobs_do_not_kill_the_worker() {
+        let pool = pool(8, RejectionPolicy::Reject);
+        let log = RunLog::default();
+        pool.submit(Priority::Normal, Job::new(|| panic!("job failed"))).unwrap();
+        pool.submit(Priority::Normal, record(&log, "after panic")).unwrap();
+
+        wait_until(|| log.lock().unwrap().len() == 1);
+        wait_until(|| pool.metrics().active_workers == 0);
+        let metrics = pool.metrics();
+        assert_eq!((metrics.panicked, metrics.completed), (1, 1));
+        pool.shutdown();
+    }
+
+    #[test]
+    fn shutdown_finishes_the_running_job_and_drops_queued_ones() {
+        let pool = Arc::new(pool(8, RejectionPolicy::Reject));
+        let (log, dropped) = (RunLog::default(), RunLog::default());
+        let release = block_worker(&pool);
+        pool.submit(Priority::Normal, record_dropped(&log, &dropped, "normal")).unwrap();
+        pool.submit(Priority::Background, record_dropped(&log, &dropped, "background")).unwrap();
+
+        let stopping = pool.clone();
+        let shutdown = std::thread::spawn(move || stopping.shutdown());
+        wait_until(|| pool.try_submit(Priority::Normal, Job::new(|| {})).is_err());
+        release.send(()).unwrap();
+        shutdown.join().unwrap();
+
+        assert!(log.lock().unwrap().is_empty());
+        assert_eq!(*dropped.lock().unwrap(), vec!["normal", "background"]);
+        let metrics = pool.metrics();
+        assert_eq!(metrics.completed, 1);
+        assert!(metrics.dropped >= 2);
+        assert!(matches!(pool.submit(Priority::Normal, Job::new(|| {})), Err(Error::Rejected(_))));
+    }
+}
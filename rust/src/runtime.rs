@@ -0,0 +1,230 @@
+use jni::JavaVM;
+use std::fmt;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::bus::{self, Bus};
+use crate::callbacks::JavaCallbacks;
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::events::{self, EventSender};
+use crate::jni_bridge;
+use crate::native_activity::NativeActivity;
+use crate::notifications::store::StoreConfig;
+use crate::pool::{Job, Priority, WorkerPool};
+use crate::scheduler::{Scheduler, SystemClock};
+use crate::tasks::TaskManager;
+use crate::threads;
+
+static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+/// Process-wide state shared by every JNI entry point.
+///
+/// Created exactly once from the `JavaVM` handed to `JNI_OnLoad` and never
+/// replaced afterwards, so background threads can hold `&'static` references
+/// into it.
+pub struct Runtime {
+    java_vm: JavaVM,
+    config: Config,
+    callbacks: JavaCallbacks,
+    events: EventSender,
+    bus: Bus,
+    pool: Arc<WorkerPool>,
+    tasks: TaskManager,
+    scheduler: Scheduler,
+    native_activity: Mutex<Option<Arc<Mutex<NativeActivity>>>>,
+    /// Opened by the native activity; starts as `Config::notification_store`.
+    notification_store: Mutex<StoreConfig>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeError {
+    /// `JNI_OnLoad` has not run (or failed) so there is no `JavaVM` yet.
+    NotInitialized,
+    /// `init` was called a second time.
+    AlreadyInitialized,
+    /// A call needed the native activity before `initializeNativeActivity`.
+    NativeActivityNotInitialized,
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeError::NotInitialized => write!(f, "Rust runtime not initialized"),
+            RuntimeError::AlreadyInitialized => write!(f, "Rust runtime already initialized"),
+            RuntimeError::NativeActivityNotInitialized => {
+                write!(f, "Native activity not initialized")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+/// Crate initialization hooks, run in order from `JNI_OnLoad` so every
+/// subsystem is ready before Kotlin makes its first call.
+pub fn on_load(java_vm: JavaVM) -> Result<&'static Runtime> {
+    let config = Config::from_env();
+    init_logging(&config);
+
+    // Class lookups must happen here: this is the only native frame where
+    // `find_class` is guaranteed to see the application class loader
+    let callbacks = {
+        let mut env = java_vm.get_env()?;
+        let result = JavaCallbacks::new(&mut env, &config.bridge_class)
+            .and_then(|callbacks| {
+                jni_bridge::register_natives(&mut env, &config.bridge_class)?;
+                Ok(callbacks)
+            });
+        if result.is_err() {
+            // Report through our own error instead of a pending ClassNotFound
+            let _ = env.exception_clear();
+        }
+        result?
+    };
+
+    let (events, receiver) = events::channel();
+    let pool = WorkerPool::start(config.pool)?;
+    let runtime = init(java_vm, config, callbacks, events, pool)?;
+    bus::register_default_handlers(&runtime.bus);
+    runtime
+        .scheduler
+        .start()
+        .map_err(|e| Error::Internal(format!("failed to start scheduler: {}", e)))?;
+    events::start_dispatcher(receiver, runtime.callbacks(), runtime.config.event_format)
+        .map_err(|e| Error::Internal(format!("failed to start event dispatcher: {}", e)))?;
+    log::info!("Rust runtime initialized for {}", runtime.config.bridge_class);
+    Ok(runtime)
+}
+
+/// Stops everything `on_load` and later calls started. Called from
+/// `JNI_OnUnload`.
+pub fn on_unload() {
+    log::info!("Rust runtime shutting down");
+    threads::shutdown();
+}
+
+fn init_logging(config: &Config) {
+    #[cfg(target_os = "android")]
+    android_logger::init_once(
+        android_logger::Config::default()
+            .with_tag("mainlogic")
+            .with_max_level(config.log_level),
+    );
+    log::set_max_level(config.log_level);
+}
+
+/// Installs the process-wide runtime.
+pub fn init(
+    java_vm: JavaVM,
+    config: Config,
+    callbacks: JavaCallbacks,
+    events: EventSender,
+    pool: WorkerPool,
+) -> std::result::Result<&'static Runtime, RuntimeError> {
+    let pool = Arc::new(pool);
+    let mut created = false;
+    let runtime = RUNTIME.get_or_init(|| {
+        created = true;
+        Runtime {
+            java_vm,
+            notification_store: Mutex::new(config.notification_store.clone()),
+            config,
+            callbacks,
+            bus: Bus::new(events.clone()),
+            tasks: TaskManager::new(events.clone(), pool.clone()),
+            scheduler: {
+                let pool = pool.clone();
+                Scheduler::new(Arc::new(SystemClock::default()), move |run| {
+                    // The timer thread must never run a job itself
+                    pool.try_submit(Priority::Normal, Job::new(run))
+                })
+            },
+            pool,
+            events,
+            native_activity: Mutex::new(None),
+        }
+    });
+
+    if created {
+        Ok(runtime)
+    } else {
+        Err(RuntimeError::AlreadyInitialized)
+    }
+}
+
+/// Returns the runtime installed by `JNI_OnLoad`.
+pub fn get() -> std::result::Result<&'static Runtime, RuntimeError> {
+    RUNTIME.get().ok_or(RuntimeError::NotInitialized)
+}
+
+impl Runtime {
+    pub fn java_vm(&'static self) -> &'static JavaVM {
+        &self.java_vm
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn callbacks(&'static self) -> &'static JavaCallbacks {
+        &self.callbacks
+    }
+
+    pub fn events(&self) -> &EventSender {
+        &self.events
+    }
+
+    pub fn bus(&self) -> &Bus {
+        &self.bus
+    }
+
+    pub fn pool(&self) -> &WorkerPool {
+        &self.pool
+    }
+
+    pub fn tasks(&self) -> &TaskManager {
+        &self.tasks
+    }
+
+    pub fn scheduler(&self) -> &Scheduler {
+        &self.scheduler
+    }
+
+    /// Creates and initializes the native activity. Calling this again once
+    /// it succeeded is a no-op so the sensor thread is never started twice.
+    pub fn initialize_native_activity(&'static self) -> Result<()> {
+        let mut slot = self.native_activity.lock()?;
+        if slot.is_some() {
+            log::info!("Native activity already initialized");
+            return Ok(());
+        }
+
+        let native_activity = NativeActivity::new(
+            self.events.clone(),
+            self.config.sensor_backend.clone(),
+            &*self.notification_store.lock()?,
+            self.scheduler.clone(),
+        )?;
+        native_activity.initialize()?;
+        *slot = Some(Arc::new(Mutex::new(native_activity)));
+        Ok(())
+    }
+
+    /// Selects the notification store: used by `initialize_native_activity`,
+    /// or opened right away once the native activity exists.
+    pub fn set_notification_store(&self, store: StoreConfig) -> Result<()> {
+        let slot = self.native_activity.lock()?;
+        if let Some(activity) = slot.as_ref() {
+            activity.lock()?.set_notification_store(&store)?;
+        }
+        *self.notification_store.lock()? = store;
+        Ok(())
+    }
+
+    pub fn native_activity(&self) -> Result<Arc<Mutex<NativeActivity>>> {
+        self.native_activity
+            .lock()?
+            .clone()
+            .ok_or(RuntimeError::NativeActivityNotInitialized.into())
+    }
+}
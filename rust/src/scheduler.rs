@@ -0,0 +1,747 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, Result};
+use crate::threads;
+
+/// Finished or cancelled jobs kept around so Kotlin can still query them.
+const MAX_FINISHED_JOBS: usize = 64;
+/// Longest the timer thread sleeps without checking for a crate shutdown.
+const IDLE_POLL: Duration = Duration::from_millis(250);
+/// How long a run the executor refused waits before it is tried again.
+const RETRY_REJECTED: Duration = Duration::from_millis(100);
+
+/// Time source for the scheduler. Delays and periods use `monotonic`;
+/// `wall` is only consulted to evaluate cron expressions.
+pub trait Clock: Send + Sync {
+    /// Time elapsed since an arbitrary, fixed origin.
+    fn monotonic(&self) -> Duration;
+    fn wall(&self) -> DateTime<Utc>;
+}
+
+pub struct SystemClock {
+    origin: Instant,
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self {
+            origin: Instant::now(),
+        }
+    }
+}
+
+impl Clock for SystemClock {
+    fn monotonic(&self) -> Duration {
+        self.origin.elapsed()
+    }
+
+    fn wall(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Clock that only moves when `advance` is called.
+pub struct ManualClock {
+    now: Mutex<(Duration, DateTime<Utc>)>,
+}
+
+impl ManualClock {
+    pub fn new(wall: DateTime<Utc>) -> Self {
+        Self {
+            now: Mutex::new((Duration::ZERO, wall)),
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        let mut now = self.now.lock().unwrap();
+        now.0 += by;
+        now.1 += chrono::Duration::from_std(by).expect("advance out of range");
+    }
+}
+
+impl Clock for ManualClock {
+    fn monotonic(&self) -> Duration {
+        self.now.lock().unwrap().0
+    }
+
+    fn wall(&self) -> DateTime<Utc> {
+        self.now.lock().unwrap().1
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Schedule {
+    /// Runs once, `delay` from now.
+    Once { delay: Duration },
+    /// Runs every `period` measured from the first due time, so callback
+    /// latency does not accumulate. Ticks missed while a run was still
+    /// going collapse into one.
+    FixedRate {
+        initial_delay: Duration,
+        period: Duration,
+    },
+    /// Runs `delay` after the previous run finished.
+    FixedDelay {
+        initial_delay: Duration,
+        delay: Duration,
+    },
+    /// Runs whenever the cron expression matches, evaluated in UTC.
+    Cron(Box<cron::Schedule>),
+}
+
+impl Schedule {
+    /// Parses a cron expression with a leading seconds field, e.g.
+    /// `0 */15 * * * *` for every quarter hour.
+    pub fn cron(expression: &str) -> Result<Self> {
+        cron::Schedule::from_str(expression)
+            .map(|schedule| Schedule::Cron(Box::new(schedule)))
+            .map_err(|e| Error::InvalidArgument(format!("invalid cron expression {:?}: {}", expression, e)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct JobId(pub u64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Scheduled,
+    Paused,
+    Cancelled,
+    Finished,
+}
+
+impl JobState {
+    pub fn is_finished(self) -> bool {
+        matches!(self, JobState::Cancelled | JobState::Finished)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JobState::Scheduled => "scheduled",
+            JobState::Paused => "paused",
+            JobState::Cancelled => "cancelled",
+            JobState::Finished => "finished",
+        }
+    }
+}
+
+/// Passed to the job callback on every run.
+#[derive(Debug, Clone)]
+pub struct JobRun {
+    pub id: JobId,
+    pub name: String,
+    /// 1 for the first run.
+    pub run: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobInfo {
+    pub id: JobId,
+    pub name: String,
+    pub state: JobState,
+    pub runs: u64,
+}
+
+type JobFn = Arc<dyn Fn(&JobRun) + Send + Sync>;
+/// Runs a due job somewhere other than the timer thread.
+type Executor = Box<dyn Fn(Box<dyn FnOnce() + Send>) -> Result<()> + Send + Sync>;
+
+struct Entry {
+    name: String,
+    schedule: Schedule,
+    task: JobFn,
+    state: JobState,
+    next_due: Duration,
+    running: bool,
+    runs: u64,
+    max_runs: Option<u64>,
+}
+
+#[derive(Default)]
+struct State {
+    jobs: HashMap<JobId, Entry>,
+    /// Set whenever the timer thread may need to wake earlier than planned.
+    changed: bool,
+}
+
+struct Inner {
+    clock: Arc<dyn Clock>,
+    executor: Executor,
+    state: Mutex<State>,
+    wakeup: Condvar,
+    next_id: AtomicU64,
+}
+
+/// Runs delayed, periodic and cron jobs from a single timer thread.
+///
+/// The timer thread only decides what is due; the runs themselves go to
+/// the executor (the worker pool in the app), so a slow job never delays
+/// another one. Tests drive `run_due` directly with a `ManualClock`.
+#[derive(Clone)]
+pub struct Scheduler {
+    inner: Arc<Inner>,
+}
+
+impl Scheduler {
+    pub fn new(
+        clock: Arc<dyn Clock>,
+        executor: impl Fn(Box<dyn FnOnce() + Send>) -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                clock,
+                executor: Box::new(executor),
+                state: Mutex::new(State::default()),
+                wakeup: Condvar::new(),
+                next_id: AtomicU64::new(1),
+            }),
+        }
+    }
+
+    /// Starts the timer thread.
+    pub fn start(&self) -> io::Result<()> {
+        let scheduler = self.clone();
+        threads::spawn("scheduler", move || {
+            while !threads::is_shutting_down() {
+                let wait = scheduler.run_due().map_or(IDLE_POLL, |wait| wait.min(IDLE_POLL));
+                let mut state = scheduler.inner.state.lock().unwrap();
+                if !state.changed {
+                    state = scheduler.inner.wakeup.wait_timeout(state, wait).unwrap().0;
+                }
+                state.changed = false;
+            }
+        })?;
+        Ok(())
+    }
+
+    /// Adds a job. With `max_runs` set, the job finishes after that many
+    /// runs.
+    pub fn schedule(
+        &self,
+        name: &str,
+        schedule: Schedule,
+        max_runs: Option<u64>,
+        task: impl Fn(&JobRun) + Send + Sync + 'static,
+    ) -> Result<JobId> {
+        match &schedule {
+            Schedule::FixedRate { period, .. } if period.is_zero() => {
+                return Err(Error::InvalidArgument("fixed-rate period must be positive".to_string()));
+            }
+            Schedule::FixedDelay { delay, .. } if delay.is_zero() => {
+                return Err(Error::InvalidArgument("fixed-delay delay must be positive".to_string()));
+            }
+            _ => {}
+        }
+        if max_runs == Some(0) {
+            return Err(Error::InvalidArgument("max_runs must be positive".to_string()));
+        }
+
+        let now = self.inner.clock.monotonic();
+        let next_due = match &schedule {
+            Schedule::Once { delay } => now + *delay,
+            Schedule::FixedRate { initial_delay, .. } => now + *initial_delay,
+            Schedule::FixedDelay { initial_delay, .. } => now + *initial_delay,
+            Schedule::Cron(cron) => self
+                .next_cron_due(cron, now)
+                .ok_or_else(|| Error::InvalidArgument("cron expression never fires".to_string()))?,
+        };
+
+        let id = JobId(self.inner.next_id.fetch_add(1, Ordering::Relaxed));
+        let mut state = self.inner.state.lock()?;
+        state.jobs.insert(
+            id,
+            Entry {
+                name: name.to_string(),
+                schedule,
+                task: Arc::new(task),
+                state: JobState::Scheduled,
+                next_due,
+                running: false,
+                runs: 0,
+                max_runs,
+            },
+        );
+        self.notify(&mut state);
+        log::info!("Scheduler: job {} ({}) scheduled", id.0, name);
+        Ok(id)
+    }
+
+    /// Stops a scheduled job from running until `resume`. A run already in
+    /// progress completes.
+    pub fn pause(&self, id: JobId) -> bool {
+        self.transition(id, JobState::Scheduled, JobState::Paused)
+    }
+
+    /// Resumes a paused job. Fixed-rate and cron jobs skip the runs they
+    /// missed while paused; other jobs run right away if they became due.
+    pub fn resume(&self, id: JobId) -> bool {
+        let now = self.inner.clock.monotonic();
+        let mut state = self.inner.state.lock().unwrap();
+        let Some(entry) = state.jobs.get_mut(&id) else {
+            return false;
+        };
+        if entry.state != JobState::Paused {
+            return false;
+        }
+
+        entry.state = JobState::Scheduled;
+        match &entry.schedule {
+            Schedule::FixedRate { period, .. } => entry.next_due = skip_missed(entry.next_due, *period, now),
+            Schedule::Cron(cron) => match self.next_cron_due(cron, now) {
+                Some(next_due) => entry.next_due = next_due,
+                None => entry.state = JobState::Finished,
+            },
+            Schedule::Once { .. } | Schedule::FixedDelay { .. } => {}
+        }
+        self.notify(&mut state);
+        true
+    }
+
+    /// Returns `false` for unknown, finished or already cancelled jobs.
+    pub fn cancel(&self, id: JobId) -> bool {
+        let mut state = self.inner.state.lock().unwrap();
+        match state.jobs.get_mut(&id) {
+            Some(entry) if !entry.state.is_finished() => {
+                entry.state = JobState::Cancelled;
+                prune_finished(&mut state.jobs);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Wall-clock time of the scheduler's clock.
+    pub fn now(&self) -> DateTime<Utc> {
+        self.inner.clock.wall()
+    }
+
+    pub fn clock(&self) -> Arc<dyn Clock> {
+        self.inner.clock.clone()
+    }
+
+    pub fn info(&self, id: JobId) -> Option<JobInfo> {
+        self.inner.state.lock().unwrap().jobs.get(&id).map(|entry| JobInfo {
+            id,
+            name: entry.name.clone(),
+            state: entry.state,
+            runs: entry.runs,
+        })
+    }
+
+    /// Hands every due job to the executor. Returns how long until the
+    /// next job is due, or `None` if nothing is scheduled.
+    pub fn run_due(&self) -> Option<Duration> {
+        let now = self.inner.clock.monotonic();
+        let mut due = Vec::new();
+        {
+            let mut state = self.inner.state.lock().unwrap();
+            for (id, entry) in state.jobs.iter_mut() {
+                if entry.state != JobState::Scheduled || entry.running || entry.next_due > now {
+                    continue;
+                }
+                entry.runs += 1;
+                entry.running = true;
+                match &entry.schedule {
+                    Schedule::FixedRate { period, .. } => {
+                        entry.next_due = skip_missed(entry.next_due, *period, now);
+                    }
+                    Schedule::Cron(cron) => match self.next_cron_due(cron, now) {
+                        Some(next_due) => entry.next_due = next_due,
+                        // That was the last occurrence
+                        None => entry.state = JobState::Finished,
+                    },
+                    // Rescheduled, or finished, once the run completes
+                    Schedule::Once { .. } | Schedule::FixedDelay { .. } => {}
+                }
+                due.push((
+                    entry.task.clone(),
+                    JobRun {
+                        id: *id,
+                        name: entry.name.clone(),
+                        run: entry.runs,
+                    },
+                ));
+            }
+        }
+
+        for (task, run) in due {
+            let id = run.id;
+            let mut pending = PendingRun {
+                inner: Some(self.inner.clone()),
+                id,
+            };
+            let job = Box::new(move || {
+                let inner = pending.inner.take().expect("run started twice");
+                if panic::catch_unwind(AssertUnwindSafe(|| task(&run))).is_err() {
+                    log::error!("Scheduler: job {} ({}) panicked", run.id.0, run.name);
+                }
+                Scheduler { inner }.complete(run.id);
+            });
+            // A refused job was dropped, and its `PendingRun` retries it
+            if let Err(e) = (self.inner.executor)(job) {
+                log::warn!("Scheduler: could not run job {}, retrying: {}", id.0, e);
+            }
+        }
+
+        let state = self.inner.state.lock().unwrap();
+        state
+            .jobs
+            .values()
+            .filter(|entry| entry.state == JobState::Scheduled && !entry.running)
+            .map(|entry| entry.next_due.saturating_sub(now))
+            .min()
+    }
+
+    fn complete(&self, id: JobId) {
+        let now = self.inner.clock.monotonic();
+        let mut state = self.inner.state.lock().unwrap();
+        let Some(entry) = state.jobs.get_mut(&id) else {
+            return;
+        };
+        entry.running = false;
+
+        if entry.max_runs.is_some_and(|max_runs| entry.runs >= max_runs) {
+            if !entry.state.is_finished() {
+                entry.state = JobState::Finished;
+            }
+        } else {
+            match &entry.schedule {
+                Schedule::Once { .. } => {
+                    if !entry.state.is_finished() {
+                        entry.state = JobState::Finished;
+                    }
+                }
+                Schedule::FixedDelay { delay, .. } => entry.next_due = now + *delay,
+                Schedule::FixedRate { .. } | Schedule::Cron(_) => {}
+            }
+        }
+
+        if entry.state.is_finished() {
+            prune_finished(&mut state.jobs);
+        }
+        self.notify(&mut state);
+    }
+
+    /// Takes back a run that never started, so it counts neither towards
+    /// `max_runs` nor as the last occurrence, and tries it again shortly.
+    /// Also called for runs the pool dropped, from a worker or at shutdown.
+    fn retry(&self, id: JobId) {
+        let now = self.inner.clock.monotonic();
+        let mut state = self.inner.state.lock().unwrap();
+        let Some(entry) = state.jobs.get_mut(&id) else {
+            return;
+        };
+        entry.running = false;
+        entry.runs -= 1;
+        // Set by `run_due` for the last cron occurrence; a cancellation
+        // meanwhile stands
+        if entry.state == JobState::Finished {
+            entry.state = JobState::Scheduled;
+        }
+        entry.next_due = now + RETRY_REJECTED;
+        self.notify(&mut state);
+    }
+
+    fn transition(&self, id: JobId, from: JobState, to: JobState) -> bool {
+        let mut state = self.inner.state.lock().unwrap();
+        match state.jobs.get_mut(&id) {
+            Some(entry) if entry.state == from => {
+                entry.state = to;
+                self.notify(&mut state);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn notify(&self, state: &mut State) {
+        state.changed = true;
+        self.inner.wakeup.notify_one();
+    }
+
+    /// Converts the next cron match after the current wall time into a
+    /// monotonic due time.
+    fn next_cron_due(&self, cron: &cron::Schedule, now: Duration) -> Option<Duration> {
+        let wall = self.inner.clock.wall();
+        let next = cron.after(&wall).next()?;
+        Some(now + (next - wall).to_std().unwrap_or_default())
+    }
+}
+
+/// Owned by a run handed to the executor. If the run is dropped before it
+/// starts (refused, evicted from a full worker queue or drained at
+/// shutdown), the job would otherwise stay `running` forever; this takes
+/// the run back instead.
+struct PendingRun {
+    inner: Option<Arc<Inner>>,
+    id: JobId,
+}
+
+impl Drop for PendingRun {
+    fn drop(&mut self) {
+        if let Some(inner) = self.inner.take() {
+            Scheduler { inner }.retry(self.id);
+        }
+    }
+}
+
+/// Next due time of a fixed-rate job after `due`, skipping every tick that
+/// is already in the past.
+fn skip_missed(due: Duration, period: Duration, now: Duration) -> Duration {
+    if due > now {
+        return due;
+    }
+    let missed = ((now - due).as_nanos() / period.as_nanos()) as u32 + 1;
+    due + period * missed
+}
+
+fn prune_finished(jobs: &mut HashMap<JobId, Entry>) {
+    let mut finished: Vec<JobId> = jobs
+        .iter()
+        .filter(|(_, entry)| entry.state.is_finished() && !entry.running)
+        .map(|(id, _)| *id)
+        .collect();
+    if finished.len() <= MAX_FINISHED_JOBS {
+        return;
+    }
+    // Ids grow monotonically, so this drops the oldest jobs first
+    finished.sort();
+    for id in &finished[..finished.len() - MAX_FINISHED_JOBS] {
+        jobs.remove(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn ms(value: u64) -> Duration {
+        Duration::from_millis(value)
+    }
+
+    /// `(run, clock.monotonic())` for every run of a job.
+    type RunLog = Arc<Mutex<Vec<(u64, Duration)>>>;
+
+    /// Scheduler that runs jobs inline, plus a log of `(run, time)` pairs
+    /// for the job under test.
+    fn setup() -> (Scheduler, Arc<ManualClock>, RunLog) {
+        let clock = Arc::new(ManualClock::new(Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap()));
+        let scheduler = Scheduler::new(clock.clone(), |run| {
+            run();
+            Ok(())
+        });
+        (scheduler, clock, Arc::new(Mutex::new(Vec::new())))
+    }
+
+    fn recorder(
+        clock: &Arc<ManualClock>,
+        log: &RunLog,
+    ) -> impl Fn(&JobRun) + Send + Sync + 'static {
+        let clock = clock.clone();
+        let log = log.clone();
+        move |run| log.lock().unwrap().push((run.run, clock.monotonic()))
+    }
+
+    /// Advances the clock in `step` increments up to `total`, running due
+    /// jobs after every step like the timer thread would.
+    fn run_for(scheduler: &Scheduler, clock: &ManualClock, total: Duration, step: Duration) {
+        let mut elapsed = Duration::ZERO;
+        while elapsed < total {
+            clock.advance(step);
+            elapsed += step;
+            scheduler.run_due();
+        }
+    }
+
+    #[test]
+    fn once_runs_after_delay_then_finishes() {
+        let (scheduler, clock, log) = setup();
+        let id = scheduler
+            .schedule("once", Schedule::Once { delay: ms(100) }, None, recorder(&clock, &log))
+            .unwrap();
+
+        assert_eq!(scheduler.run_due(), Some(ms(100)));
+        run_for(&scheduler, &clock, ms(300), ms(10));
+
+        assert_eq!(*log.lock().unwrap(), vec![(1, ms(100))]);
+        assert_eq!(scheduler.info(id).unwrap().state, JobState::Finished);
+        assert_eq!(scheduler.run_due(), None);
+    }
+
+    #[test]
+    fn fixed_rate_does_not_drift_and_skips_missed_ticks() {
+        let (scheduler, clock, log) = setup();
+        let schedule = Schedule::FixedRate {
+            initial_delay: ms(50),
+            period: ms(100),
+        };
+        scheduler.schedule("rate", schedule, None, recorder(&clock, &log)).unwrap();
+
+        // Polled late every time: runs still land on the 100ms grid
+        run_for(&scheduler, &clock, ms(315), ms(35));
+        let times: Vec<Duration> = log.lock().unwrap().iter().map(|(_, at)| *at).collect();
+        assert_eq!(times, vec![ms(70), ms(175), ms(280)]);
+
+        // A long stall only produces one catch-up run
+        clock.advance(ms(1000));
+        scheduler.run_due();
+        assert_eq!(log.lock().unwrap().len(), 4);
+        assert_eq!(scheduler.run_due(), Some(ms(35)));
+    }
+
+    #[test]
+    fn fixed_delay_waits_from_completion() {
+        let (scheduler, clock, log) = setup();
+        let worker_clock = clock.clone();
+        let record = recorder(&clock, &log);
+        let schedule = Schedule::FixedDelay {
+            initial_delay: ms(10),
+            delay: ms(100),
+        };
+        scheduler
+            .schedule("delay", schedule, Some(3), move |run| {
+                record(run);
+                // Each run takes 40ms
+                worker_clock.advance(ms(40));
+            })
+            .unwrap();
+
+        run_for(&scheduler, &clock, ms(500), ms(10));
+        let times: Vec<Duration> = log.lock().unwrap().iter().map(|(_, at)| *at).collect();
+        assert_eq!(times, vec![ms(10), ms(150), ms(290)]);
+    }
+
+    #[test]
+    fn max_runs_finishes_job() {
+        let (scheduler, clock, log) = setup();
+        let schedule = Schedule::FixedRate {
+            initial_delay: ms(0),
+            period: ms(10),
+        };
+        let id = scheduler.schedule("limited", schedule, Some(3), recorder(&clock, &log)).unwrap();
+
+        run_for(&scheduler, &clock, ms(100), ms(10));
+        assert_eq!(log.lock().unwrap().len(), 3);
+        let info = scheduler.info(id).unwrap();
+        assert_eq!((info.state, info.runs), (JobState::Finished, 3));
+    }
+
+    #[test]
+    fn paused_jobs_do_not_run_and_resume_on_the_grid() {
+        let (scheduler, clock, log) = setup();
+        let schedule = Schedule::FixedRate {
+            initial_delay: ms(100),
+            period: ms(100),
+        };
+        let id = scheduler.schedule("pausable", schedule, None, recorder(&clock, &log)).unwrap();
+
+        run_for(&scheduler, &clock, ms(100), ms(10));
+        assert!(scheduler.pause(id));
+        assert!(!scheduler.pause(id));
+        run_for(&scheduler, &clock, ms(250), ms(10));
+        assert_eq!(log.lock().unwrap().len(), 1);
+
+        assert!(scheduler.resume(id));
+        run_for(&scheduler, &clock, ms(100), ms(10));
+        let times: Vec<Duration> = log.lock().unwrap().iter().map(|(_, at)| *at).collect();
+        assert_eq!(times, vec![ms(100), ms(400)]);
+    }
+
+    #[test]
+    fn cancelled_jobs_never_run_again() {
+        let (scheduler, clock, log) = setup();
+        let schedule = Schedule::FixedRate {
+            initial_delay: ms(10),
+            period: ms(10),
+        };
+        let id = scheduler.schedule("cancel", schedule, None, recorder(&clock, &log)).unwrap();
+
+        run_for(&scheduler, &clock, ms(20), ms(10));
+        assert!(scheduler.cancel(id));
+        assert!(!scheduler.cancel(id));
+        assert!(!scheduler.resume(id));
+        run_for(&scheduler, &clock, ms(100), ms(10));
+
+        assert_eq!(log.lock().unwrap().len(), 2);
+        assert_eq!(scheduler.info(id).unwrap().state, JobState::Cancelled);
+    }
+
+    #[test]
+    fn cron_follows_wall_clock() {
+        let (scheduler, clock, log) = setup();
+        let schedule = Schedule::cron("*/15 * * * * *").unwrap();
+        scheduler.schedule("cron", schedule, None, recorder(&clock, &log)).unwrap();
+
+        run_for(&scheduler, &clock, Duration::from_secs(60), Duration::from_secs(1));
+        let times: Vec<u64> = log.lock().unwrap().iter().map(|(_, at)| at.as_secs()).collect();
+        assert_eq!(times, vec![15, 30, 45, 60]);
+    }
+
+    #[test]
+    fn cron_finishes_after_its_last_occurrence() {
+        let (scheduler, clock, log) = setup();
+        // Seconds 0, 20 and 40 of 12:00 on 2024-01-01 only; it is 12:00:00
+        let schedule = Schedule::cron("0/20 0 12 1 1 * 2024").unwrap();
+        let id = scheduler.schedule("cron", schedule, None, recorder(&clock, &log)).unwrap();
+
+        run_for(&scheduler, &clock, Duration::from_secs(120), Duration::from_secs(1));
+        let times: Vec<u64> = log.lock().unwrap().iter().map(|(_, at)| at.as_secs()).collect();
+        assert_eq!(times, vec![20, 40]);
+        assert_eq!(scheduler.info(id).unwrap().state, JobState::Finished);
+        assert_eq!(scheduler.run_due(), None);
+    }
+
+    #[test]
+    fn rejects_invalid_schedules() {
+        let (scheduler, _, _) = setup();
+        assert!(Schedule::cron("not a cron").is_err());
+        let schedule = Schedule::FixedRate {
+            initial_delay: ms(0),
+            period: ms(0),
+        };
+        assert!(scheduler.schedule("zero", schedule, None, |_| {}).is_err());
+        assert!(scheduler
+            .schedule("none", Schedule::Once { delay: ms(0) }, Some(0), |_| {})
+            .is_err());
+    }
+
+    #[test]
+    fn rejected_runs_are_retried() {
+        let clock = Arc::new(ManualClock::new(Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap()));
+        let full = Arc::new(std::sync::atomic::AtomicBool::new(true));
+        let queue = full.clone();
+        let scheduler = Scheduler::new(clock.clone(), move |run| {
+            if queue.load(Ordering::SeqCst) {
+                return Err(Error::Rejected("full".to_string()));
+            }
+            run();
+            Ok(())
+        });
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let id = scheduler
+            .schedule("once", Schedule::Once { delay: ms(10) }, None, recorder(&clock, &log))
+            .unwrap();
+
+        run_for(&scheduler, &clock, ms(300), ms(10));
+        let info = scheduler.info(id).unwrap();
+        assert_eq!((info.state, info.runs), (JobState::Scheduled, 0));
+        // Tried at 10, 110 and 210 ms
+        assert_eq!(scheduler.run_due(), Some(ms(10)));
+
+        full.store(false, Ordering::SeqCst);
+        run_for(&scheduler, &clock, RETRY_REJECTED, ms(10));
+        assert_eq!(*log.lock().unwrap(), vec![(1, ms(310))]);
+        assert_eq!(scheduler.info(id).unwrap().state, JobState::Finished);
+    }
+}
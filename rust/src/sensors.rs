@@ -1,14 +1,167 @@
-use std::sync::{Arc, Mutex};
-use std::thread;
-use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, Result};
+use crate::tasks::CancellationToken;
+use crate::threads::{self, ThreadHandle};
+
+#[cfg(target_os = "android")]
+pub mod android;
+pub mod filters;
+pub mod fusion;
+pub mod gestures;
+pub mod history;
+pub mod recording;
+pub mod rules;
+pub mod simulator;
+
+#[cfg(target_os = "android")]
+use android::AndroidSensors;
+use filters::{FilterConfig, SensorFilters};
+use history::{SensorHistories, SensorStats};
+use recording::{RecordingFormat, RecordingWriter, ReplayConfig, ReplaySensorSource};
+use simulator::SimulatedSensors;
+
+/// Longest a source may block in `poll` before the thread checks for shutdown.
+const POLL_TIMEOUT: Duration = Duration::from_millis(250);
 
 pub struct SensorManager {
+    backend: SensorBackend,
+    config: SensorConfig,
+    monitor: Option<Monitor>,
+    /// Monitoring was stopped by `pause_monitoring` and restarts on
+    /// `resume_monitoring`.
+    resume_on_foreground: bool,
     accelerometer_data: Arc<Mutex<SensorData>>,
     gyroscope_data: Arc<Mutex<SensorData>>,
     magnetometer_data: Arc<Mutex<SensorData>>,
-    callback: Option<Box<dyn Fn(i32, f32) + Send + Sync>>,
+    /// Applied to every reading before it is stored or delivered.
+    filters: Arc<Mutex<SensorFilters>>,
+    /// The filtered readings of the last few seconds.
+    history: Arc<Mutex<SensorHistories>>,
+    /// Receives the unfiltered readings while a recording runs.
+    recorder: Arc<Mutex<Option<Recorder>>>,
+    /// Shared with the monitoring thread so it can be replaced while running.
+    callback: Arc<RwLock<Option<SensorCallback>>>,
+}
+
+type Recorder = RecordingWriter<BufWriter<File>>;
+
+/// The running monitoring thread.
+struct Monitor {
+    stop: CancellationToken,
+    thread: ThreadHandle,
+}
+
+/// Receives every flushed batch; without batching each batch holds the
+/// samples of one poll.
+pub type SensorCallback = Arc<dyn Fn(&[SensorEvent]) + Send + Sync>;
+
+/// Per-sensor settings in a `SensorConfig`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SensorSettings {
+    pub enabled: bool,
+    pub period_ms: u64,
+}
+
+impl Default for SensorSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            period_ms: 100,
+        }
+    }
+}
+
+impl SensorSettings {
+    pub fn period(&self) -> Duration {
+        Duration::from_millis(self.period_ms)
+    }
+}
+
+/// Which sensors to read, how often, and how samples are batched.
+///
+/// Kotlin passes it to `startSensors` as JSON; missing fields keep their
+/// defaults, e.g. `{"gyroscope":{"enabled":false},"batch_latency_ms":500}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SensorConfig {
+    pub accelerometer: SensorSettings,
+    pub gyroscope: SensorSettings,
+    pub magnetometer: SensorSettings,
+    /// Longest a sample is held back before its batch is delivered.
+    /// 0 delivers every sample as soon as it is read.
+    pub batch_latency_ms: u64,
+    /// A batch is delivered early once it holds this many samples.
+    pub batch_size: usize,
+}
+
+impl Default for SensorConfig {
+    fn default() -> Self {
+        Self {
+            accelerometer: SensorSettings::default(),
+            gyroscope: SensorSettings::default(),
+            magnetometer: SensorSettings::default(),
+            batch_latency_ms: 0,
+            batch_size: 64,
+        }
+    }
+}
+
+impl SensorConfig {
+    /// Every sensor enabled at `period_ms`, without batching.
+    pub fn all(period_ms: u64) -> Self {
+        let settings = SensorSettings {
+            enabled: true,
+            period_ms,
+        };
+        Self {
+            accelerometer: settings,
+            gyroscope: settings,
+            magnetometer: settings,
+            ..Self::default()
+        }
+    }
+
+    pub fn settings(&self, kind: SensorKind) -> &SensorSettings {
+        match kind {
+            SensorKind::Accelerometer => &self.accelerometer,
+            SensorKind::Gyroscope => &self.gyroscope,
+            SensorKind::Magnetometer => &self.magnetometer,
+        }
+    }
+
+    /// Enabled sensors and their sampling periods.
+    pub fn enabled(&self) -> impl Iterator<Item = (SensorKind, Duration)> + '_ {
+        SensorKind::ALL
+            .into_iter()
+            .filter(|kind| self.settings(*kind).enabled)
+            .map(|kind| (kind, self.settings(kind).period()))
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.enabled().next().is_none() {
+            return Err(Error::InvalidArgument("no sensor enabled".to_string()));
+        }
+        if let Some((kind, _)) = self.enabled().find(|(_, period)| period.is_zero()) {
+            return Err(Error::InvalidArgument(format!("{} period must be positive", kind.name())));
+        }
+        if self.batch_size == 0 {
+            return Err(Error::InvalidArgument("batch_size must be positive".to_string()));
+        }
+        Ok(())
+    }
 }
 
+/// Latest reading of one sensor. `timestamp` is in milliseconds on the
+/// same clock as `SensorEvent::timestamp_ns`.
 #[derive(Clone)]
 pub struct SensorData {
     pub x: f32,
@@ -17,9 +170,186 @@ pub struct SensorData {
     pub timestamp: u64,
 }
 
+impl From<&SensorEvent> for SensorData {
+    fn from(event: &SensorEvent) -> Self {
+        Self {
+            x: event.x,
+            y: event.y,
+            z: event.z,
+            timestamp: event.timestamp_ns / 1_000_000,
+        }
+    }
+}
+
+/// One 3-axis sample as delivered by a `SensorSource`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SensorEvent {
+    pub sensor: SensorKind,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub accuracy: SensorAccuracy,
+    /// Nanoseconds on a monotonic clock: the sensor clock
+    /// (`SystemClock.elapsedRealtimeNanos`) on Android, `monotonic_nanos`
+    /// elsewhere. Only differences between timestamps are meaningful.
+    pub timestamp_ns: u64,
+}
+
+impl SensorEvent {
+    pub fn magnitude(&self) -> f32 {
+        (self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SensorAccuracy {
+    Unreliable,
+    Low,
+    Medium,
+    High,
+}
+
+/// Nanoseconds since the first call, for sources without a clock of their
+/// own.
+pub fn monotonic_nanos() -> u64 {
+    static ORIGIN: OnceLock<Instant> = OnceLock::new();
+    ORIGIN.get_or_init(Instant::now).elapsed().as_nanos() as u64
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SensorKind {
+    Accelerometer,
+    Gyroscope,
+    Magnetometer,
+}
+
+impl SensorKind {
+    pub const ALL: [SensorKind; 3] = [
+        SensorKind::Accelerometer,
+        SensorKind::Gyroscope,
+        SensorKind::Magnetometer,
+    ];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SensorKind::Accelerometer => "Accelerometer",
+            SensorKind::Gyroscope => "Gyroscope",
+            SensorKind::Magnetometer => "Magnetometer",
+        }
+    }
+
+    /// The snake_case name used on the wire, e.g. `gyroscope`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SensorKind::Accelerometer => "accelerometer",
+            SensorKind::Gyroscope => "gyroscope",
+            SensorKind::Magnetometer => "magnetometer",
+        }
+    }
+}
+
+/// Parses `SensorKind::as_str` names.
+impl FromStr for SensorKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let name = s.to_ascii_lowercase();
+        SensorKind::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == name)
+            .ok_or_else(|| Error::InvalidArgument(format!("unknown sensor: {}", s)))
+    }
+}
+
+/// Where sensor readings come from.
+///
+/// A source is opened on the monitoring thread and only used there; the
+/// Android implementation is bound to that thread's looper.
+pub trait SensorSource {
+    fn name(&self) -> &'static str;
+
+    /// Waits up to `timeout` for new samples and appends them to `events`.
+    /// Should return early once `stop` is cancelled. Returning with nothing
+    /// appended is fine.
+    fn poll(&mut self, timeout: Duration, stop: &CancellationToken, events: &mut Vec<SensorEvent>) -> Result<()>;
+}
+
+/// Which `SensorSource` `SensorManager` opens.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SensorBackend {
+    /// Hardware sensors when available, the simulator otherwise.
+    Auto,
+    /// Hardware sensors only; monitoring fails without them.
+    Android,
+    Simulator,
+    /// Plays back a recording, parsed from `replay:<path>`.
+    Replay(ReplayConfig),
+}
+
+impl FromStr for SensorBackend {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        const REPLAY: &str = "replay:";
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Ok(SensorBackend::Auto),
+            "android" => Ok(SensorBackend::Android),
+            "simulator" => Ok(SensorBackend::Simulator),
+            // The path keeps its case
+            lower if lower.starts_with(REPLAY) => Ok(SensorBackend::Replay(ReplayConfig::new(&s[REPLAY.len()..]))),
+            other => Err(Error::InvalidArgument(format!("unknown sensor backend: {}", other))),
+        }
+    }
+}
+
+impl SensorBackend {
+    fn open(&self, config: &SensorConfig) -> Result<Box<dyn SensorSource>> {
+        match self {
+            SensorBackend::Simulator => Ok(Box::new(SimulatedSensors::new(config))),
+            SensorBackend::Replay(replay) => Ok(Box::new(ReplaySensorSource::open(replay, config)?)),
+            #[cfg(target_os = "android")]
+            SensorBackend::Android => Ok(Box::new(AndroidSensors::open(config)?)),
+            #[cfg(not(target_os = "android"))]
+            SensorBackend::Android => Err(Error::InvalidArgument(
+                "Android sensors are not available on this platform".to_string(),
+            )),
+            #[cfg(target_os = "android")]
+            SensorBackend::Auto => match AndroidSensors::open(config) {
+                Ok(source) => Ok(Box::new(source)),
+                Err(e) => {
+                    log::warn!("Hardware sensors unavailable ({}), using the simulator", e);
+                    Ok(Box::new(SimulatedSensors::new(config)))
+                }
+            },
+            #[cfg(not(target_os = "android"))]
+            SensorBackend::Auto => Ok(Box::new(SimulatedSensors::new(config))),
+        }
+    }
+}
+
+impl Default for SensorManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SensorManager {
     pub fn new() -> Self {
+        Self::with_backend(SensorBackend::Auto)
+    }
+
+    pub fn with_backend(backend: SensorBackend) -> Self {
         Self {
+            backend,
+            config: SensorConfig::default(),
+            monitor: None,
+            resume_on_foreground: false,
             accelerometer_data: Arc::new(Mutex::new(SensorData {
                 x: 0.0,
                 y: 0.0,
@@ -38,79 +368,422 @@ impl SensorManager {
                 z: 0.0,
                 timestamp: 0,
             })),
-            callback: None,
-        }
-    }
-    
-    pub fn set_callback(&mut self, callback: impl Fn(i32, f32) + Send + Sync + 'static) {
-        self.callback = Some(Box::new(callback));
-    }
-    
-    pub fn start_sensor_monitoring(&mut self) {
-        let accelerometer_data = self.accelerometer_data.clone();
-        let gyroscope_data = self.gyroscope_data.clone();
-        let magnetometer_data = self.magnetometer_data.clone();
-        
-        // Take ownership of the callback
-        let callback = self.callback.take();
-        
-        thread::spawn(move || {
-            loop {
-                // Simulate sensor data updates
-                {
-                    let mut accel = accelerometer_data.lock().unwrap();
-                    accel.x += 0.1;
-                    accel.y += 0.05;
-                    accel.z = 9.8 + (accel.x * 0.01).sin();
-                    accel.timestamp = std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap()
-                        .as_millis() as u64;
+            filters: Arc::new(Mutex::new(SensorFilters::default())),
+            history: Arc::new(Mutex::new(SensorHistories::default())),
+            recorder: Arc::new(Mutex::new(None)),
+            callback: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Sets the callback, or replaces it; a running monitor delivers its
+    /// next batch to the new one.
+    pub fn set_callback(&mut self, callback: impl Fn(&[SensorEvent]) + Send + Sync + 'static) {
+        *self.callback.write().unwrap() = Some(Arc::new(callback));
+    }
+
+    pub fn clear_callback(&mut self) {
+        *self.callback.write().unwrap() = None;
+    }
+
+    pub fn config(&self) -> &SensorConfig {
+        &self.config
+    }
+
+    /// Replaces the configuration. A running monitor is restarted with it.
+    pub fn set_config(&mut self, config: SensorConfig) -> Result<()> {
+        config.validate()?;
+        if config == self.config {
+            return Ok(());
+        }
+        self.config = config;
+        if self.is_monitoring() {
+            self.stop_sensor_monitoring();
+            self.start_sensor_monitoring()?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the filter pipelines, also while monitoring runs. The new
+    /// filters start without history.
+    pub fn set_filters(&mut self, config: &FilterConfig) -> Result<()> {
+        *self.filters.lock().unwrap() = SensorFilters::new(config)?;
+        Ok(())
+    }
+
+    pub fn backend(&self) -> &SensorBackend {
+        &self.backend
+    }
+
+    /// Switches the sensor source. A running monitor is restarted on it.
+    pub fn set_backend(&mut self, backend: SensorBackend) -> Result<()> {
+        if let SensorBackend::Replay(replay) = &backend {
+            replay.validate()?;
+        }
+        self.backend = backend;
+        if self.is_monitoring() {
+            self.stop_sensor_monitoring();
+            self.start_sensor_monitoring()?;
+        }
+        Ok(())
+    }
+
+    /// Records every reading from now on to `path`, before filtering.
+    /// Replaces a recording in progress, which is finished first.
+    pub fn start_recording(&mut self, path: &Path, format: RecordingFormat) -> Result<()> {
+        let recorder = RecordingWriter::create(path, format)?;
+        self.stop_recording()?;
+        log::info!("Recording sensors to {}", path.display());
+        *self.recorder.lock()? = Some(recorder);
+        Ok(())
+    }
+
+    /// Finishes the recording in progress and returns the number of samples
+    /// it holds, or `None` if nothing was being recorded.
+    pub fn stop_recording(&mut self) -> Result<Option<u64>> {
+        let Some(recorder) = self.recorder.lock()?.take() else {
+            return Ok(None);
+        };
+        let count = recorder.count();
+        recorder.finish()?;
+        log::info!("Recorded {} sensor samples", count);
+        Ok(Some(count))
+    }
+
+    pub fn is_monitoring(&self) -> bool {
+        self.monitor.as_ref().is_some_and(|monitor| !monitor.thread.is_finished())
+    }
+
+    /// Starts reading sensors with the current configuration, failing if
+    /// the backend cannot open them. Does nothing if monitoring is already
+    /// running.
+    pub fn start_sensor_monitoring(&mut self) -> Result<()> {
+        if self.is_monitoring() {
+            log::debug!("Sensor monitoring already running");
+            return Ok(());
+        }
+
+        let backend = self.backend.clone();
+        let config = self.config.clone();
+        let callback = self.callback.clone();
+        let filters = self.filters.clone();
+        filters.lock().unwrap().reset();
+        let recorder = self.recorder.clone();
+        let history = self.history.clone();
+        let stop = CancellationToken::default();
+        let latest = [
+            self.accelerometer_data.clone(),
+            self.gyroscope_data.clone(),
+            self.magnetometer_data.clone(),
+        ];
+
+        let token = stop.clone();
+        // Opened on the monitor thread, which Android's event queue is bound
+        // to; the result comes back so a missing sensor reaches the caller
+        let (opened_tx, opened) = mpsc::channel();
+        let thread = threads::spawn("sensor-monitor", move || {
+            let mut source = match backend.open(&config) {
+                Ok(source) => {
+                    let _ = opened_tx.send(Ok(()));
+                    source
                 }
-                
-                {
-                    let mut gyro = gyroscope_data.lock().unwrap();
-                    gyro.x += 0.01;
-                    gyro.y += 0.02;
-                    gyro.z += 0.005;
-                    gyro.timestamp = std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap()
-                        .as_millis() as u64;
+                Err(e) => {
+                    let _ = opened_tx.send(Err(e));
+                    return;
                 }
-                
-                {
-                    let mut mag = magnetometer_data.lock().unwrap();
-                    mag.x = 25.0 + (mag.x * 0.1).sin() * 5.0;
-                    mag.y = 30.0 + (mag.y * 0.1).cos() * 3.0;
-                    mag.z = 45.0 + (mag.z * 0.1).sin() * 2.0;
-                    mag.timestamp = std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap()
-                        .as_millis() as u64;
+            };
+            log::info!("Sensor monitoring using the {} source", source.name());
+
+            let deliver = |samples: Vec<SensorEvent>| {
+                // Clone so the callback runs without the lock and can be
+                // swapped from another thread meanwhile
+                let callback = callback.read().unwrap().clone();
+                if let Some(callback) = callback {
+                    callback(&samples);
                 }
-                
-                // Call callback with sensor data
-                if let Some(callback) = &callback {
-                    callback(0, 0.0); // Accelerometer
-                    callback(1, 0.0); // Gyroscope
-                    callback(2, 0.0); // Magnetometer
+            };
+            let mut batch = Batch::new(&config);
+            let mut events = Vec::new();
+            while !token.is_cancelled() {
+                if let Err(e) = source.poll(batch.poll_timeout(), &token, &mut events) {
+                    log::error!("Sensor source {} failed: {}", source.name(), e);
+                    break;
+                }
+
+                record(&recorder, &events);
+                filters.lock().unwrap().apply(&mut events);
+                for event in &events {
+                    *latest[event.sensor.index()].lock().unwrap() = SensorData::from(event);
+                }
+                history.lock().unwrap().extend(&events);
+                batch.extend(events.drain(..));
+                if batch.is_due() {
+                    deliver(batch.take());
                 }
-                
-                thread::sleep(Duration::from_millis(100));
             }
-        });
+            if !batch.is_empty() {
+                deliver(batch.take());
+            }
+            log::info!("Sensor monitoring stopped");
+        })
+        .map_err(|e| Error::Internal(format!("failed to start sensor monitoring: {}", e)))?;
+
+        let opened = opened
+            .recv()
+            .unwrap_or_else(|_| Err(Error::Internal("sensor monitor exited before opening the sensors".to_string())));
+        if let Err(e) = opened {
+            thread.join();
+            log::error!("Failed to open sensors: {}", e);
+            return Err(e);
+        }
+        self.monitor = Some(Monitor { stop, thread });
+        Ok(())
+    }
+
+    /// Stops the monitoring thread and waits for it to exit, which releases
+    /// the sensors. Does nothing if monitoring is not running.
+    pub fn stop_sensor_monitoring(&mut self) {
+        if let Some(monitor) = self.monitor.take() {
+            monitor.stop.cancel();
+            monitor.thread.join();
+        }
+    }
+
+    /// Stops monitoring while the app is in the background, remembering
+    /// whether to restart it in `resume_monitoring`.
+    pub fn pause_monitoring(&mut self) {
+        if self.is_monitoring() {
+            self.stop_sensor_monitoring();
+            self.resume_on_foreground = true;
+        }
+    }
+
+    /// Restarts monitoring if `pause_monitoring` stopped it.
+    pub fn resume_monitoring(&mut self) -> Result<()> {
+        if std::mem::take(&mut self.resume_on_foreground) {
+            self.start_sensor_monitoring()?;
+        }
+        Ok(())
+    }
+
+    /// Changes how many seconds of readings each sensor keeps.
+    pub fn set_history_window(&self, window: Duration) -> Result<()> {
+        self.history.lock()?.set_window(window)
+    }
+
+    /// `kind`'s readings of the last `window` (everything kept if `None`),
+    /// flattened as described at `SensorHistory::to_floats`.
+    pub fn history(&self, kind: SensorKind, window: Option<Duration>) -> Result<Vec<f32>> {
+        Ok(self.history.lock()?.get(kind).to_floats(window))
+    }
+
+    /// Statistics of `kind`'s readings of the last `window`, `None` before
+    /// the sensor has reported.
+    pub fn stats(&self, kind: SensorKind, window: Option<Duration>) -> Result<Option<SensorStats>> {
+        Ok(self.history.lock()?.get(kind).stats(window))
     }
-    
+
     pub fn get_accelerometer_data(&self) -> SensorData {
         self.accelerometer_data.lock().unwrap().clone()
     }
-    
+
     pub fn get_gyroscope_data(&self) -> SensorData {
         self.gyroscope_data.lock().unwrap().clone()
     }
-    
+
     pub fn get_magnetometer_data(&self) -> SensorData {
         self.magnetometer_data.lock().unwrap().clone()
     }
-} 
\ No newline at end of file
+}
+
+impl Drop for SensorManager {
+    fn drop(&mut self) {
+        self.stop_sensor_monitoring();
+        if let Err(e) = self.stop_recording() {
+            log::error!("Failed to finish sensor recording: {}", e);
+        }
+    }
+}
+
+/// Appends `events` to the recording in progress. A failed write ends the
+/// recording rather than monitoring.
+fn record(recorder: &Mutex<Option<Recorder>>, events: &[SensorEvent]) {
+    let mut recorder = recorder.lock().unwrap();
+    if let Some(writer) = recorder.as_mut() {
+        if let Err(e) = writer.write(events) {
+            log::error!("Sensor recording stopped: {}", e);
+            *recorder = None;
+        }
+    }
+}
+
+/// Software batching: samples are held until the oldest one is
+/// `batch_latency_ms` old or `batch_size` of them are pending.
+struct Batch {
+    latency: Duration,
+    size: usize,
+    samples: Vec<SensorEvent>,
+    oldest: Option<Instant>,
+}
+
+impl Batch {
+    fn new(config: &SensorConfig) -> Self {
+        Self {
+            latency: Duration::from_millis(config.batch_latency_ms),
+            size: config.batch_size,
+            samples: Vec::new(),
+            oldest: None,
+        }
+    }
+
+    fn extend(&mut self, samples: impl Iterator<Item = SensorEvent>) {
+        let before = self.samples.len();
+        self.samples.extend(samples);
+        if before == 0 && !self.samples.is_empty() {
+            self.oldest = Some(Instant::now());
+        }
+    }
+
+    fn is_due(&self) -> bool {
+        match self.oldest {
+            Some(oldest) => self.samples.len() >= self.size || oldest.elapsed() >= self.latency,
+            None => false,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    fn take(&mut self) -> Vec<SensorEvent> {
+        self.oldest = None;
+        std::mem::take(&mut self.samples)
+    }
+
+    /// Wakes the source up in time to deliver the pending batch.
+    fn poll_timeout(&self) -> Duration {
+        match self.oldest {
+            Some(oldest) => self.latency.saturating_sub(oldest.elapsed()).min(POLL_TIMEOUT),
+            None => POLL_TIMEOUT,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn simulated(period_ms: u64) -> SensorManager {
+        let mut manager = SensorManager::with_backend(SensorBackend::Simulator);
+        manager.set_config(SensorConfig::all(period_ms)).unwrap();
+        manager
+    }
+
+    fn wait_for(condition: impl Fn() -> bool) {
+        let deadline = std::time::Instant::now() + Duration::from_secs(2);
+        while !condition() {
+            assert!(std::time::Instant::now() < deadline, "timed out");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn start_and_stop_are_idempotent() {
+        let mut manager = simulated(5);
+        manager.stop_sensor_monitoring();
+        manager.start_sensor_monitoring().unwrap();
+        let thread = manager.monitor.as_ref().unwrap().thread.clone();
+        manager.start_sensor_monitoring().unwrap();
+        assert!(manager.is_monitoring());
+
+        manager.stop_sensor_monitoring();
+        assert!(thread.is_finished());
+        assert!(!manager.is_monitoring());
+        manager.stop_sensor_monitoring();
+    }
+
+    #[test]
+    fn callback_can_be_swapped_while_running() {
+        let mut manager = simulated(5);
+        let first = Arc::new(AtomicUsize::new(0));
+        let second = Arc::new(AtomicUsize::new(0));
+
+        let counter = first.clone();
+        manager.set_callback(move |samples| {
+            counter.fetch_add(samples.len(), Ordering::SeqCst);
+        });
+        manager.start_sensor_monitoring().unwrap();
+        wait_for(|| first.load(Ordering::SeqCst) > 0);
+
+        let counter = second.clone();
+        manager.set_callback(move |samples| {
+            counter.fetch_add(samples.len(), Ordering::SeqCst);
+        });
+        wait_for(|| second.load(Ordering::SeqCst) > 0);
+        manager.stop_sensor_monitoring();
+    }
+
+    #[test]
+    fn resume_restarts_only_what_pause_stopped() {
+        let mut manager = simulated(5);
+        manager.pause_monitoring();
+        manager.resume_monitoring().unwrap();
+        assert!(!manager.is_monitoring());
+
+        manager.start_sensor_monitoring().unwrap();
+        manager.pause_monitoring();
+        assert!(!manager.is_monitoring());
+        manager.resume_monitoring().unwrap();
+        assert!(manager.is_monitoring());
+    }
+
+    #[test]
+    fn parses_backends() {
+        assert_eq!("Simulator".parse::<SensorBackend>().unwrap(), SensorBackend::Simulator);
+        assert_eq!(
+            "REPLAY:/sdcard/Trip.bin".parse::<SensorBackend>().unwrap(),
+            SensorBackend::Replay(ReplayConfig::new("/sdcard/Trip.bin"))
+        );
+        assert!("bluetooth".parse::<SensorBackend>().is_err());
+    }
+
+    #[test]
+    fn failing_to_open_the_sensors_is_reported() {
+        let mut manager = simulated(5);
+        let missing = std::env::temp_dir().join(format!("mainlogic-{}-missing.jsonl", std::process::id()));
+        manager.set_backend(SensorBackend::Replay(ReplayConfig::new(missing))).unwrap();
+        assert!(manager.start_sensor_monitoring().is_err());
+        assert!(!manager.is_monitoring());
+    }
+
+    #[test]
+    fn stopping_delivers_the_pending_batch() {
+        let mut manager = simulated(5);
+        manager.set_config(SensorConfig { batch_latency_ms: 60_000, batch_size: 100_000, ..SensorConfig::all(5) }).unwrap();
+        let delivered = Arc::new(AtomicUsize::new(0));
+        let counter = delivered.clone();
+        manager.set_callback(move |samples| {
+            counter.fetch_add(samples.len(), Ordering::SeqCst);
+        });
+        manager.start_sensor_monitoring().unwrap();
+        wait_for(|| manager.stats(SensorKind::Gyroscope, None).unwrap().is_some());
+        assert_eq!(delivered.load(Ordering::SeqCst), 0);
+
+        manager.stop_sensor_monitoring();
+        let stats = manager.stats(SensorKind::Gyroscope, None).unwrap().unwrap();
+        assert!(delivered.load(Ordering::SeqCst) >= stats.count);
+    }
+
+    #[test]
+    fn monitoring_fills_the_history() {
+        let mut manager = simulated(5);
+        manager.start_sensor_monitoring().unwrap();
+        wait_for(|| manager.stats(SensorKind::Gyroscope, None).unwrap().is_some_and(|stats| stats.count >= 5));
+        manager.stop_sensor_monitoring();
+
+        let stats = manager.stats(SensorKind::Accelerometer, None).unwrap().unwrap();
+        assert!((stats.z.mean - 9.81).abs() < 0.2, "{:?}", stats);
+        let history = manager.history(SensorKind::Accelerometer, None).unwrap();
+        assert_eq!(history.len(), stats.count * history::FLOATS_PER_SAMPLE);
+    }
+}
@@ -0,0 +1,160 @@
+use ndk_sys as ndk;
+use std::os::raw::c_int;
+use std::ptr;
+use std::time::Duration;
+
+use super::{SensorAccuracy, SensorConfig, SensorEvent, SensorKind, SensorSource};
+use crate::error::{Error, Result};
+use crate::tasks::CancellationToken;
+
+/// Looper ident returned by `ALooper_pollOnce` when the queue has events.
+const LOOPER_ID_SENSORS: c_int = 1;
+/// Events read from the queue per `ASensorEventQueue_getEvents` call.
+const EVENT_BUFFER: usize = 16;
+/// Longest single looper wait, bounding how long a stop request can take
+/// when no sensor events arrive.
+const STOP_POLL: Duration = Duration::from_millis(50);
+
+/// Hardware sensors read through the NDK sensor API.
+///
+/// The event queue is attached to the looper of the thread that opened it,
+/// so an `AndroidSensors` must be opened and polled on the same thread.
+pub struct AndroidSensors {
+    manager: *mut ndk::ASensorManager,
+    queue: *mut ndk::ASensorEventQueue,
+    sensors: Vec<(SensorKind, *const ndk::ASensor)>,
+}
+
+impl SensorKind {
+    fn ndk_type(self) -> c_int {
+        (match self {
+            SensorKind::Accelerometer => ndk::ASENSOR_TYPE_ACCELEROMETER,
+            SensorKind::Gyroscope => ndk::ASENSOR_TYPE_GYROSCOPE,
+            SensorKind::Magnetometer => ndk::ASENSOR_TYPE_MAGNETIC_FIELD,
+        }) as c_int
+    }
+
+    fn from_ndk_type(value: i32) -> Option<Self> {
+        SensorKind::ALL.into_iter().find(|kind| kind.ndk_type() == value)
+    }
+}
+
+impl SensorAccuracy {
+    fn from_ndk_status(status: i8) -> Self {
+        match status as c_int {
+            ndk::ASENSOR_STATUS_ACCURACY_HIGH => SensorAccuracy::High,
+            ndk::ASENSOR_STATUS_ACCURACY_MEDIUM => SensorAccuracy::Medium,
+            ndk::ASENSOR_STATUS_ACCURACY_LOW => SensorAccuracy::Low,
+            _ => SensorAccuracy::Unreliable,
+        }
+    }
+}
+
+impl AndroidSensors {
+    /// Enables the configured sensors at their sampling periods. Fails if
+    /// the device has none of them, e.g. on some emulators.
+    pub fn open(config: &SensorConfig) -> Result<Self> {
+        // SAFETY: plain NDK calls; every returned pointer is checked for
+        // null before use and released in `Drop`
+        unsafe {
+            let looper = ndk::ALooper_prepare(ndk::ALOOPER_PREPARE_ALLOW_NON_CALLBACKS as c_int);
+            // `getInstanceForPackage` needs API 26; we build for platform 23
+            let manager = ndk::ASensorManager_getInstance();
+            if manager.is_null() {
+                return Err(Error::Internal("ASensorManager unavailable".to_string()));
+            }
+            let queue = ndk::ASensorManager_createEventQueue(
+                manager,
+                looper,
+                LOOPER_ID_SENSORS,
+                None,
+                ptr::null_mut(),
+            );
+            if queue.is_null() {
+                return Err(Error::Internal("failed to create sensor event queue".to_string()));
+            }
+
+            let mut source = Self {
+                manager,
+                queue,
+                sensors: Vec::new(),
+            };
+            for (kind, period) in config.enabled() {
+                let sensor = ndk::ASensorManager_getDefaultSensor(manager, kind.ndk_type());
+                if sensor.is_null() {
+                    log::warn!("No {} on this device", kind.name());
+                    continue;
+                }
+                if ndk::ASensorEventQueue_enableSensor(queue, sensor) < 0 {
+                    log::warn!("Failed to enable {}", kind.name());
+                    continue;
+                }
+                let period_us = period.as_micros().min(i32::MAX as u128) as i32;
+                ndk::ASensorEventQueue_setEventRate(queue, sensor, period_us);
+                source.sensors.push((kind, sensor));
+            }
+
+            if source.sensors.is_empty() {
+                return Err(Error::Internal("no supported sensors on this device".to_string()));
+            }
+            Ok(source)
+        }
+    }
+}
+
+impl SensorSource for AndroidSensors {
+    fn name(&self) -> &'static str {
+        "android"
+    }
+
+    fn poll(&mut self, timeout: Duration, stop: &CancellationToken, events: &mut Vec<SensorEvent>) -> Result<()> {
+        if stop.is_cancelled() {
+            return Ok(());
+        }
+        // SAFETY: `queue` is valid until `Drop`, and `ASensorEvent` is plain
+        // old data, so a zeroed buffer is a valid output array
+        unsafe {
+            let timeout_ms = timeout.min(STOP_POLL).as_millis() as c_int;
+            let ident = ndk::ALooper_pollOnce(timeout_ms, ptr::null_mut(), ptr::null_mut(), ptr::null_mut());
+            if ident == ndk::ALOOPER_POLL_ERROR {
+                return Err(Error::Internal("ALooper_pollOnce failed".to_string()));
+            }
+
+            let mut buffer: [ndk::ASensorEvent; EVENT_BUFFER] = std::mem::zeroed();
+            loop {
+                let count = ndk::ASensorEventQueue_getEvents(self.queue, buffer.as_mut_ptr(), EVENT_BUFFER);
+                if count <= 0 {
+                    break;
+                }
+                for event in &buffer[..count as usize] {
+                    let Some(kind) = SensorKind::from_ndk_type(event.type_) else {
+                        continue;
+                    };
+                    let vector = event.__bindgen_anon_1.__bindgen_anon_1.vector;
+                    let [x, y, z] = vector.__bindgen_anon_1.v;
+                    events.push(SensorEvent {
+                        sensor: kind,
+                        x,
+                        y,
+                        z,
+                        accuracy: SensorAccuracy::from_ndk_status(vector.status),
+                        timestamp_ns: event.timestamp as u64,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for AndroidSensors {
+    fn drop(&mut self) {
+        // SAFETY: the pointers came from `open` and are released exactly once
+        unsafe {
+            for (_, sensor) in &self.sensors {
+                ndk::ASensorEventQueue_disableSensor(self.queue, *sensor);
+            }
+            ndk::ASensorManager_destroyEventQueue(self.manager, self.queue);
+        }
+    }
+}
@@ -0,0 +1,511 @@
+//! Stateful per-axis filters applied to sensor readings before they are
+//! delivered.
+//!
+//! Every sensor has its own `FilterPipeline`, a chain of filters built from
+//! `FilterSpec`s. Filters keep state between samples, so a pipeline must see
+//! the readings of one sensor only and in order.
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use super::{SensorEvent, SensorKind};
+use crate::error::{Error, Result};
+
+type Vec3 = [f32; 3];
+
+/// Longest window accepted for moving average and median filters.
+pub const MAX_WINDOW: usize = 256;
+
+/// A stateful filter over 3-axis samples.
+pub trait Filter: Send {
+    /// Filters the next sample.
+    fn process(&mut self, sample: Vec3) -> Vec3;
+
+    /// Forgets all previous samples.
+    fn reset(&mut self);
+}
+
+/// Exponential smoothing: `y += alpha * (x - y)`. Smaller `alpha` smooths
+/// more.
+pub struct LowPass {
+    alpha: f32,
+    state: Option<Vec3>,
+}
+
+impl LowPass {
+    pub fn new(alpha: f32) -> Self {
+        Self { alpha, state: None }
+    }
+}
+
+impl Filter for LowPass {
+    fn process(&mut self, sample: Vec3) -> Vec3 {
+        let state = match self.state {
+            Some(mut y) => {
+                for (y, x) in y.iter_mut().zip(sample) {
+                    *y += self.alpha * (x - *y);
+                }
+                y
+            }
+            None => sample,
+        };
+        self.state = Some(state);
+        state
+    }
+
+    fn reset(&mut self) {
+        self.state = None;
+    }
+}
+
+/// First-order high-pass: `y = alpha * (y + x - x_prev)`. Removes constant
+/// offsets such as gravity; larger `alpha` keeps lower frequencies.
+pub struct HighPass {
+    alpha: f32,
+    /// Previous input and output.
+    state: Option<(Vec3, Vec3)>,
+}
+
+impl HighPass {
+    pub fn new(alpha: f32) -> Self {
+        Self { alpha, state: None }
+    }
+}
+
+impl Filter for HighPass {
+    fn process(&mut self, sample: Vec3) -> Vec3 {
+        let output = match self.state {
+            Some((previous, mut y)) => {
+                for ((y, x), p) in y.iter_mut().zip(sample).zip(previous) {
+                    *y = self.alpha * (*y + x - p);
+                }
+                y
+            }
+            None => [0.0; 3],
+        };
+        self.state = Some((sample, output));
+        output
+    }
+
+    fn reset(&mut self) {
+        self.state = None;
+    }
+}
+
+/// Mean of the last `window` samples.
+pub struct MovingAverage {
+    window: usize,
+    samples: VecDeque<Vec3>,
+    sum: [f64; 3],
+}
+
+impl MovingAverage {
+    pub fn new(window: usize) -> Self {
+        Self {
+            window,
+            samples: VecDeque::with_capacity(window),
+            sum: [0.0; 3],
+        }
+    }
+}
+
+impl Filter for MovingAverage {
+    fn process(&mut self, sample: Vec3) -> Vec3 {
+        if self.samples.len() == self.window {
+            if let Some(oldest) = self.samples.pop_front() {
+                for (sum, x) in self.sum.iter_mut().zip(oldest) {
+                    *sum -= x as f64;
+                }
+            }
+        }
+        self.samples.push_back(sample);
+        for (sum, x) in self.sum.iter_mut().zip(sample) {
+            *sum += x as f64;
+        }
+        let count = self.samples.len() as f64;
+        self.sum.map(|sum| (sum / count) as f32)
+    }
+
+    fn reset(&mut self) {
+        self.samples.clear();
+        self.sum = [0.0; 3];
+    }
+}
+
+/// Median of the last `window` samples, per axis. Drops isolated spikes
+/// without smearing them like an average does.
+pub struct Median {
+    window: usize,
+    samples: VecDeque<Vec3>,
+}
+
+impl Median {
+    pub fn new(window: usize) -> Self {
+        Self {
+            window,
+            samples: VecDeque::with_capacity(window),
+        }
+    }
+}
+
+impl Filter for Median {
+    fn process(&mut self, sample: Vec3) -> Vec3 {
+        if self.samples.len() == self.window {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+
+        let mut output = [0.0; 3];
+        let mut axis = Vec::with_capacity(self.samples.len());
+        for (i, out) in output.iter_mut().enumerate() {
+            axis.clear();
+            axis.extend(self.samples.iter().map(|sample| sample[i]));
+            axis.sort_by(f32::total_cmp);
+            let middle = axis.len() / 2;
+            *out = if axis.len() % 2 == 0 {
+                (axis[middle - 1] + axis[middle]) / 2.0
+            } else {
+                axis[middle]
+            };
+        }
+        output
+    }
+
+    fn reset(&mut self) {
+        self.samples.clear();
+    }
+}
+
+/// One-dimensional Kalman filter per axis for a slowly varying value:
+/// `process_noise` is how much the true value may change between samples,
+/// `measurement_noise` how noisy the readings are (both variances).
+pub struct Kalman {
+    process_noise: f32,
+    measurement_noise: f32,
+    /// Estimate and its variance per axis.
+    state: Option<(Vec3, Vec3)>,
+}
+
+impl Kalman {
+    pub fn new(process_noise: f32, measurement_noise: f32) -> Self {
+        Self {
+            process_noise,
+            measurement_noise,
+            state: None,
+        }
+    }
+}
+
+impl Filter for Kalman {
+    fn process(&mut self, sample: Vec3) -> Vec3 {
+        let (mut estimate, mut variance) = self
+            .state
+            .unwrap_or((sample, [self.measurement_noise; 3]));
+        for i in 0..3 {
+            let predicted = variance[i] + self.process_noise;
+            let gain = predicted / (predicted + self.measurement_noise);
+            estimate[i] += gain * (sample[i] - estimate[i]);
+            variance[i] = (1.0 - gain) * predicted;
+        }
+        self.state = Some((estimate, variance));
+        estimate
+    }
+
+    fn reset(&mut self) {
+        self.state = None;
+    }
+}
+
+/// Serializable description of one filter stage, e.g.
+/// `{"type":"low_pass","alpha":0.2}` or `{"type":"median","window":5}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FilterSpec {
+    LowPass { alpha: f32 },
+    HighPass { alpha: f32 },
+    MovingAverage { window: usize },
+    Median { window: usize },
+    Kalman { process_noise: f32, measurement_noise: f32 },
+}
+
+impl FilterSpec {
+    pub fn validate(&self) -> Result<()> {
+        let unit = |name: &str, alpha: f32| {
+            if alpha > 0.0 && alpha <= 1.0 {
+                Ok(())
+            } else {
+                Err(Error::InvalidArgument(format!("{} alpha must be in (0, 1], got {}", name, alpha)))
+            }
+        };
+        let window = |name: &str, window: usize| {
+            if (1..=MAX_WINDOW).contains(&window) {
+                Ok(())
+            } else {
+                Err(Error::InvalidArgument(format!(
+                    "{} window must be in 1..={}, got {}",
+                    name, MAX_WINDOW, window
+                )))
+            }
+        };
+        match *self {
+            FilterSpec::LowPass { alpha } => unit("low_pass", alpha),
+            FilterSpec::HighPass { alpha } => unit("high_pass", alpha),
+            FilterSpec::MovingAverage { window: w } => window("moving_average", w),
+            FilterSpec::Median { window: w } => window("median", w),
+            FilterSpec::Kalman {
+                process_noise,
+                measurement_noise,
+            } => {
+                if process_noise >= 0.0 && measurement_noise > 0.0 {
+                    Ok(())
+                } else {
+                    Err(Error::InvalidArgument(format!(
+                        "kalman needs process_noise >= 0 and measurement_noise > 0, got {} and {}",
+                        process_noise, measurement_noise
+                    )))
+                }
+            }
+        }
+    }
+
+    pub fn build(&self) -> Result<Box<dyn Filter>> {
+        self.validate()?;
+        Ok(match *self {
+            FilterSpec::LowPass { alpha } => Box::new(LowPass::new(alpha)),
+            FilterSpec::HighPass { alpha } => Box::new(HighPass::new(alpha)),
+            FilterSpec::MovingAverage { window } => Box::new(MovingAverage::new(window)),
+            FilterSpec::Median { window } => Box::new(Median::new(window)),
+            FilterSpec::Kalman {
+                process_noise,
+                measurement_noise,
+            } => Box::new(Kalman::new(process_noise, measurement_noise)),
+        })
+    }
+}
+
+/// Filters applied in order, each to the output of the previous one.
+#[derive(Default)]
+pub struct FilterPipeline {
+    stages: Vec<Box<dyn Filter>>,
+}
+
+impl FilterPipeline {
+    pub fn new(specs: &[FilterSpec]) -> Result<Self> {
+        let stages = specs.iter().map(FilterSpec::build).collect::<Result<_>>()?;
+        Ok(Self { stages })
+    }
+
+    pub fn push(&mut self, filter: impl Filter + 'static) {
+        self.stages.push(Box::new(filter));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+}
+
+impl Filter for FilterPipeline {
+    fn process(&mut self, sample: Vec3) -> Vec3 {
+        self.stages.iter_mut().fold(sample, |sample, stage| stage.process(sample))
+    }
+
+    fn reset(&mut self) {
+        for stage in &mut self.stages {
+            stage.reset();
+        }
+    }
+}
+
+/// Filter stages per sensor. Kotlin passes it to `setSensorFilters` as
+/// JSON; sensors left out are not filtered, e.g.
+/// `{"accelerometer":[{"type":"median","window":5},{"type":"low_pass","alpha":0.2}]}`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilterConfig {
+    pub accelerometer: Vec<FilterSpec>,
+    pub gyroscope: Vec<FilterSpec>,
+    pub magnetometer: Vec<FilterSpec>,
+}
+
+impl FilterConfig {
+    pub fn specs(&self, kind: SensorKind) -> &[FilterSpec] {
+        match kind {
+            SensorKind::Accelerometer => &self.accelerometer,
+            SensorKind::Gyroscope => &self.gyroscope,
+            SensorKind::Magnetometer => &self.magnetometer,
+        }
+    }
+}
+
+/// One `FilterPipeline` per sensor.
+#[derive(Default)]
+pub struct SensorFilters {
+    pipelines: [FilterPipeline; 3],
+}
+
+impl SensorFilters {
+    pub fn new(config: &FilterConfig) -> Result<Self> {
+        let mut filters = Self::default();
+        for kind in SensorKind::ALL {
+            filters.pipelines[kind.index()] = FilterPipeline::new(config.specs(kind))?;
+        }
+        Ok(filters)
+    }
+
+    /// Replaces each event's reading with its filtered value.
+    pub fn apply(&mut self, events: &mut [SensorEvent]) {
+        for event in events {
+            let pipeline = &mut self.pipelines[event.sensor.index()];
+            if pipeline.is_empty() {
+                continue;
+            }
+            [event.x, event.y, event.z] = pipeline.process([event.x, event.y, event.z]);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for pipeline in &mut self.pipelines {
+            pipeline.reset();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensors::simulator::SimulatedSensors;
+    use crate::sensors::SensorConfig;
+    use std::time::Duration;
+
+    /// Two seconds of simulator accelerometer readings at 100 Hz:
+    /// x ≈ 0.3·sin(πt), z ≈ 9.81 + 0.1·sin(2πt), each with ±0.02 noise.
+    fn accelerometer() -> Vec<Vec3> {
+        let mut simulator = SimulatedSensors::new(&SensorConfig::all(10));
+        (0..200)
+            .map(|i| simulator.sample(SensorKind::Accelerometer, Duration::from_millis(i * 10)))
+            .collect()
+    }
+
+    fn run(filter: &mut dyn Filter, input: &[Vec3]) -> Vec<Vec3> {
+        input.iter().map(|sample| filter.process(*sample)).collect()
+    }
+
+    /// Mean absolute difference between consecutive samples of `axis`:
+    /// dominated by noise for a slow signal.
+    fn roughness(samples: &[Vec3], axis: usize) -> f32 {
+        let diffs: Vec<f32> = samples.windows(2).map(|w| (w[1][axis] - w[0][axis]).abs()).collect();
+        diffs.iter().sum::<f32>() / diffs.len() as f32
+    }
+
+    fn mean(samples: &[Vec3], axis: usize) -> f32 {
+        samples.iter().map(|sample| sample[axis]).sum::<f32>() / samples.len() as f32
+    }
+
+    #[test]
+    fn low_pass_smooths_noise_and_keeps_the_signal() {
+        let input = accelerometer();
+        let output = run(&mut LowPass::new(0.2), &input);
+        assert!(roughness(&output, 2) < roughness(&input, 2) / 2.0);
+        assert!((mean(&output, 2) - mean(&input, 2)).abs() < 0.05);
+    }
+
+    #[test]
+    fn high_pass_removes_gravity() {
+        let input = accelerometer();
+        let output = run(&mut HighPass::new(0.8), &input);
+        assert!(mean(&input, 2) > 9.0);
+        assert!(mean(&output[20..], 2).abs() < 0.05, "mean {}", mean(&output[20..], 2));
+    }
+
+    #[test]
+    fn moving_average_matches_window_mean() {
+        let input = accelerometer();
+        let output = run(&mut MovingAverage::new(5), &input);
+        let expected = mean(&input[10..15], 0);
+        assert!((output[14][0] - expected).abs() < 1e-5);
+        assert!(roughness(&output, 2) < roughness(&input, 2));
+    }
+
+    #[test]
+    fn median_drops_spikes() {
+        let mut input = accelerometer();
+        for i in [50, 120, 121] {
+            input[i][2] += 30.0;
+        }
+        let output = run(&mut Median::new(5), &input);
+        assert!(output.iter().all(|sample| (sample[2] - 9.81).abs() < 0.2));
+    }
+
+    #[test]
+    fn kalman_converges_on_a_noisy_constant() {
+        let mut simulator = SimulatedSensors::new(&SensorConfig::all(10));
+        // The gyroscope barely moves within 200 ms: a near constant with noise
+        let input: Vec<Vec3> = (0..20)
+            .map(|i| simulator.sample(SensorKind::Gyroscope, Duration::from_millis(i * 10)))
+            .collect();
+        let output = run(&mut Kalman::new(1e-6, 1e-3), &input);
+        let last = output.last().unwrap();
+        assert!(last.iter().all(|v| v.abs() < 0.04), "estimate {:?}", last);
+        assert!(roughness(&output, 0) < roughness(&input, 0) / 2.0);
+    }
+
+    #[test]
+    fn pipeline_applies_stages_in_order_per_sensor() {
+        let config: FilterConfig = serde_json::from_str(
+            r#"{"accelerometer":[{"type":"median","window":3},{"type":"low_pass","alpha":0.5}]}"#,
+        )
+        .unwrap();
+        let mut filters = SensorFilters::new(&config).unwrap();
+
+        let mut simulator = SimulatedSensors::new(&SensorConfig::all(10));
+        let mut events: Vec<SensorEvent> = Vec::new();
+        for i in 0..30 {
+            let t = Duration::from_millis(i * 10);
+            for kind in [SensorKind::Accelerometer, SensorKind::Gyroscope] {
+                let [x, y, z] = simulator.sample(kind, t);
+                let spike = if kind == SensorKind::Accelerometer && i == 15 { 50.0 } else { 0.0 };
+                events.push(SensorEvent {
+                    sensor: kind,
+                    x,
+                    y,
+                    z: z + spike,
+                    accuracy: crate::sensors::SensorAccuracy::High,
+                    timestamp_ns: i * 10_000_000,
+                });
+            }
+        }
+        let raw = events.clone();
+        filters.apply(&mut events);
+
+        for (filtered, raw) in events.iter().zip(&raw) {
+            match filtered.sensor {
+                SensorKind::Accelerometer => assert!(filtered.z < 10.5),
+                _ => assert_eq!(filtered, raw),
+            }
+        }
+    }
+
+    #[test]
+    fn reset_forgets_state() {
+        let mut filter = LowPass::new(0.1);
+        filter.process([10.0; 3]);
+        filter.reset();
+        assert_eq!(filter.process([1.0; 3]), [1.0; 3]);
+    }
+
+    #[test]
+    fn rejects_invalid_specs() {
+        for spec in [
+            FilterSpec::LowPass { alpha: 0.0 },
+            FilterSpec::HighPass { alpha: 1.5 },
+            FilterSpec::MovingAverage { window: 0 },
+            FilterSpec::Median { window: MAX_WINDOW + 1 },
+            FilterSpec::Kalman {
+                process_noise: 0.1,
+                measurement_noise: 0.0,
+            },
+        ] {
+            assert!(spec.build().is_err(), "{:?}", spec);
+        }
+    }
+}
@@ -0,0 +1,773 @@
+//! Orientation from accelerometer, gyroscope and magnetometer samples.
+//!
+//! Orientations are unit quaternions rotating the sensor frame into an earth
+//! frame with x towards magnetic north, y west and z up, so a device lying
+//! flat with its top pointing north has the identity orientation. Angles are
+//! in radians, gyroscope rates in rad/s; accelerometer and magnetometer
+//! readings only matter by direction, except for linear acceleration which is
+//! reported in the accelerometer's unit (m/s²).
+
+use serde::{Deserialize, Serialize};
+use std::ops::Mul;
+use std::str::FromStr;
+use std::time::Duration;
+
+use super::{SensorEvent, SensorKind};
+use crate::error::{Error, Result};
+
+/// Gravity removed from accelerometer readings for linear acceleration.
+pub const STANDARD_GRAVITY: f32 = 9.80665;
+
+/// Gyroscope gaps longer than this (e.g. after a pause) restart the filter
+/// from the accelerometer and magnetometer instead of integrating.
+const MAX_GYRO_GAP: Duration = Duration::from_secs(1);
+/// Longest `event_period_ms` accepted: a minute.
+const MAX_EVENT_PERIOD_MS: u64 = 60 * 1000;
+
+type Vec3 = [f32; 3];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Quaternion {
+    pub w: f32,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Quaternion {
+    pub const IDENTITY: Self = Self { w: 1.0, x: 0.0, y: 0.0, z: 0.0 };
+
+    pub fn new(w: f32, x: f32, y: f32, z: f32) -> Self {
+        Self { w, x, y, z }
+    }
+
+    /// Rotation by `roll` about x, then `pitch` about y, then `yaw` about z,
+    /// all in the earth frame.
+    pub fn from_euler(euler: EulerAngles) -> Self {
+        let (sr, cr) = (euler.roll / 2.0).sin_cos();
+        let (sp, cp) = (euler.pitch / 2.0).sin_cos();
+        let (sy, cy) = (euler.yaw / 2.0).sin_cos();
+        Self {
+            w: cr * cp * cy + sr * sp * sy,
+            x: sr * cp * cy - cr * sp * sy,
+            y: cr * sp * cy + sr * cp * sy,
+            z: cr * cp * sy - sr * sp * cy,
+        }
+    }
+
+    pub fn to_euler(self) -> EulerAngles {
+        let Self { w, x, y, z } = self;
+        EulerAngles {
+            roll: (2.0 * (w * x + y * z)).atan2(1.0 - 2.0 * (x * x + y * y)),
+            pitch: (2.0 * (w * y - z * x)).clamp(-1.0, 1.0).asin(),
+            yaw: (2.0 * (w * z + x * y)).atan2(1.0 - 2.0 * (y * y + z * z)),
+        }
+    }
+
+    pub fn conjugate(self) -> Self {
+        Self::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    pub fn dot(self, other: Self) -> f32 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn normalized(self) -> Self {
+        let norm = self.dot(self).sqrt();
+        if norm == 0.0 {
+            return Self::IDENTITY;
+        }
+        self.scale(1.0 / norm)
+    }
+
+    fn scale(self, s: f32) -> Self {
+        Self::new(self.w * s, self.x * s, self.y * s, self.z * s)
+    }
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.w + other.w, self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+
+    /// Rotates `v` from the sensor frame into the earth frame.
+    pub fn rotate(self, v: Vec3) -> Vec3 {
+        let p = self * Self::new(0.0, v[0], v[1], v[2]) * self.conjugate();
+        [p.x, p.y, p.z]
+    }
+
+    /// Rotates `v` from the earth frame into the sensor frame.
+    pub fn rotate_inverse(self, v: Vec3) -> Vec3 {
+        self.conjugate().rotate(v)
+    }
+
+    /// Advances the orientation by the body rates `gyro` over `dt` seconds.
+    fn integrate(self, gyro: Vec3, dt: f32) -> Self {
+        self.add(self.derivative(gyro).scale(dt)).normalized()
+    }
+
+    /// `½ q ⊗ (0, ω)`.
+    fn derivative(self, gyro: Vec3) -> Self {
+        (self * Self::new(0.0, gyro[0], gyro[1], gyro[2])).scale(0.5)
+    }
+
+    /// Normalized linear interpolation towards `target` by `t` along the
+    /// shorter arc.
+    fn nlerp(self, target: Self, t: f32) -> Self {
+        let target = if self.dot(target) < 0.0 { target.scale(-1.0) } else { target };
+        self.scale(1.0 - t).add(target.scale(t)).normalized()
+    }
+}
+
+/// Hamilton product `a ⊗ b`.
+impl Mul for Quaternion {
+    type Output = Self;
+
+    fn mul(self, b: Self) -> Self {
+        let a = self;
+        Self {
+            w: a.w * b.w - a.x * b.x - a.y * b.y - a.z * b.z,
+            x: a.w * b.x + a.x * b.w + a.y * b.z - a.z * b.y,
+            y: a.w * b.y - a.x * b.z + a.y * b.w + a.z * b.x,
+            z: a.w * b.z + a.x * b.y - a.y * b.x + a.z * b.w,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EulerAngles {
+    pub roll: f32,
+    pub pitch: f32,
+    /// Heading: 0 is magnetic north, positive turning west.
+    pub yaw: f32,
+}
+
+impl EulerAngles {
+    /// Orientation of a device that reads `accel` at rest: roll and pitch
+    /// from gravity, yaw from the tilt-compensated `mag` or `yaw` when no
+    /// magnetometer reading is available.
+    fn from_reference(accel: Vec3, mag: Option<Vec3>, yaw: f32) -> Self {
+        let [ax, ay, az] = accel;
+        let roll = ay.atan2(az);
+        let pitch = (-ax).atan2((ay * ay + az * az).sqrt());
+        let yaw = match mag {
+            Some([mx, my, mz]) => {
+                let (sr, cr) = roll.sin_cos();
+                let (sp, cp) = pitch.sin_cos();
+                let north = mx * cp + (my * sr + mz * cr) * sp;
+                let west = -(my * cr - mz * sr);
+                west.atan2(north)
+            }
+            None => yaw,
+        };
+        Self { roll, pitch, yaw }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FusionAlgorithm {
+    /// Gyroscope integration pulled towards the accelerometer/magnetometer
+    /// orientation by a fixed weight.
+    Complementary,
+    /// Gradient-descent correction (Madgwick 2010).
+    #[default]
+    Madgwick,
+    /// Proportional-integral correction (Mahony 2008); the integral term
+    /// also estimates gyroscope bias.
+    Mahony,
+}
+
+impl FromStr for FusionAlgorithm {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "complementary" => Ok(FusionAlgorithm::Complementary),
+            "madgwick" => Ok(FusionAlgorithm::Madgwick),
+            "mahony" => Ok(FusionAlgorithm::Mahony),
+            other => Err(Error::InvalidArgument(format!("unknown fusion algorithm: {}", other))),
+        }
+    }
+}
+
+/// Kotlin passes it to `setFusionConfig` as JSON; missing fields keep their
+/// defaults, e.g. `{"algorithm":"mahony","kp":2.0}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FusionConfig {
+    pub algorithm: FusionAlgorithm,
+    /// Correct heading with the magnetometer. Without it yaw is integrated
+    /// from the gyroscope only and drifts.
+    pub use_magnetometer: bool,
+    /// Complementary: weight of the gyroscope estimate per update, in 0..=1.
+    pub alpha: f32,
+    /// Madgwick: gradient step, roughly the expected gyroscope error in rad/s.
+    pub beta: f32,
+    /// Mahony: proportional gain.
+    pub kp: f32,
+    /// Mahony: integral gain; 0 disables bias estimation.
+    pub ki: f32,
+    /// Shortest gap between two `orientation` events; 0 reports every update.
+    pub event_period_ms: u64,
+}
+
+impl Default for FusionConfig {
+    fn default() -> Self {
+        Self {
+            algorithm: FusionAlgorithm::default(),
+            use_magnetometer: true,
+            alpha: 0.98,
+            beta: 0.1,
+            kp: 1.0,
+            ki: 0.0,
+            event_period_ms: 100,
+        }
+    }
+}
+
+impl FusionConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !(0.0..=1.0).contains(&self.alpha) {
+            return Err(Error::InvalidArgument(format!("alpha must be in 0..=1, got {}", self.alpha)));
+        }
+        for (name, gain) in [("beta", self.beta), ("kp", self.kp), ("ki", self.ki)] {
+            if !(gain >= 0.0 && gain.is_finite()) {
+                return Err(Error::InvalidArgument(format!("{} must be a non-negative number, got {}", name, gain)));
+            }
+        }
+        if self.event_period_ms > MAX_EVENT_PERIOD_MS {
+            return Err(Error::InvalidArgument(format!(
+                "event_period_ms must be at most {}, got {}",
+                MAX_EVENT_PERIOD_MS, self.event_period_ms
+            )));
+        }
+        Ok(())
+    }
+
+    fn filter(&self) -> Box<dyn OrientationFilter> {
+        match self.algorithm {
+            FusionAlgorithm::Complementary => Box::new(ComplementaryFilter::new(self.alpha)),
+            FusionAlgorithm::Madgwick => Box::new(MadgwickFilter::new(self.beta)),
+            FusionAlgorithm::Mahony => Box::new(MahonyFilter::new(self.kp, self.ki)),
+        }
+    }
+}
+
+/// An orientation estimator fed one gyroscope step at a time.
+pub trait OrientationFilter: Send {
+    /// Advances by `dt` seconds with body rates `gyro` and the latest
+    /// `accel` and, if used, `mag` readings.
+    fn update(&mut self, gyro: Vec3, accel: Vec3, mag: Option<Vec3>, dt: f32);
+
+    fn orientation(&self) -> Quaternion;
+
+    /// Restarts from `orientation`, forgetting any accumulated state.
+    fn reset(&mut self, orientation: Quaternion);
+}
+
+pub struct ComplementaryFilter {
+    alpha: f32,
+    q: Quaternion,
+}
+
+impl ComplementaryFilter {
+    pub fn new(alpha: f32) -> Self {
+        Self { alpha, q: Quaternion::IDENTITY }
+    }
+}
+
+impl OrientationFilter for ComplementaryFilter {
+    fn update(&mut self, gyro: Vec3, accel: Vec3, mag: Option<Vec3>, dt: f32) {
+        let predicted = self.q.integrate(gyro, dt);
+        if norm(accel) == 0.0 {
+            self.q = predicted;
+            return;
+        }
+        let yaw = predicted.to_euler().yaw;
+        let reference = Quaternion::from_euler(EulerAngles::from_reference(accel, mag, yaw));
+        self.q = predicted.nlerp(reference, 1.0 - self.alpha);
+    }
+
+    fn orientation(&self) -> Quaternion {
+        self.q
+    }
+
+    fn reset(&mut self, orientation: Quaternion) {
+        self.q = orientation;
+    }
+}
+
+pub struct MadgwickFilter {
+    beta: f32,
+    q: Quaternion,
+}
+
+impl MadgwickFilter {
+    pub fn new(beta: f32) -> Self {
+        Self { beta, q: Quaternion::IDENTITY }
+    }
+
+    /// Gradient of the error between the measured and the predicted
+    /// directions of gravity and, with `mag`, the magnetic field.
+    fn gradient(&self, accel: Vec3, mag: Option<Vec3>) -> [f32; 4] {
+        let Quaternion { w: q0, x: q1, y: q2, z: q3 } = self.q;
+        let [ax, ay, az] = accel;
+
+        // Gravity: f = qᶜ ⊗ (0,0,1) ⊗ q - a
+        let f = [
+            2.0 * (q1 * q3 - q0 * q2) - ax,
+            2.0 * (q0 * q1 + q2 * q3) - ay,
+            2.0 * (0.5 - q1 * q1 - q2 * q2) - az,
+        ];
+        let j = [
+            [-2.0 * q2, 2.0 * q3, -2.0 * q0, 2.0 * q1],
+            [2.0 * q1, 2.0 * q0, 2.0 * q3, 2.0 * q2],
+            [0.0, -4.0 * q1, -4.0 * q2, 0.0],
+        ];
+        let mut gradient = transpose_mul(&j, f);
+
+        if let Some(m) = mag {
+            // Earth field in the x-z plane: only inclination is trusted
+            let h = self.q.rotate(m);
+            let (bx, bz) = ((h[0] * h[0] + h[1] * h[1]).sqrt(), h[2]);
+            let [mx, my, mz] = m;
+            let f = [
+                2.0 * bx * (0.5 - q2 * q2 - q3 * q3) + 2.0 * bz * (q1 * q3 - q0 * q2) - mx,
+                2.0 * bx * (q1 * q2 - q0 * q3) + 2.0 * bz * (q0 * q1 + q2 * q3) - my,
+                2.0 * bx * (q0 * q2 + q1 * q3) + 2.0 * bz * (0.5 - q1 * q1 - q2 * q2) - mz,
+            ];
+            let j = [
+                [
+                    -2.0 * bz * q2,
+                    2.0 * bz * q3,
+                    -4.0 * bx * q2 - 2.0 * bz * q0,
+                    -4.0 * bx * q3 + 2.0 * bz * q1,
+                ],
+                [
+                    -2.0 * bx * q3 + 2.0 * bz * q1,
+                    2.0 * bx * q2 + 2.0 * bz * q0,
+                    2.0 * bx * q1 + 2.0 * bz * q3,
+                    -2.0 * bx * q0 + 2.0 * bz * q2,
+                ],
+                [
+                    2.0 * bx * q2,
+                    2.0 * bx * q3 - 4.0 * bz * q1,
+                    2.0 * bx * q0 - 4.0 * bz * q2,
+                    2.0 * bx * q1,
+                ],
+            ];
+            let magnetic = transpose_mul(&j, f);
+            for (g, m) in gradient.iter_mut().zip(magnetic) {
+                *g += m;
+            }
+        }
+        gradient
+    }
+}
+
+impl OrientationFilter for MadgwickFilter {
+    fn update(&mut self, gyro: Vec3, accel: Vec3, mag: Option<Vec3>, dt: f32) {
+        let mut rate = self.q.derivative(gyro);
+        if let Some(accel) = normalize(accel) {
+            let [s0, s1, s2, s3] = self.gradient(accel, mag.and_then(normalize));
+            let step = Quaternion::new(s0, s1, s2, s3);
+            if step.dot(step) > 0.0 {
+                rate = rate.add(step.normalized().scale(-self.beta));
+            }
+        }
+        self.q = self.q.add(rate.scale(dt)).normalized();
+    }
+
+    fn orientation(&self) -> Quaternion {
+        self.q
+    }
+
+    fn reset(&mut self, orientation: Quaternion) {
+        self.q = orientation;
+    }
+}
+
+pub struct MahonyFilter {
+    kp: f32,
+    ki: f32,
+    q: Quaternion,
+    /// Integral of the error, i.e. the estimated gyroscope bias (negated).
+    integral: Vec3,
+}
+
+impl MahonyFilter {
+    pub fn new(kp: f32, ki: f32) -> Self {
+        Self {
+            kp,
+            ki,
+            q: Quaternion::IDENTITY,
+            integral: [0.0; 3],
+        }
+    }
+
+    /// Sum of the cross products between the measured and the predicted
+    /// directions of gravity and, with `mag`, the magnetic field.
+    fn error(&self, accel: Vec3, mag: Option<Vec3>) -> Vec3 {
+        let gravity = self.q.rotate_inverse([0.0, 0.0, 1.0]);
+        let mut error = cross(accel, gravity);
+        if let Some(m) = mag {
+            let h = self.q.rotate(m);
+            let field = [(h[0] * h[0] + h[1] * h[1]).sqrt(), 0.0, h[2]];
+            let predicted = self.q.rotate_inverse(field);
+            error = add(error, cross(m, predicted));
+        }
+        error
+    }
+}
+
+impl OrientationFilter for MahonyFilter {
+    fn update(&mut self, gyro: Vec3, accel: Vec3, mag: Option<Vec3>, dt: f32) {
+        let mut gyro = gyro;
+        if let Some(accel) = normalize(accel) {
+            let error = self.error(accel, mag.and_then(normalize));
+            if self.ki > 0.0 {
+                self.integral = add(self.integral, scale(error, self.ki * dt));
+                gyro = add(gyro, self.integral);
+            } else {
+                self.integral = [0.0; 3];
+            }
+            gyro = add(gyro, scale(error, self.kp));
+        }
+        self.q = self.q.integrate(gyro, dt);
+    }
+
+    fn orientation(&self) -> Quaternion {
+        self.q
+    }
+
+    fn reset(&mut self, orientation: Quaternion) {
+        self.q = orientation;
+        self.integral = [0.0; 3];
+    }
+}
+
+/// One fused estimate, streamed to Kotlin as an `orientation` event.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FusionOutput {
+    pub algorithm: FusionAlgorithm,
+    pub quaternion: Quaternion,
+    pub euler: EulerAngles,
+    /// Accelerometer reading minus gravity, in the sensor frame.
+    pub linear_acceleration: Vec3,
+    /// Timestamp of the gyroscope sample that produced this estimate.
+    pub timestamp_ns: u64,
+}
+
+/// Feeds `SensorEvent`s into the configured `OrientationFilter`.
+///
+/// Accelerometer and magnetometer samples only update the latest readings;
+/// every gyroscope sample advances the filter by the time since the
+/// previous one.
+pub struct SensorFusion {
+    config: FusionConfig,
+    filter: Box<dyn OrientationFilter>,
+    accel: Option<Vec3>,
+    mag: Option<Vec3>,
+    /// Timestamp of the last gyroscope sample; `None` until the filter has
+    /// been initialized from a reference orientation.
+    last_gyro_ns: Option<u64>,
+    /// The filter was initialized before the first magnetometer reading, so
+    /// its heading is a guess.
+    heading_unknown: bool,
+    last_event_ns: Option<u64>,
+    latest: Option<FusionOutput>,
+}
+
+impl SensorFusion {
+    pub fn new(config: FusionConfig) -> Result<Self> {
+        config.validate()?;
+        Ok(Self {
+            filter: config.filter(),
+            config,
+            accel: None,
+            mag: None,
+            last_gyro_ns: None,
+            heading_unknown: false,
+            last_event_ns: None,
+            latest: None,
+        })
+    }
+
+    pub fn config(&self) -> &FusionConfig {
+        &self.config
+    }
+
+    /// Switches to `config`. The new filter continues from the current
+    /// orientation.
+    pub fn set_config(&mut self, config: FusionConfig) -> Result<()> {
+        config.validate()?;
+        let mut filter = config.filter();
+        filter.reset(self.filter.orientation());
+        self.filter = filter;
+        self.config = config;
+        Ok(())
+    }
+
+    /// Forgets all readings; the next gyroscope sample starts over from the
+    /// accelerometer and magnetometer.
+    pub fn reset(&mut self) {
+        self.accel = None;
+        self.mag = None;
+        self.last_gyro_ns = None;
+        self.heading_unknown = false;
+        self.last_event_ns = None;
+        self.latest = None;
+    }
+
+    /// Latest estimate, if any gyroscope sample has been fused yet.
+    pub fn latest(&self) -> Option<FusionOutput> {
+        self.latest
+    }
+
+    /// Fuses `event`. Returns the new estimate when an `orientation` event
+    /// is due according to `event_period_ms`.
+    pub fn update(&mut self, event: &SensorEvent) -> Option<FusionOutput> {
+        let reading = [event.x, event.y, event.z];
+        match event.sensor {
+            SensorKind::Accelerometer => {
+                self.accel = Some(reading);
+                return None;
+            }
+            SensorKind::Magnetometer => {
+                self.mag = Some(reading);
+                if std::mem::take(&mut self.heading_unknown) {
+                    // Re-initialize on the next gyroscope sample
+                    self.last_gyro_ns = None;
+                }
+                return None;
+            }
+            SensorKind::Gyroscope => {}
+        }
+
+        let accel = self.accel?;
+        let mag = if self.config.use_magnetometer { self.mag } else { None };
+        let previous = self.last_gyro_ns.replace(event.timestamp_ns);
+        match previous.map(|previous| event.timestamp_ns.checked_sub(previous)) {
+            Some(Some(elapsed)) if elapsed <= MAX_GYRO_GAP.as_nanos() as u64 => {
+                let dt = elapsed as f32 / 1e9;
+                self.filter.update(reading, accel, mag, dt);
+            }
+            _ => {
+                let yaw = self.filter.orientation().to_euler().yaw;
+                let euler = EulerAngles::from_reference(accel, mag, yaw);
+                self.filter.reset(Quaternion::from_euler(euler));
+                self.heading_unknown = self.config.use_magnetometer && mag.is_none();
+            }
+        }
+
+        let quaternion = self.filter.orientation();
+        let gravity = quaternion.rotate_inverse([0.0, 0.0, STANDARD_GRAVITY]);
+        let output = FusionOutput {
+            algorithm: self.config.algorithm,
+            quaternion,
+            euler: quaternion.to_euler(),
+            linear_acceleration: sub(accel, gravity),
+            timestamp_ns: event.timestamp_ns,
+        };
+        self.latest = Some(output);
+
+        let period_ns = self.config.event_period_ms.saturating_mul(1_000_000);
+        let due = self
+            .last_event_ns
+            .is_none_or(|last| event.timestamp_ns.saturating_sub(last) >= period_ns);
+        if due {
+            self.last_event_ns = Some(event.timestamp_ns);
+            Some(output)
+        } else {
+            None
+        }
+    }
+}
+
+fn norm(v: Vec3) -> f32 {
+    (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt()
+}
+
+fn normalize(v: Vec3) -> Option<Vec3> {
+    let n = norm(v);
+    (n > 0.0).then(|| scale(v, 1.0 / n))
+}
+
+fn scale(v: Vec3, s: f32) -> Vec3 {
+    [v[0] * s, v[1] * s, v[2] * s]
+}
+
+fn add(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: Vec3, b: Vec3) -> Vec3 {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+/// `Jᵀ f` for a 3x4 Jacobian.
+fn transpose_mul(j: &[[f32; 4]; 3], f: Vec3) -> [f32; 4] {
+    let mut out = [0.0; 4];
+    for (row, fi) in j.iter().zip(f) {
+        for (o, jij) in out.iter_mut().zip(row) {
+            *o += jij * fi;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensors::recording::{read_events, RecordingFormat};
+
+    /// Recordings at 50 Hz per sensor, `timestamp_ns,sensor,x,y,z`, with
+    /// Gaussian noise on every axis. The earth field is 50 µT at 60°
+    /// inclination.
+    ///
+    /// - `fusion_static`: still for 3 s at roll 20°, pitch -10°, yaw 60°,
+    ///   with a 0.02 rad/s gyroscope bias on every axis
+    /// - `fusion_yaw_turn`: flat, turning from yaw 0° to 90° in 1 s, then still
+    /// - `fusion_roll_turn`: rolling from 0° to 45° in 1 s, then still
+    const STATIC: &str = include_str!("testdata/fusion_static.csv");
+    const YAW_TURN: &str = include_str!("testdata/fusion_yaw_turn.csv");
+    const ROLL_TURN: &str = include_str!("testdata/fusion_roll_turn.csv");
+
+    const ALGORITHMS: [FusionAlgorithm; 3] =
+        [FusionAlgorithm::Complementary, FusionAlgorithm::Madgwick, FusionAlgorithm::Mahony];
+
+    fn parse(recording: &str) -> Vec<SensorEvent> {
+        read_events(recording.as_bytes(), RecordingFormat::Csv).unwrap()
+    }
+
+    fn replay(algorithm: FusionAlgorithm, recording: &str) -> FusionOutput {
+        let config = FusionConfig {
+            algorithm,
+            ..FusionConfig::default()
+        };
+        let mut fusion = SensorFusion::new(config).unwrap();
+        for event in parse(recording) {
+            fusion.update(&event);
+        }
+        fusion.latest().unwrap()
+    }
+
+    fn assert_close(algorithm: FusionAlgorithm, name: &str, actual: f32, expected_degrees: f32, tolerance: f32) {
+        let actual = actual.to_degrees();
+        assert!(
+            (actual - expected_degrees).abs() <= tolerance,
+            "{:?}: {} is {:.2}°, expected {}° ± {}°",
+            algorithm,
+            name,
+            actual,
+            expected_degrees,
+            tolerance
+        );
+    }
+
+    #[test]
+    fn euler_round_trip() {
+        let euler = EulerAngles { roll: 0.3, pitch: -0.4, yaw: 2.0 };
+        let back = Quaternion::from_euler(euler).to_euler();
+        assert!((back.roll - euler.roll).abs() < 1e-5);
+        assert!((back.pitch - euler.pitch).abs() < 1e-5);
+        assert!((back.yaw - euler.yaw).abs() < 1e-5);
+    }
+
+    #[test]
+    fn rotate_matches_euler_convention() {
+        // Yawing 90° turns the sensor x axis from north to west
+        let q = Quaternion::from_euler(EulerAngles { roll: 0.0, pitch: 0.0, yaw: 90f32.to_radians() });
+        let [x, y, z] = q.rotate([1.0, 0.0, 0.0]);
+        assert!(x.abs() < 1e-6 && (y - 1.0).abs() < 1e-6 && z.abs() < 1e-6);
+    }
+
+    #[test]
+    fn static_recording_holds_attitude_despite_gyro_bias() {
+        for algorithm in ALGORITHMS {
+            let output = replay(algorithm, STATIC);
+            assert_close(algorithm, "roll", output.euler.roll, 20.0, 3.0);
+            assert_close(algorithm, "pitch", output.euler.pitch, -10.0, 3.0);
+            assert_close(algorithm, "yaw", output.euler.yaw, 60.0, 3.0);
+            assert!(
+                norm(output.linear_acceleration) < 0.5,
+                "{:?}: linear acceleration {:?}",
+                algorithm,
+                output.linear_acceleration
+            );
+        }
+    }
+
+    #[test]
+    fn yaw_turn_recording_ends_at_90_degrees() {
+        for algorithm in ALGORITHMS {
+            let output = replay(algorithm, YAW_TURN);
+            assert_close(algorithm, "roll", output.euler.roll, 0.0, 3.0);
+            assert_close(algorithm, "pitch", output.euler.pitch, 0.0, 3.0);
+            assert_close(algorithm, "yaw", output.euler.yaw, 90.0, 3.0);
+        }
+    }
+
+    #[test]
+    fn roll_turn_recording_ends_at_45_degrees() {
+        for algorithm in ALGORITHMS {
+            let output = replay(algorithm, ROLL_TURN);
+            assert_close(algorithm, "roll", output.euler.roll, 45.0, 3.0);
+            assert_close(algorithm, "pitch", output.euler.pitch, 0.0, 3.0);
+            assert_close(algorithm, "yaw", output.euler.yaw, 0.0, 3.0);
+        }
+    }
+
+    #[test]
+    fn gyroscope_alone_tracks_fast_turns() {
+        // Gains of zero leave only gyroscope integration
+        let config = FusionConfig {
+            algorithm: FusionAlgorithm::Mahony,
+            kp: 0.0,
+            ..FusionConfig::default()
+        };
+        let mut fusion = SensorFusion::new(config).unwrap();
+        for event in parse(YAW_TURN) {
+            fusion.update(&event);
+        }
+        assert_close(FusionAlgorithm::Mahony, "yaw", fusion.latest().unwrap().euler.yaw, 90.0, 3.0);
+    }
+
+    #[test]
+    fn events_are_throttled_by_period() {
+        let config = FusionConfig {
+            event_period_ms: 100,
+            ..FusionConfig::default()
+        };
+        let mut fusion = SensorFusion::new(config).unwrap();
+        let emitted = parse(STATIC).iter().filter_map(|event| fusion.update(event)).count();
+        // 3 s of gyroscope samples, one event per 100 ms
+        assert_eq!(emitted, 30);
+    }
+
+    #[test]
+    fn rejects_invalid_configs() {
+        let config = FusionConfig {
+            alpha: 1.5,
+            ..FusionConfig::default()
+        };
+        assert!(SensorFusion::new(config).is_err());
+        let config = FusionConfig {
+            beta: -1.0,
+            ..FusionConfig::default()
+        };
+        assert!(SensorFusion::new(config).is_err());
+        let config = FusionConfig {
+            event_period_ms: u64::MAX,
+            ..FusionConfig::default()
+        };
+        assert!(SensorFusion::new(config).is_err());
+    }
+}
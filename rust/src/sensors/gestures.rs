@@ -0,0 +1,781 @@
+//! Motion events detected from accelerometer and gyroscope samples: shake,
+//! tap and double tap, free fall, tilt past an angle, and steps.
+//!
+//! Every detector is a small state machine fed one sample at a time and
+//! timed by the sample timestamps, so a replayed recording produces the same
+//! events as the live stream did.
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use super::fusion::STANDARD_GRAVITY;
+use super::{SensorEvent, SensorKind};
+use crate::error::{Error, Result};
+
+type Vec3 = [f32; 3];
+
+const NANOS_PER_MS: u64 = 1_000_000;
+/// Longest window, cooldown or interval accepted in a `GestureConfig`: an
+/// hour.
+const MAX_DURATION_MS: u64 = 60 * 60 * 1000;
+
+/// Weight of a new sample in the gravity estimate used to isolate linear
+/// acceleration.
+const GRAVITY_ALPHA: f32 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "gesture", rename_all = "snake_case")]
+pub enum Gesture {
+    /// `peaks` strong movements within the shake window.
+    Shake { peaks: u32 },
+    Tap,
+    /// A second tap soon after a `Tap`; reported instead of a second `Tap`.
+    DoubleTap,
+    /// Reported once the fall has lasted `min_duration_ms`.
+    FreeFall { duration_ms: u64 },
+    /// The screen normal moved past (`tilted`) or back within the angle.
+    Tilt { angle_degrees: f32, tilted: bool },
+    /// `count` steps since the detectors were configured.
+    Step { count: u64 },
+}
+
+/// A detected gesture, sent to Kotlin as a `gesture` event, e.g.
+/// `{"type":"gesture","gesture":"tilt","angle_degrees":47.5,"tilted":true,"timestamp_ns":...}`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GestureEvent {
+    #[serde(flatten)]
+    pub gesture: Gesture,
+    /// Timestamp of the sample that completed the gesture.
+    pub timestamp_ns: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShakeConfig {
+    pub enabled: bool,
+    /// Linear acceleration (gravity removed) that counts as a peak, in m/s².
+    pub threshold: f32,
+    /// Rotation rate that counts as a peak, in rad/s; 0 ignores the gyroscope.
+    pub rotation_threshold: f32,
+    /// Peaks of one sensor needed within `window_ms`.
+    pub min_peaks: u32,
+    pub window_ms: u64,
+    /// Quiet time after a shake before the next can be reported.
+    pub cooldown_ms: u64,
+}
+
+impl Default for ShakeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold: 12.0,
+            rotation_threshold: 8.0,
+            min_peaks: 3,
+            window_ms: 1000,
+            cooldown_ms: 1000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TapConfig {
+    pub enabled: bool,
+    /// Linear acceleration (gravity removed) of a tap, in m/s².
+    pub threshold: f32,
+    /// Longest spike that still counts as a tap; longer ones are movement.
+    pub max_duration_ms: u64,
+    /// Longest gap between the taps of a double tap.
+    pub double_tap_window_ms: u64,
+}
+
+impl Default for TapConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold: 4.0,
+            max_duration_ms: 60,
+            double_tap_window_ms: 400,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FreeFallConfig {
+    pub enabled: bool,
+    /// Acceleration magnitude below which the device is falling, in m/s².
+    pub threshold: f32,
+    pub min_duration_ms: u64,
+}
+
+impl Default for FreeFallConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold: 3.0,
+            min_duration_ms: 150,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TiltConfig {
+    pub enabled: bool,
+    /// Angle between the screen normal and vertical that counts as tilted.
+    pub angle_degrees: f32,
+    /// How far back the angle must go before tilting is reported as over.
+    pub hysteresis_degrees: f32,
+}
+
+impl Default for TiltConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            angle_degrees: 45.0,
+            hysteresis_degrees: 5.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StepConfig {
+    pub enabled: bool,
+    /// Rise of the smoothed acceleration magnitude above its running mean
+    /// that counts as a step, in m/s².
+    pub threshold: f32,
+    /// Shortest time between two steps.
+    pub min_interval_ms: u64,
+}
+
+impl Default for StepConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold: 1.0,
+            min_interval_ms: 250,
+        }
+    }
+}
+
+/// Settings of every detector. Kotlin passes it to `setGestureConfig` as
+/// JSON; missing fields keep their defaults, e.g.
+/// `{"tilt":{"angle_degrees":30},"shake":{"enabled":false}}`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GestureConfig {
+    pub shake: ShakeConfig,
+    pub tap: TapConfig,
+    pub free_fall: FreeFallConfig,
+    pub tilt: TiltConfig,
+    pub steps: StepConfig,
+}
+
+impl GestureConfig {
+    pub fn validate(&self) -> Result<()> {
+        let positive = |name: &str, value: f32| {
+            if value > 0.0 && value.is_finite() {
+                Ok(())
+            } else {
+                Err(Error::InvalidArgument(format!("{} must be positive, got {}", name, value)))
+            }
+        };
+        positive("shake.threshold", self.shake.threshold)?;
+        if !(self.shake.rotation_threshold >= 0.0 && self.shake.rotation_threshold.is_finite()) {
+            return Err(Error::InvalidArgument(format!(
+                "shake.rotation_threshold must not be negative, got {}",
+                self.shake.rotation_threshold
+            )));
+        }
+        if self.shake.min_peaks == 0 {
+            return Err(Error::InvalidArgument("shake.min_peaks must be positive".to_string()));
+        }
+        positive("tap.threshold", self.tap.threshold)?;
+        positive("free_fall.threshold", self.free_fall.threshold)?;
+        if !(0.0..90.0).contains(&self.tilt.angle_degrees)
+            || !(0.0..=self.tilt.angle_degrees).contains(&self.tilt.hysteresis_degrees)
+        {
+            return Err(Error::InvalidArgument(format!(
+                "tilt needs 0 <= hysteresis_degrees <= angle_degrees < 90, got {} and {}",
+                self.tilt.hysteresis_degrees, self.tilt.angle_degrees
+            )));
+        }
+        positive("steps.threshold", self.steps.threshold)?;
+        for (name, value) in [
+            ("shake.window_ms", self.shake.window_ms),
+            ("shake.cooldown_ms", self.shake.cooldown_ms),
+            ("tap.max_duration_ms", self.tap.max_duration_ms),
+            ("tap.double_tap_window_ms", self.tap.double_tap_window_ms),
+            ("free_fall.min_duration_ms", self.free_fall.min_duration_ms),
+            ("steps.min_interval_ms", self.steps.min_interval_ms),
+        ] {
+            if value > MAX_DURATION_MS {
+                return Err(Error::InvalidArgument(format!(
+                    "{} must be at most {}, got {}",
+                    name, MAX_DURATION_MS, value
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Detects one kind of gesture.
+pub trait Detector: Send {
+    /// Feeds one sample, appending any detected gestures to `out`.
+    fn update(&mut self, event: &SensorEvent, out: &mut Vec<GestureEvent>);
+}
+
+/// Low-passed accelerometer reading: what is left once movement averages out.
+#[derive(Default)]
+struct Gravity(Option<Vec3>);
+
+impl Gravity {
+    /// Returns the magnitude of `accel` minus gravity. Readings above
+    /// `threshold` are movement and leave the estimate alone, so a long push
+    /// is not mistaken for a new orientation.
+    fn linear(&mut self, accel: Vec3, threshold: f32) -> f32 {
+        let gravity = self.0.get_or_insert(accel);
+        let linear = norm([accel[0] - gravity[0], accel[1] - gravity[1], accel[2] - gravity[2]]);
+        if linear <= threshold {
+            for (g, a) in gravity.iter_mut().zip(accel) {
+                *g += GRAVITY_ALPHA * (a - *g);
+            }
+        }
+        linear
+    }
+}
+
+/// Counts rising edges of a signal above a threshold within a window.
+struct PeakCounter {
+    threshold: f32,
+    above: bool,
+    peaks: VecDeque<u64>,
+}
+
+impl PeakCounter {
+    fn new(threshold: f32) -> Self {
+        Self {
+            threshold,
+            above: false,
+            peaks: VecDeque::new(),
+        }
+    }
+
+    /// Returns the number of peaks within `window_ns` before `timestamp_ns`.
+    fn update(&mut self, value: f32, timestamp_ns: u64, window_ns: u64) -> usize {
+        let above = value > self.threshold;
+        if above && !self.above {
+            self.peaks.push_back(timestamp_ns);
+        }
+        self.above = above;
+        while self
+            .peaks
+            .front()
+            .is_some_and(|peak| timestamp_ns.saturating_sub(*peak) > window_ns)
+        {
+            self.peaks.pop_front();
+        }
+        self.peaks.len()
+    }
+}
+
+pub struct ShakeDetector {
+    config: ShakeConfig,
+    gravity: Gravity,
+    movement: PeakCounter,
+    rotation: PeakCounter,
+    last_shake_ns: Option<u64>,
+}
+
+impl ShakeDetector {
+    pub fn new(config: ShakeConfig) -> Self {
+        Self {
+            gravity: Gravity::default(),
+            movement: PeakCounter::new(config.threshold),
+            rotation: PeakCounter::new(config.rotation_threshold),
+            last_shake_ns: None,
+            config,
+        }
+    }
+}
+
+impl Detector for ShakeDetector {
+    fn update(&mut self, event: &SensorEvent, out: &mut Vec<GestureEvent>) {
+        let window_ns = self.config.window_ms.saturating_mul(NANOS_PER_MS);
+        let peaks = match event.sensor {
+            SensorKind::Accelerometer => {
+                let linear = self.gravity.linear([event.x, event.y, event.z], self.config.threshold);
+                self.movement.update(linear, event.timestamp_ns, window_ns)
+            }
+            SensorKind::Gyroscope if self.config.rotation_threshold > 0.0 => {
+                self.rotation.update(event.magnitude(), event.timestamp_ns, window_ns)
+            }
+            _ => return,
+        };
+        if peaks < self.config.min_peaks as usize {
+            return;
+        }
+        let cooldown_ns = self.config.cooldown_ms.saturating_mul(NANOS_PER_MS);
+        if self
+            .last_shake_ns
+            .is_some_and(|last| event.timestamp_ns.saturating_sub(last) < cooldown_ns)
+        {
+            return;
+        }
+        self.last_shake_ns = Some(event.timestamp_ns);
+        self.movement.peaks.clear();
+        self.rotation.peaks.clear();
+        out.push(GestureEvent {
+            gesture: Gesture::Shake { peaks: peaks as u32 },
+            timestamp_ns: event.timestamp_ns,
+        });
+    }
+}
+
+pub struct TapDetector {
+    config: TapConfig,
+    gravity: Gravity,
+    spike_start_ns: Option<u64>,
+    /// A tap that may still become the first half of a double tap.
+    last_tap_ns: Option<u64>,
+}
+
+impl TapDetector {
+    pub fn new(config: TapConfig) -> Self {
+        Self {
+            config,
+            gravity: Gravity::default(),
+            spike_start_ns: None,
+            last_tap_ns: None,
+        }
+    }
+}
+
+impl Detector for TapDetector {
+    fn update(&mut self, event: &SensorEvent, out: &mut Vec<GestureEvent>) {
+        if event.sensor != SensorKind::Accelerometer {
+            return;
+        }
+        let linear = self.gravity.linear([event.x, event.y, event.z], self.config.threshold);
+        if linear > self.config.threshold {
+            self.spike_start_ns.get_or_insert(event.timestamp_ns);
+            return;
+        }
+        let Some(start) = self.spike_start_ns.take() else {
+            return;
+        };
+        if event.timestamp_ns.saturating_sub(start) > self.config.max_duration_ms.saturating_mul(NANOS_PER_MS) {
+            return;
+        }
+
+        let window_ns = self.config.double_tap_window_ms.saturating_mul(NANOS_PER_MS);
+        let gesture = match self.last_tap_ns {
+            Some(last) if start.saturating_sub(last) <= window_ns => {
+                self.last_tap_ns = None;
+                Gesture::DoubleTap
+            }
+            _ => {
+                self.last_tap_ns = Some(start);
+                Gesture::Tap
+            }
+        };
+        out.push(GestureEvent {
+            gesture,
+            timestamp_ns: event.timestamp_ns,
+        });
+    }
+}
+
+pub struct FreeFallDetector {
+    config: FreeFallConfig,
+    fall_start_ns: Option<u64>,
+    reported: bool,
+}
+
+impl FreeFallDetector {
+    pub fn new(config: FreeFallConfig) -> Self {
+        Self {
+            config,
+            fall_start_ns: None,
+            reported: false,
+        }
+    }
+}
+
+impl Detector for FreeFallDetector {
+    fn update(&mut self, event: &SensorEvent, out: &mut Vec<GestureEvent>) {
+        if event.sensor != SensorKind::Accelerometer {
+            return;
+        }
+        if event.magnitude() >= self.config.threshold {
+            self.fall_start_ns = None;
+            self.reported = false;
+            return;
+        }
+        let start = *self.fall_start_ns.get_or_insert(event.timestamp_ns);
+        let duration_ms = event.timestamp_ns.saturating_sub(start) / NANOS_PER_MS;
+        if !self.reported && duration_ms >= self.config.min_duration_ms {
+            self.reported = true;
+            out.push(GestureEvent {
+                gesture: Gesture::FreeFall { duration_ms },
+                timestamp_ns: event.timestamp_ns,
+            });
+        }
+    }
+}
+
+pub struct TiltDetector {
+    config: TiltConfig,
+    /// Smoothed accelerometer reading.
+    smoothed: Option<Vec3>,
+    tilted: bool,
+}
+
+impl TiltDetector {
+    /// Weight of a new sample in the smoothed reading.
+    const ALPHA: f32 = 0.2;
+    /// Readings this far from 1 g are movement, not orientation.
+    const MAX_DEVIATION: f32 = 3.0;
+
+    pub fn new(config: TiltConfig) -> Self {
+        Self {
+            config,
+            smoothed: None,
+            tilted: false,
+        }
+    }
+}
+
+impl Detector for TiltDetector {
+    fn update(&mut self, event: &SensorEvent, out: &mut Vec<GestureEvent>) {
+        if event.sensor != SensorKind::Accelerometer {
+            return;
+        }
+        let accel = [event.x, event.y, event.z];
+        let smoothed = self.smoothed.get_or_insert(accel);
+        for (s, a) in smoothed.iter_mut().zip(accel) {
+            *s += Self::ALPHA * (a - *s);
+        }
+        let magnitude = norm(*smoothed);
+        if (magnitude - STANDARD_GRAVITY).abs() > Self::MAX_DEVIATION {
+            return;
+        }
+
+        let angle_degrees = (smoothed[2] / magnitude).clamp(-1.0, 1.0).acos().to_degrees();
+        let tilted = if self.tilted {
+            angle_degrees > self.config.angle_degrees - self.config.hysteresis_degrees
+        } else {
+            angle_degrees > self.config.angle_degrees
+        };
+        if tilted != self.tilted {
+            self.tilted = tilted;
+            out.push(GestureEvent {
+                gesture: Gesture::Tilt { angle_degrees, tilted },
+                timestamp_ns: event.timestamp_ns,
+            });
+        }
+    }
+}
+
+/// Counts peaks of the smoothed acceleration magnitude above its running
+/// mean.
+pub struct StepCounter {
+    config: StepConfig,
+    /// Smoothed magnitude and its slow running mean.
+    levels: Option<(f32, f32)>,
+    /// Set after a step until the magnitude falls back below the mean.
+    in_step: bool,
+    last_step_ns: Option<u64>,
+    count: u64,
+}
+
+impl StepCounter {
+    const SMOOTHING_ALPHA: f32 = 0.3;
+    const MEAN_ALPHA: f32 = 0.02;
+
+    pub fn new(config: StepConfig) -> Self {
+        Self {
+            config,
+            levels: None,
+            in_step: false,
+            last_step_ns: None,
+            count: 0,
+        }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+}
+
+impl Detector for StepCounter {
+    fn update(&mut self, event: &SensorEvent, out: &mut Vec<GestureEvent>) {
+        if event.sensor != SensorKind::Accelerometer {
+            return;
+        }
+        let magnitude = event.magnitude();
+        let (smoothed, mean) = self.levels.get_or_insert((magnitude, magnitude));
+        *smoothed += Self::SMOOTHING_ALPHA * (magnitude - *smoothed);
+        *mean += Self::MEAN_ALPHA * (magnitude - *mean);
+        let (smoothed, mean) = (*smoothed, *mean);
+
+        if self.in_step {
+            if smoothed < mean {
+                self.in_step = false;
+            }
+            return;
+        }
+        if smoothed < mean + self.config.threshold {
+            return;
+        }
+        self.in_step = true;
+        let min_interval_ns = self.config.min_interval_ms.saturating_mul(NANOS_PER_MS);
+        if self
+            .last_step_ns
+            .is_some_and(|last| event.timestamp_ns.saturating_sub(last) < min_interval_ns)
+        {
+            return;
+        }
+        self.last_step_ns = Some(event.timestamp_ns);
+        self.count += 1;
+        out.push(GestureEvent {
+            gesture: Gesture::Step { count: self.count },
+            timestamp_ns: event.timestamp_ns,
+        });
+    }
+}
+
+/// The enabled detectors of a `GestureConfig`.
+pub struct GestureDetectors {
+    config: GestureConfig,
+    detectors: Vec<Box<dyn Detector>>,
+}
+
+impl GestureDetectors {
+    pub fn new(config: GestureConfig) -> Result<Self> {
+        config.validate()?;
+        let mut detectors: Vec<Box<dyn Detector>> = Vec::new();
+        if config.shake.enabled {
+            detectors.push(Box::new(ShakeDetector::new(config.shake.clone())));
+        }
+        if config.tap.enabled {
+            detectors.push(Box::new(TapDetector::new(config.tap.clone())));
+        }
+        if config.free_fall.enabled {
+            detectors.push(Box::new(FreeFallDetector::new(config.free_fall.clone())));
+        }
+        if config.tilt.enabled {
+            detectors.push(Box::new(TiltDetector::new(config.tilt.clone())));
+        }
+        if config.steps.enabled {
+            detectors.push(Box::new(StepCounter::new(config.steps.clone())));
+        }
+        Ok(Self { config, detectors })
+    }
+
+    pub fn config(&self) -> &GestureConfig {
+        &self.config
+    }
+
+    /// Feeds `event` to every detector and returns what they detected.
+    pub fn update(&mut self, event: &SensorEvent) -> Vec<GestureEvent> {
+        let mut out = Vec::new();
+        for detector in &mut self.detectors {
+            detector.update(event, &mut out);
+        }
+        out
+    }
+}
+
+impl Default for GestureDetectors {
+    fn default() -> Self {
+        Self::new(GestureConfig::default()).expect("default gesture config is valid")
+    }
+}
+
+fn norm(v: Vec3) -> f32 {
+    (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensors::simulator::SimulatedSensors;
+    use crate::sensors::{SensorAccuracy, SensorConfig};
+    use std::f32::consts::TAU;
+    use std::time::Duration;
+
+    const PERIOD_MS: u64 = 10;
+
+    /// Accelerometer samples every 10 ms: the simulator's phone lying on a
+    /// table plus `motion(t)` on top.
+    fn accelerometer(seconds: f32, motion: impl Fn(f32) -> Vec3) -> Vec<SensorEvent> {
+        let mut simulator = SimulatedSensors::new(&SensorConfig::all(PERIOD_MS));
+        let count = (seconds * 1000.0) as u64 / PERIOD_MS;
+        (0..count)
+            .map(|i| {
+                let t = Duration::from_millis(i * PERIOD_MS);
+                let [x, y, z] = simulator.sample(SensorKind::Accelerometer, t);
+                let [dx, dy, dz] = motion(t.as_secs_f32());
+                SensorEvent {
+                    sensor: SensorKind::Accelerometer,
+                    x: x + dx,
+                    y: y + dy,
+                    z: z + dz,
+                    accuracy: SensorAccuracy::High,
+                    timestamp_ns: i * PERIOD_MS * NANOS_PER_MS,
+                }
+            })
+            .collect()
+    }
+
+    fn detect(detector: &mut dyn Detector, events: &[SensorEvent]) -> Vec<Gesture> {
+        let mut out = Vec::new();
+        for event in events {
+            detector.update(event, &mut out);
+        }
+        out.into_iter().map(|event| event.gesture).collect()
+    }
+
+    /// A spike of `height` m/s² on z lasting two samples at each of `times`.
+    fn taps(times: &'static [f32], height: f32) -> impl Fn(f32) -> Vec3 {
+        move |t| {
+            let hit = times.iter().any(|start| t >= *start && t < start + 0.015);
+            [0.0, 0.0, if hit { height } else { 0.0 }]
+        }
+    }
+
+    #[test]
+    fn a_phone_at_rest_makes_no_gestures() {
+        let mut detectors = GestureDetectors::default();
+        let events = accelerometer(5.0, |_| [0.0; 3]);
+        let detected: Vec<GestureEvent> = events.iter().flat_map(|event| detectors.update(event)).collect();
+        assert!(detected.is_empty(), "{:?}", detected);
+    }
+
+    #[test]
+    fn shaking_is_reported_once_per_cooldown() {
+        // 5 Hz, 20 m/s² along x for 1.5 s
+        let events = accelerometer(1.5, |t| [20.0 * (TAU * 5.0 * t).sin(), 0.0, 0.0]);
+        let detected = detect(&mut ShakeDetector::new(ShakeConfig::default()), &events);
+        assert_eq!(detected.len(), 2, "{:?}", detected);
+        assert!(matches!(detected[0], Gesture::Shake { peaks: 3 }));
+    }
+
+    #[test]
+    fn fast_rotation_shakes_too() {
+        let events: Vec<SensorEvent> = (0..100)
+            .map(|i| SensorEvent {
+                sensor: SensorKind::Gyroscope,
+                x: 0.0,
+                y: 0.0,
+                z: 10.0 * (TAU * 4.0 * i as f32 / 100.0).sin(),
+                accuracy: SensorAccuracy::High,
+                timestamp_ns: i * PERIOD_MS * NANOS_PER_MS,
+            })
+            .collect();
+        let detected = detect(&mut ShakeDetector::new(ShakeConfig::default()), &events);
+        assert_eq!(detected, vec![Gesture::Shake { peaks: 3 }]);
+    }
+
+    #[test]
+    fn taps_and_double_taps() {
+        let events = accelerometer(3.0, taps(&[0.5, 0.8, 2.0], 8.0));
+        let detected = detect(&mut TapDetector::new(TapConfig::default()), &events);
+        assert_eq!(detected, vec![Gesture::Tap, Gesture::DoubleTap, Gesture::Tap]);
+    }
+
+    #[test]
+    fn long_pushes_are_not_taps() {
+        let events = accelerometer(1.0, |t| [0.0, 0.0, if (0.3..0.5).contains(&t) { 8.0 } else { 0.0 }]);
+        assert!(detect(&mut TapDetector::new(TapConfig::default()), &events).is_empty());
+    }
+
+    #[test]
+    fn free_fall_is_reported_once_after_min_duration() {
+        // Weightless from 0.5 s to 1.0 s
+        let events = accelerometer(1.5, |t| {
+            if (0.5..1.0).contains(&t) {
+                [0.0, 0.0, -9.81]
+            } else {
+                [0.0; 3]
+            }
+        });
+        let detected = detect(&mut FreeFallDetector::new(FreeFallConfig::default()), &events);
+        assert_eq!(detected, vec![Gesture::FreeFall { duration_ms: 150 }]);
+
+        let short = accelerometer(1.0, |t| if (0.5..0.6).contains(&t) { [0.0, 0.0, -9.81] } else { [0.0; 3] });
+        assert!(detect(&mut FreeFallDetector::new(FreeFallConfig::default()), &short).is_empty());
+    }
+
+    #[test]
+    fn tilt_reports_crossing_and_return_with_hysteresis() {
+        // Tip over to 60° and back, rotating gravity about x
+        let angle = |t: f32| -> f32 {
+            let degrees = if t < 1.0 { 60.0 * t } else { 60.0 * (2.0 - t) };
+            degrees.max(0.0).to_radians()
+        };
+        let events = accelerometer(2.0, move |t| {
+            let a = angle(t);
+            [0.0, 9.81 * a.sin(), 9.81 * a.cos() - 9.81]
+        });
+        let detected = detect(&mut TiltDetector::new(TiltConfig::default()), &events);
+        assert_eq!(detected.len(), 2, "{:?}", detected);
+        assert!(matches!(detected[0], Gesture::Tilt { tilted: true, angle_degrees } if (45.0..48.0).contains(&angle_degrees)));
+        assert!(matches!(detected[1], Gesture::Tilt { tilted: false, angle_degrees } if angle_degrees <= 40.0));
+    }
+
+    #[test]
+    fn walking_counts_steps() {
+        // Two steps per second for 5 s
+        let events = accelerometer(5.0, |t| [0.0, 0.0, 3.0 * (TAU * 2.0 * t).sin()]);
+        let mut counter = StepCounter::new(StepConfig::default());
+        detect(&mut counter, &events);
+        assert!((9..=10).contains(&counter.count()), "{} steps", counter.count());
+    }
+
+    #[test]
+    fn disabled_detectors_stay_quiet() {
+        let config: GestureConfig = serde_json::from_str(r#"{"tap":{"enabled":false}}"#).unwrap();
+        let mut detectors = GestureDetectors::new(config).unwrap();
+        let events = accelerometer(1.0, taps(&[0.5, 0.7], 8.0));
+        let detected: Vec<Gesture> = events
+            .iter()
+            .flat_map(|event| detectors.update(event))
+            .map(|event| event.gesture)
+            .collect();
+        assert!(!detected.iter().any(|g| matches!(g, Gesture::Tap | Gesture::DoubleTap)), "{:?}", detected);
+    }
+
+    #[test]
+    fn events_serialize_flat() {
+        let event = GestureEvent {
+            gesture: Gesture::Step { count: 3 },
+            timestamp_ns: 7,
+        };
+        let json = serde_json::to_string(&crate::events::Event::Gesture(event)).unwrap();
+        assert_eq!(json, r#"{"type":"gesture","gesture":"step","count":3,"timestamp_ns":7}"#);
+    }
+
+    #[test]
+    fn rejects_invalid_config() {
+        let mut config = GestureConfig::default();
+        config.tilt.angle_degrees = 95.0;
+        assert!(GestureDetectors::new(config).is_err());
+        let mut config = GestureConfig::default();
+        config.shake.min_peaks = 0;
+        assert!(GestureDetectors::new(config).is_err());
+        let mut config = GestureConfig::default();
+        config.shake.cooldown_ms = u64::MAX;
+        assert!(GestureDetectors::new(config).is_err());
+        let mut config = GestureConfig::default();
+        config.steps.min_interval_ms = MAX_DURATION_MS + 1;
+        assert!(GestureDetectors::new(config).is_err());
+        let mut config = GestureConfig::default();
+        config.tap.double_tap_window_ms = MAX_DURATION_MS;
+        assert!(GestureDetectors::new(config).is_ok());
+    }
+}
@@ -0,0 +1,345 @@
+//! The last few seconds of every sensor, for charts and statistics.
+//!
+//! Each sensor keeps its filtered readings in a ring buffer that drops
+//! samples older than the window, measured back from the newest sample's
+//! timestamp, and never holds more than `MAX_SAMPLES`.
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use super::{SensorEvent, SensorKind};
+use crate::error::{Error, Result};
+
+/// Kept per sensor regardless of the window: 60 s at 200 Hz.
+pub const MAX_SAMPLES: usize = 12_000;
+pub const DEFAULT_WINDOW: Duration = Duration::from_secs(10);
+
+/// Floats per sample in `SensorHistory::to_floats`.
+pub const FLOATS_PER_SAMPLE: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HistorySample {
+    pub timestamp_ns: u64,
+    pub values: [f32; 3],
+}
+
+/// Statistics of one signal over a window.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SignalStats {
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    /// Population standard deviation.
+    pub stddev: f32,
+    pub rms: f32,
+}
+
+#[derive(Default)]
+struct Accumulator {
+    min: f64,
+    max: f64,
+    sum: f64,
+    sum_squares: f64,
+    count: usize,
+}
+
+impl Accumulator {
+    fn add(&mut self, value: f32) {
+        let value = value as f64;
+        if self.count == 0 {
+            self.min = value;
+            self.max = value;
+        } else {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+        self.sum += value;
+        self.sum_squares += value * value;
+        self.count += 1;
+    }
+
+    fn finish(&self) -> SignalStats {
+        let n = self.count as f64;
+        let mean = self.sum / n;
+        let mean_square = self.sum_squares / n;
+        SignalStats {
+            min: self.min as f32,
+            max: self.max as f32,
+            mean: mean as f32,
+            // Clamped: rounding can make the variance slightly negative
+            stddev: (mean_square - mean * mean).max(0.0).sqrt() as f32,
+            rms: mean_square.sqrt() as f32,
+        }
+    }
+}
+
+/// Statistics of a sensor's readings over a window, as returned by
+/// `getSensorStats`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SensorStats {
+    pub sensor: SensorKind,
+    pub count: usize,
+    /// Time between the first and the last sample in the window.
+    pub duration_ms: u64,
+    pub x: SignalStats,
+    pub y: SignalStats,
+    pub z: SignalStats,
+    pub magnitude: SignalStats,
+}
+
+/// Ring buffer of one sensor's recent readings.
+pub struct SensorHistory {
+    sensor: SensorKind,
+    window: Duration,
+    samples: VecDeque<HistorySample>,
+}
+
+impl SensorHistory {
+    pub fn new(sensor: SensorKind, window: Duration) -> Self {
+        Self {
+            sensor,
+            window,
+            samples: VecDeque::new(),
+        }
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Changes how far back samples are kept; shrinking drops the older ones.
+    pub fn set_window(&mut self, window: Duration) {
+        self.window = window;
+        self.evict();
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    pub fn push(&mut self, event: &SensorEvent) {
+        // A restarted source may begin a new clock; keep the buffer in order
+        if self.samples.back().is_some_and(|last| event.timestamp_ns < last.timestamp_ns) {
+            self.samples.clear();
+        }
+        self.samples.push_back(HistorySample {
+            timestamp_ns: event.timestamp_ns,
+            values: [event.x, event.y, event.z],
+        });
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        let window_ns = saturating_nanos(self.window);
+        let Some(newest) = self.samples.back().map(|sample| sample.timestamp_ns) else {
+            return;
+        };
+        while self.samples.len() > MAX_SAMPLES
+            || self
+                .samples
+                .front()
+                .is_some_and(|oldest| newest - oldest.timestamp_ns > window_ns)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Samples of the last `window` before the newest one, oldest first;
+    /// `None` returns everything kept.
+    pub fn samples(&self, window: Option<Duration>) -> impl Iterator<Item = &HistorySample> + '_ {
+        let newest = self.samples.back().map_or(0, |sample| sample.timestamp_ns);
+        let window_ns = window.map_or(u64::MAX, saturating_nanos);
+        let start = self
+            .samples
+            .partition_point(|sample| newest - sample.timestamp_ns > window_ns);
+        self.samples.range(start..)
+    }
+
+    /// Flattens `samples(window)` for a Java `float[]`: for each sample,
+    /// seconds relative to the newest sample (0 or negative), then x, y, z.
+    pub fn to_floats(&self, window: Option<Duration>) -> Vec<f32> {
+        let newest = self.samples.back().map_or(0, |sample| sample.timestamp_ns);
+        self.samples(window)
+            .flat_map(|sample| {
+                let age = (newest - sample.timestamp_ns) as f64 / 1e9;
+                let [x, y, z] = sample.values;
+                [-age as f32, x, y, z]
+            })
+            .collect()
+    }
+
+    /// Statistics over `samples(window)`, `None` if there are none.
+    pub fn stats(&self, window: Option<Duration>) -> Option<SensorStats> {
+        let mut axes: [Accumulator; 4] = Default::default();
+        let mut first_ns = None;
+        let mut last_ns = 0;
+        for sample in self.samples(window) {
+            first_ns.get_or_insert(sample.timestamp_ns);
+            last_ns = sample.timestamp_ns;
+            let [x, y, z] = sample.values;
+            axes[0].add(x);
+            axes[1].add(y);
+            axes[2].add(z);
+            axes[3].add((x * x + y * y + z * z).sqrt());
+        }
+        let first_ns = first_ns?;
+        Some(SensorStats {
+            sensor: self.sensor,
+            count: axes[0].count,
+            duration_ms: (last_ns - first_ns) / 1_000_000,
+            x: axes[0].finish(),
+            y: axes[1].finish(),
+            z: axes[2].finish(),
+            magnitude: axes[3].finish(),
+        })
+    }
+}
+
+/// One `SensorHistory` per sensor.
+pub struct SensorHistories {
+    histories: [SensorHistory; 3],
+}
+
+impl SensorHistories {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            histories: SensorKind::ALL.map(|kind| SensorHistory::new(kind, window)),
+        }
+    }
+
+    pub fn set_window(&mut self, window: Duration) -> Result<()> {
+        if window.is_zero() {
+            return Err(Error::InvalidArgument("history window must be positive".to_string()));
+        }
+        for history in &mut self.histories {
+            history.set_window(window);
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, kind: SensorKind) -> &SensorHistory {
+        &self.histories[kind.index()]
+    }
+
+    pub fn extend(&mut self, events: &[SensorEvent]) {
+        for event in events {
+            self.histories[event.sensor.index()].push(event);
+        }
+    }
+}
+
+impl Default for SensorHistories {
+    fn default() -> Self {
+        Self::new(DEFAULT_WINDOW)
+    }
+}
+
+/// `window` in nanoseconds, saturating where `u64` ends after 584 years.
+fn saturating_nanos(window: Duration) -> u64 {
+    u64::try_from(window.as_nanos()).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensors::SensorAccuracy;
+
+    fn reading(sensor: SensorKind, values: [f32; 3], millis: u64) -> SensorEvent {
+        let [x, y, z] = values;
+        SensorEvent {
+            sensor,
+            x,
+            y,
+            z,
+            accuracy: SensorAccuracy::High,
+            timestamp_ns: millis * 1_000_000,
+        }
+    }
+
+    fn filled(window: Duration, count: u64) -> SensorHistory {
+        let mut history = SensorHistory::new(SensorKind::Accelerometer, window);
+        for i in 0..count {
+            history.push(&reading(SensorKind::Accelerometer, [i as f32, 0.0, 0.0], i * 100));
+        }
+        history
+    }
+
+    #[test]
+    fn drops_samples_older_than_the_window() {
+        // 0..=3000 ms, keeping 1 s
+        let history = filled(Duration::from_secs(1), 31);
+        assert_eq!(history.len(), 11);
+        assert_eq!(history.samples(None).next().unwrap().timestamp_ns, 2_000_000_000);
+    }
+
+    #[test]
+    fn statistics_over_the_window() {
+        let mut history = SensorHistory::new(SensorKind::Gyroscope, DEFAULT_WINDOW);
+        for (i, x) in [1.0, -1.0, 1.0, -1.0, 3.0].into_iter().enumerate() {
+            history.push(&reading(SensorKind::Gyroscope, [x, 2.0, 0.0], i as u64 * 10));
+        }
+
+        let stats = history.stats(None).unwrap();
+        assert_eq!((stats.count, stats.duration_ms), (5, 40));
+        assert_eq!((stats.x.min, stats.x.max), (-1.0, 3.0));
+        assert!((stats.x.mean - 0.6).abs() < 1e-6);
+        assert!((stats.x.rms - 13f32.sqrt() / 5f32.sqrt()).abs() < 1e-6);
+        assert!((stats.x.stddev - (2.6f32 - 0.36).sqrt()).abs() < 1e-6);
+        assert_eq!(stats.y.stddev, 0.0);
+        assert_eq!(stats.y.rms, 2.0);
+
+        // Only the last two samples
+        let recent = history.stats(Some(Duration::from_millis(10))).unwrap();
+        assert_eq!(recent.count, 2);
+        assert_eq!(recent.x.mean, 1.0);
+        assert!(SensorHistory::new(SensorKind::Gyroscope, DEFAULT_WINDOW).stats(None).is_none());
+    }
+
+    #[test]
+    fn floats_are_relative_seconds_and_axes() {
+        let history = filled(Duration::from_secs(1), 5);
+        let floats = history.to_floats(Some(Duration::from_millis(100)));
+        assert_eq!(floats, vec![-0.1, 3.0, 0.0, 0.0, 0.0, 4.0, 0.0, 0.0]);
+        assert_eq!(history.to_floats(None).len(), 5 * FLOATS_PER_SAMPLE);
+    }
+
+    #[test]
+    fn shrinking_the_window_drops_samples() {
+        let mut histories = SensorHistories::default();
+        let events: Vec<SensorEvent> = (0..50)
+            .map(|i| reading(SensorKind::Magnetometer, [0.0, 0.0, 40.0], i * 100))
+            .collect();
+        histories.extend(&events);
+        assert_eq!(histories.get(SensorKind::Magnetometer).len(), 50);
+        assert!(histories.get(SensorKind::Accelerometer).is_empty());
+
+        histories.set_window(Duration::from_secs(2)).unwrap();
+        assert_eq!(histories.get(SensorKind::Magnetometer).len(), 21);
+        assert!(histories.set_window(Duration::ZERO).is_err());
+    }
+
+    #[test]
+    fn huge_windows_keep_everything() {
+        // About 584 years, past what u64 nanoseconds hold
+        let window = Duration::from_millis(18_446_744_073_710);
+        let history = filled(window, 10);
+        assert_eq!(history.len(), 10);
+        assert_eq!(history.samples(Some(window)).count(), 10);
+    }
+
+    #[test]
+    fn a_clock_going_backwards_starts_over() {
+        let mut history = filled(DEFAULT_WINDOW, 10);
+        history.push(&reading(SensorKind::Accelerometer, [7.0, 0.0, 0.0], 5));
+        assert_eq!(history.len(), 1);
+    }
+}
@@ -0,0 +1,525 @@
+//! Recording sensor streams to files and playing them back.
+//!
+//! Three formats are supported, all holding `SensorEvent`s in delivery
+//! order with their original timestamps:
+//!
+//! - binary (`.bin`): the magic `MLSR`, a little-endian `u16` version, then
+//!   22-byte records: `u64` timestamp_ns, `u8` sensor, `u8` accuracy and
+//!   three `f32` axes, all little-endian
+//! - CSV (`.csv`): a `timestamp_ns,sensor,x,y,z,accuracy` header, then one
+//!   sample per line; the accuracy column may be left out and reads as high
+//! - JSON Lines (`.jsonl`): one `SensorEvent` object per line, with the
+//!   fields of a `sensor_sample` event
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use super::{SensorAccuracy, SensorConfig, SensorEvent, SensorKind, SensorSource};
+use crate::error::{Error, Result};
+use crate::tasks::CancellationToken;
+
+const MAGIC: &[u8; 4] = b"MLSR";
+const VERSION: u16 = 1;
+const RECORD_LEN: usize = 22;
+const CSV_HEADER: &str = "timestamp_ns,sensor,x,y,z,accuracy";
+
+/// Most events an unthrottled replay returns from one `poll`.
+const UNTHROTTLED_CHUNK: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordingFormat {
+    Binary,
+    Csv,
+    JsonLines,
+}
+
+impl FromStr for RecordingFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "binary" | "bin" => Ok(RecordingFormat::Binary),
+            "csv" => Ok(RecordingFormat::Csv),
+            "jsonl" | "json_lines" | "ndjson" => Ok(RecordingFormat::JsonLines),
+            other => Err(Error::InvalidArgument(format!("unknown recording format: {}", other))),
+        }
+    }
+}
+
+impl RecordingFormat {
+    /// Format implied by the extension of `path`, e.g. `trip.jsonl`.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or("");
+        extension.parse().map_err(|_| {
+            Error::InvalidArgument(format!("cannot tell the recording format of {}", path.display()))
+        })
+    }
+}
+
+impl SensorAccuracy {
+    fn to_byte(self) -> u8 {
+        match self {
+            SensorAccuracy::Unreliable => 0,
+            SensorAccuracy::Low => 1,
+            SensorAccuracy::Medium => 2,
+            SensorAccuracy::High => 3,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(SensorAccuracy::Unreliable),
+            1 => Some(SensorAccuracy::Low),
+            2 => Some(SensorAccuracy::Medium),
+            3 => Some(SensorAccuracy::High),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            SensorAccuracy::Unreliable => "unreliable",
+            SensorAccuracy::Low => "low",
+            SensorAccuracy::Medium => "medium",
+            SensorAccuracy::High => "high",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        [
+            SensorAccuracy::Unreliable,
+            SensorAccuracy::Low,
+            SensorAccuracy::Medium,
+            SensorAccuracy::High,
+        ]
+        .into_iter()
+        .find(|accuracy| accuracy.name() == name)
+    }
+}
+
+/// Appends `SensorEvent`s to `W` in one `RecordingFormat`.
+pub struct RecordingWriter<W: Write> {
+    writer: W,
+    format: RecordingFormat,
+    count: u64,
+}
+
+impl RecordingWriter<BufWriter<File>> {
+    /// Creates (or truncates) the file at `path`.
+    pub fn create(path: &Path, format: RecordingFormat) -> Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), format)
+    }
+}
+
+impl<W: Write> RecordingWriter<W> {
+    /// Writes the format's header.
+    pub fn new(mut writer: W, format: RecordingFormat) -> Result<Self> {
+        match format {
+            RecordingFormat::Binary => {
+                writer.write_all(MAGIC)?;
+                writer.write_all(&VERSION.to_le_bytes())?;
+            }
+            RecordingFormat::Csv => writeln!(writer, "{}", CSV_HEADER)?,
+            RecordingFormat::JsonLines => {}
+        }
+        Ok(Self {
+            writer,
+            format,
+            count: 0,
+        })
+    }
+
+    pub fn write(&mut self, events: &[SensorEvent]) -> Result<()> {
+        for event in events {
+            match self.format {
+                RecordingFormat::Binary => {
+                    let mut record = [0u8; RECORD_LEN];
+                    record[..8].copy_from_slice(&event.timestamp_ns.to_le_bytes());
+                    record[8] = event.sensor.index() as u8;
+                    record[9] = event.accuracy.to_byte();
+                    for (i, value) in [event.x, event.y, event.z].into_iter().enumerate() {
+                        record[10 + 4 * i..14 + 4 * i].copy_from_slice(&value.to_le_bytes());
+                    }
+                    self.writer.write_all(&record)?;
+                }
+                RecordingFormat::Csv => writeln!(
+                    self.writer,
+                    "{},{},{},{},{},{}",
+                    event.timestamp_ns,
+                    event.sensor.as_str(),
+                    event.x,
+                    event.y,
+                    event.z,
+                    event.accuracy.name()
+                )?,
+                RecordingFormat::JsonLines => {
+                    serde_json::to_writer(&mut self.writer, event)
+                        .map_err(|e| Error::Internal(format!("failed to encode sensor event: {}", e)))?;
+                    self.writer.write_all(b"\n")?;
+                }
+            }
+        }
+        self.count += events.len() as u64;
+        Ok(())
+    }
+
+    /// Events written so far.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Flushes and returns the writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Reads a whole recording.
+pub fn read_events(reader: impl Read, format: RecordingFormat) -> Result<Vec<SensorEvent>> {
+    let mut reader = BufReader::new(reader);
+    match format {
+        RecordingFormat::Binary => read_binary(&mut reader),
+        RecordingFormat::Csv => read_lines(reader, |line| {
+            if line.starts_with("timestamp_ns") {
+                return Ok(None);
+            }
+            parse_csv(line).map(Some)
+        }),
+        RecordingFormat::JsonLines => read_lines(reader, |line| {
+            serde_json::from_str(line)
+                .map(Some)
+                .map_err(|e| Error::InvalidArgument(format!("invalid sensor event: {}", e)))
+        }),
+    }
+}
+
+/// Reads the recording at `path`, in the format implied by its extension.
+pub fn load(path: &Path) -> Result<Vec<SensorEvent>> {
+    let format = RecordingFormat::from_path(path)?;
+    read_events(File::open(path)?, format)
+}
+
+fn read_binary(reader: &mut impl Read) -> Result<Vec<SensorEvent>> {
+    let mut header = [0u8; 6];
+    reader.read_exact(&mut header)?;
+    if &header[..4] != MAGIC {
+        return Err(Error::InvalidArgument("not a binary sensor recording".to_string()));
+    }
+    let version = u16::from_le_bytes([header[4], header[5]]);
+    if version != VERSION {
+        return Err(Error::InvalidArgument(format!("unsupported recording version {}", version)));
+    }
+
+    let mut events = Vec::new();
+    let mut record = [0u8; RECORD_LEN];
+    loop {
+        match read_record(reader, &mut record)? {
+            0 => break,
+            RECORD_LEN => {}
+            partial => {
+                return Err(Error::InvalidArgument(format!(
+                    "sensor recording ends in a truncated record of {} bytes after {} records",
+                    partial,
+                    events.len()
+                )))
+            }
+        }
+        let invalid = || Error::InvalidArgument(format!("corrupt record {} in sensor recording", events.len()));
+        let sensor = *SensorKind::ALL.get(record[8] as usize).ok_or_else(invalid)?;
+        let accuracy = SensorAccuracy::from_byte(record[9]).ok_or_else(invalid)?;
+        let axis = |i: usize| f32::from_le_bytes(record[10 + 4 * i..14 + 4 * i].try_into().unwrap());
+        events.push(SensorEvent {
+            sensor,
+            x: axis(0),
+            y: axis(1),
+            z: axis(2),
+            accuracy,
+            timestamp_ns: u64::from_le_bytes(record[..8].try_into().unwrap()),
+        });
+    }
+    Ok(events)
+}
+
+/// Fills `record` unless the input ends first, returning the bytes read.
+fn read_record(reader: &mut impl Read, record: &mut [u8; RECORD_LEN]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < RECORD_LEN {
+        match reader.read(&mut record[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(filled)
+}
+
+fn read_lines(
+    reader: impl BufRead,
+    mut parse: impl FnMut(&str) -> Result<Option<SensorEvent>>,
+) -> Result<Vec<SensorEvent>> {
+    let mut events = Vec::new();
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        match parse(line) {
+            Ok(Some(event)) => events.push(event),
+            Ok(None) => {}
+            Err(e) => return Err(Error::InvalidArgument(format!("line {}: {}", number + 1, e))),
+        }
+    }
+    Ok(events)
+}
+
+fn parse_csv(line: &str) -> Result<SensorEvent> {
+    let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+    if !(5..=6).contains(&fields.len()) {
+        return Err(Error::InvalidArgument(format!("expected 5 or 6 fields, got {}", fields.len())));
+    }
+    let number = |i: usize| {
+        fields[i]
+            .parse::<f32>()
+            .map_err(|e| Error::InvalidArgument(format!("invalid value {:?}: {}", fields[i], e)))
+    };
+    let accuracy = match fields.get(5) {
+        Some(name) => SensorAccuracy::from_name(name)
+            .ok_or_else(|| Error::InvalidArgument(format!("unknown accuracy: {}", name)))?,
+        None => SensorAccuracy::High,
+    };
+    Ok(SensorEvent {
+        sensor: fields[1].parse()?,
+        x: number(2)?,
+        y: number(3)?,
+        z: number(4)?,
+        accuracy,
+        timestamp_ns: fields[0]
+            .parse()
+            .map_err(|e| Error::InvalidArgument(format!("invalid timestamp {:?}: {}", fields[0], e)))?,
+    })
+}
+
+/// Where `SensorBackend::Replay` reads from, and how fast.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayConfig {
+    pub path: PathBuf,
+    /// Playback speed: 1 is real time, 4 four times faster. 0 delivers the
+    /// samples as fast as they are polled.
+    pub speed: f32,
+}
+
+impl ReplayConfig {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            speed: 1.0,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.speed >= 0.0 && self.speed.is_finite() {
+            Ok(())
+        } else {
+            Err(Error::InvalidArgument(format!("replay speed must be >= 0, got {}", self.speed)))
+        }
+    }
+}
+
+/// Plays a recording back, keeping the gaps between sample timestamps
+/// (divided by `speed`). Samples keep their recorded timestamps, so fusion
+/// and rules see exactly what was captured. The source goes quiet at the end
+/// of the recording.
+pub struct ReplaySensorSource {
+    events: Vec<SensorEvent>,
+    next: usize,
+    speed: f32,
+    /// Set on the first poll, so loading time does not count.
+    started: Option<Instant>,
+}
+
+impl ReplaySensorSource {
+    pub fn new(events: Vec<SensorEvent>, speed: f32) -> Self {
+        Self {
+            events,
+            next: 0,
+            speed,
+            started: None,
+        }
+    }
+
+    /// Loads `config.path`, keeping only the sensors enabled in `sensors`.
+    pub fn open(config: &ReplayConfig, sensors: &SensorConfig) -> Result<Self> {
+        config.validate()?;
+        let mut events = load(&config.path)?;
+        events.retain(|event| sensors.settings(event.sensor).enabled);
+        log::info!("Replaying {} samples from {}", events.len(), config.path.display());
+        Ok(Self::new(events, config.speed))
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.events.len()
+    }
+
+    /// Time after the start at which the event at `index` is due.
+    fn due(&self, index: usize) -> Duration {
+        let first = self.events[0].timestamp_ns;
+        let offset = self.events[index].timestamp_ns.saturating_sub(first);
+        Duration::from_nanos(offset).div_f32(self.speed)
+    }
+}
+
+impl SensorSource for ReplaySensorSource {
+    fn name(&self) -> &'static str {
+        "replay"
+    }
+
+    fn poll(&mut self, timeout: Duration, stop: &CancellationToken, events: &mut Vec<SensorEvent>) -> Result<()> {
+        if self.is_finished() {
+            stop.sleep(timeout);
+            return Ok(());
+        }
+        if self.speed == 0.0 {
+            let end = (self.next + UNTHROTTLED_CHUNK).min(self.events.len());
+            events.extend_from_slice(&self.events[self.next..end]);
+            self.next = end;
+            return Ok(());
+        }
+
+        let started = *self.started.get_or_insert_with(Instant::now);
+        let due = self.due(self.next);
+        let elapsed = started.elapsed();
+        if elapsed < due && (!stop.sleep((due - elapsed).min(timeout)) || started.elapsed() < due) {
+            return Ok(());
+        }
+
+        let elapsed = started.elapsed();
+        while !self.is_finished() && self.due(self.next) <= elapsed {
+            events.push(self.events[self.next]);
+            self.next += 1;
+        }
+        if self.is_finished() {
+            log::info!("Replay finished after {} samples", self.events.len());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensors::fusion::{FusionConfig, SensorFusion};
+    use crate::sensors::rules::{Condition, RuleEngine, RuleSpec, Signal};
+
+    const YAW_TURN: &str = include_str!("testdata/fusion_yaw_turn.csv");
+
+    fn events() -> Vec<SensorEvent> {
+        read_events(YAW_TURN.as_bytes(), RecordingFormat::Csv).unwrap()
+    }
+
+    fn drain(source: &mut ReplaySensorSource) -> Vec<SensorEvent> {
+        let stop = CancellationToken::default();
+        let mut events = Vec::new();
+        while !source.is_finished() {
+            source.poll(Duration::from_millis(50), &stop, &mut events).unwrap();
+        }
+        events
+    }
+
+    #[test]
+    fn every_format_round_trips() {
+        let mut events = events();
+        events[1].accuracy = SensorAccuracy::Low;
+        for format in [RecordingFormat::Binary, RecordingFormat::Csv, RecordingFormat::JsonLines] {
+            let mut writer = RecordingWriter::new(Vec::new(), format).unwrap();
+            writer.write(&events[..10]).unwrap();
+            writer.write(&events[10..]).unwrap();
+            assert_eq!(writer.count(), events.len() as u64);
+            let bytes = writer.finish().unwrap();
+            assert_eq!(read_events(bytes.as_slice(), format).unwrap(), events, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn binary_records_are_compact() {
+        let events = events();
+        let mut writer = RecordingWriter::new(Vec::new(), RecordingFormat::Binary).unwrap();
+        writer.write(&events).unwrap();
+        let bytes = writer.finish().unwrap();
+        assert_eq!(bytes.len(), 6 + RECORD_LEN * events.len());
+    }
+
+    #[test]
+    fn rejects_corrupt_recordings() {
+        assert!(read_events(&b"NOPE\x01\x00"[..], RecordingFormat::Binary).is_err());
+        let mut bytes = RecordingWriter::new(Vec::new(), RecordingFormat::Binary)
+            .unwrap()
+            .finish()
+            .unwrap();
+        bytes.extend_from_slice(&[0u8; 8]);
+        bytes.extend_from_slice(&[7u8; RECORD_LEN - 8]);
+        assert!(read_events(bytes.as_slice(), RecordingFormat::Binary).is_err());
+        let mut writer = RecordingWriter::new(Vec::new(), RecordingFormat::Binary).unwrap();
+        writer.write(&events()[..2]).unwrap();
+        let mut bytes = writer.finish().unwrap();
+        bytes.pop();
+        assert!(read_events(bytes.as_slice(), RecordingFormat::Binary).is_err());
+        assert!(read_events(&b"1,gyroscope,0,0"[..], RecordingFormat::Csv).is_err());
+    }
+
+    #[test]
+    fn format_follows_extension() {
+        assert_eq!(RecordingFormat::from_path(Path::new("a/trip.jsonl")).unwrap(), RecordingFormat::JsonLines);
+        assert_eq!(RecordingFormat::from_path(Path::new("trip.BIN")).unwrap(), RecordingFormat::Binary);
+        assert!(RecordingFormat::from_path(Path::new("trip.txt")).is_err());
+    }
+
+    #[test]
+    fn accelerated_replay_keeps_pacing() {
+        // 2 s of samples at 40x take about 50 ms
+        let mut source = ReplaySensorSource::new(events(), 40.0);
+        let started = Instant::now();
+        let replayed = drain(&mut source);
+        let elapsed = started.elapsed();
+        assert_eq!(replayed, events());
+        assert!(elapsed >= Duration::from_millis(45), "took {:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(500), "took {:?}", elapsed);
+    }
+
+    #[test]
+    fn replay_drives_fusion_and_rules_deterministically() {
+        let run = || {
+            let mut source = ReplaySensorSource::new(events(), 0.0);
+            let mut fusion = SensorFusion::new(FusionConfig::default()).unwrap();
+            let mut rules = RuleEngine::new();
+            rules
+                .add(RuleSpec {
+                    sensor: SensorKind::Gyroscope,
+                    signal: Signal::Z,
+                    condition: Condition::Above,
+                    threshold: 1.0,
+                    hysteresis: 0.5,
+                    cooldown_ms: 0,
+                })
+                .unwrap();
+
+            let mut alerts = Vec::new();
+            for event in drain(&mut source) {
+                fusion.update(&event);
+                alerts.extend(rules.evaluate(&event).into_iter().map(|alert| alert.timestamp_ns));
+            }
+            (fusion.latest().unwrap(), alerts)
+        };
+
+        let (orientation, alerts) = run();
+        // The turn at 90°/s starts right away and stops after a second
+        assert_eq!(alerts, vec![1_000_000_000]);
+        assert!((orientation.euler.yaw.to_degrees() - 90.0).abs() < 3.0);
+        assert_eq!(run(), (orientation, alerts));
+    }
+}
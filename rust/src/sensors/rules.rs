@@ -0,0 +1,365 @@
+//! Threshold rules over sensor readings.
+//!
+//! A rule watches one value of one sensor (the magnitude or a single axis)
+//! and fires when it goes above or below a threshold, or changes faster than
+//! a threshold per second. After firing, a rule stays quiet until the value
+//! has moved back by `hysteresis` past the threshold and `cooldown_ms` has
+//! passed, so a value hovering around the threshold alerts once.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::{SensorEvent, SensorKind};
+use crate::error::{Error, Result};
+
+/// Longest `cooldown_ms` accepted: a day.
+const MAX_COOLDOWN_MS: u64 = 24 * 60 * 60 * 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RuleId(pub u64);
+
+/// The value of a reading a rule looks at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Signal {
+    #[default]
+    Magnitude,
+    X,
+    Y,
+    Z,
+}
+
+impl Signal {
+    pub fn value(self, event: &SensorEvent) -> f32 {
+        match self {
+            Signal::Magnitude => event.magnitude(),
+            Signal::X => event.x,
+            Signal::Y => event.y,
+            Signal::Z => event.z,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Signal::Magnitude => "magnitude",
+            Signal::X => "x",
+            Signal::Y => "y",
+            Signal::Z => "z",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    Above,
+    Below,
+    /// Absolute change per second above the threshold.
+    RateOfChange,
+}
+
+/// A rule as registered from Kotlin, e.g.
+/// `{"sensor":"accelerometer","condition":"above","threshold":15.0,"hysteresis":1.0,"cooldown_ms":5000}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleSpec {
+    pub sensor: SensorKind,
+    #[serde(default)]
+    pub signal: Signal,
+    pub condition: Condition,
+    pub threshold: f32,
+    /// How far the value must move back past the threshold before the rule
+    /// can fire again.
+    #[serde(default)]
+    pub hysteresis: f32,
+    /// Shortest time between two alerts of this rule, by sample timestamps.
+    #[serde(default)]
+    pub cooldown_ms: u64,
+}
+
+impl RuleSpec {
+    pub fn validate(&self) -> Result<()> {
+        if !self.threshold.is_finite() {
+            return Err(Error::InvalidArgument(format!("threshold must be finite, got {}", self.threshold)));
+        }
+        if !(self.hysteresis >= 0.0 && self.hysteresis.is_finite()) {
+            return Err(Error::InvalidArgument(format!(
+                "hysteresis must be a non-negative number, got {}",
+                self.hysteresis
+            )));
+        }
+        if self.condition == Condition::RateOfChange && self.threshold < 0.0 {
+            return Err(Error::InvalidArgument(format!(
+                "rate of change threshold must be non-negative, got {}",
+                self.threshold
+            )));
+        }
+        if self.cooldown_ms > MAX_COOLDOWN_MS {
+            return Err(Error::InvalidArgument(format!(
+                "cooldown_ms must be at most {}, got {}",
+                MAX_COOLDOWN_MS, self.cooldown_ms
+            )));
+        }
+        Ok(())
+    }
+
+    /// Shown as the sensor name in alerts, e.g. `Accelerometer magnitude`.
+    pub fn describe(&self) -> String {
+        let rate = if self.condition == Condition::RateOfChange { " rate" } else { "" };
+        format!("{} {}{}", self.sensor.name(), self.signal.name(), rate)
+    }
+
+    /// Whether `value` meets the condition, and whether it is far enough
+    /// back to re-arm the rule.
+    fn check(&self, value: f32) -> (bool, bool) {
+        match self.condition {
+            Condition::Above | Condition::RateOfChange => {
+                (value > self.threshold, value < self.threshold - self.hysteresis)
+            }
+            Condition::Below => (value < self.threshold, value > self.threshold + self.hysteresis),
+        }
+    }
+}
+
+/// A registered rule, as returned by `RuleEngine::list`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RuleInfo {
+    pub id: RuleId,
+    #[serde(flatten)]
+    pub spec: RuleSpec,
+}
+
+/// A rule fired on a sample.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleAlert {
+    pub rule: RuleId,
+    pub description: String,
+    /// The value that met the condition: the reading, or its rate of change
+    /// per second.
+    pub value: f32,
+    pub threshold: f32,
+    pub timestamp_ns: u64,
+}
+
+struct Rule {
+    spec: RuleSpec,
+    /// Cleared when the rule fires, set again once the value re-arms it.
+    armed: bool,
+    last_fired_ns: Option<u64>,
+    /// Previous value and timestamp, for rate of change.
+    previous: Option<(f32, u64)>,
+}
+
+impl Rule {
+    fn evaluate(&mut self, id: RuleId, event: &SensorEvent) -> Option<RuleAlert> {
+        let reading = self.spec.signal.value(event);
+        let value = match self.spec.condition {
+            Condition::RateOfChange => {
+                let previous = self.previous.replace((reading, event.timestamp_ns));
+                let (last, last_ns) = previous?;
+                let elapsed_ns = event.timestamp_ns.checked_sub(last_ns).filter(|ns| *ns > 0)?;
+                (reading - last).abs() / (elapsed_ns as f32 / 1e9)
+            }
+            Condition::Above | Condition::Below => reading,
+        };
+
+        let (met, rearm) = self.spec.check(value);
+        if rearm {
+            self.armed = true;
+        }
+        if !met || !self.armed {
+            return None;
+        }
+        let cooldown_ns = self.spec.cooldown_ms.saturating_mul(1_000_000);
+        if let Some(last) = self.last_fired_ns {
+            if event.timestamp_ns.saturating_sub(last) < cooldown_ns {
+                return None;
+            }
+        }
+
+        self.armed = false;
+        self.last_fired_ns = Some(event.timestamp_ns);
+        Some(RuleAlert {
+            rule: id,
+            description: self.spec.describe(),
+            value,
+            threshold: self.spec.threshold,
+            timestamp_ns: event.timestamp_ns,
+        })
+    }
+}
+
+/// Registered rules, evaluated against every delivered sample.
+#[derive(Default)]
+pub struct RuleEngine {
+    rules: BTreeMap<RuleId, Rule>,
+    next_id: u64,
+}
+
+impl RuleEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, spec: RuleSpec) -> Result<RuleId> {
+        spec.validate()?;
+        self.next_id += 1;
+        let id = RuleId(self.next_id);
+        self.rules.insert(
+            id,
+            Rule {
+                spec,
+                armed: true,
+                last_fired_ns: None,
+                previous: None,
+            },
+        );
+        Ok(id)
+    }
+
+    /// Returns `false` if there is no such rule.
+    pub fn remove(&mut self, id: RuleId) -> bool {
+        self.rules.remove(&id).is_some()
+    }
+
+    pub fn list(&self) -> Vec<RuleInfo> {
+        self.rules
+            .iter()
+            .map(|(id, rule)| RuleInfo {
+                id: *id,
+                spec: rule.spec.clone(),
+            })
+            .collect()
+    }
+
+    /// Evaluates the rules for `event`'s sensor.
+    pub fn evaluate(&mut self, event: &SensorEvent) -> Vec<RuleAlert> {
+        self.rules
+            .iter_mut()
+            .filter(|(_, rule)| rule.spec.sensor == event.sensor)
+            .filter_map(|(id, rule)| rule.evaluate(*id, event))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensors::SensorAccuracy;
+
+    fn sample(sensor: SensorKind, z: f32, millis: u64) -> SensorEvent {
+        SensorEvent {
+            sensor,
+            x: 0.0,
+            y: 0.0,
+            z,
+            accuracy: SensorAccuracy::High,
+            timestamp_ns: millis * 1_000_000,
+        }
+    }
+
+    fn rule(condition: Condition, threshold: f32, hysteresis: f32, cooldown_ms: u64) -> RuleSpec {
+        RuleSpec {
+            sensor: SensorKind::Accelerometer,
+            signal: Signal::Z,
+            condition,
+            threshold,
+            hysteresis,
+            cooldown_ms,
+        }
+    }
+
+    /// Feeds `values` 10 ms apart and returns the indices that fired.
+    fn fired(engine: &mut RuleEngine, values: &[f32]) -> Vec<usize> {
+        values
+            .iter()
+            .enumerate()
+            .filter(|(i, z)| !engine.evaluate(&sample(SensorKind::Accelerometer, **z, *i as u64 * 10)).is_empty())
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    #[test]
+    fn hysteresis_suppresses_chatter() {
+        let mut engine = RuleEngine::new();
+        engine.add(rule(Condition::Above, 10.0, 1.0, 0)).unwrap();
+        // Dips to 9.5 do not re-arm; 8.5 does
+        let values = [9.0, 11.0, 9.5, 11.0, 9.5, 11.0, 8.5, 11.0];
+        assert_eq!(fired(&mut engine, &values), vec![1, 7]);
+    }
+
+    #[test]
+    fn below_rearms_above_threshold_plus_hysteresis() {
+        let mut engine = RuleEngine::new();
+        engine.add(rule(Condition::Below, 2.0, 0.5, 0)).unwrap();
+        let values = [3.0, 1.0, 2.2, 1.0, 2.6, 1.0];
+        assert_eq!(fired(&mut engine, &values), vec![1, 5]);
+    }
+
+    #[test]
+    fn cooldown_delays_the_next_alert() {
+        let mut engine = RuleEngine::new();
+        engine.add(rule(Condition::Above, 10.0, 0.0, 35)).unwrap();
+        // Re-armed at 10 ms, but the cooldown lasts until 35 ms
+        let values = [11.0, 9.0, 11.0, 11.0, 9.0, 11.0];
+        assert_eq!(fired(&mut engine, &values), vec![0, 5]);
+    }
+
+    #[test]
+    fn rate_of_change_uses_sample_timestamps() {
+        let mut engine = RuleEngine::new();
+        // 100 units per second; samples are 10 ms apart, so a step over 1.0
+        engine.add(rule(Condition::RateOfChange, 100.0, 0.0, 0)).unwrap();
+        let values = [0.0, 0.5, 2.0, 2.5, 1.0, 1.2];
+        assert_eq!(fired(&mut engine, &values), vec![2, 4]);
+
+        let alert = engine
+            .evaluate(&sample(SensorKind::Accelerometer, 5.0, 60))
+            .pop()
+            .unwrap();
+        assert!((alert.value - 380.0).abs() < 0.1, "rate {}", alert.value);
+        assert_eq!(alert.description, "Accelerometer z rate");
+    }
+
+    #[test]
+    fn rules_only_see_their_sensor() {
+        let mut engine = RuleEngine::new();
+        engine
+            .add(RuleSpec {
+                sensor: SensorKind::Gyroscope,
+                signal: Signal::Magnitude,
+                ..rule(Condition::Above, 1.0, 0.0, 0)
+            })
+            .unwrap();
+        assert!(engine.evaluate(&sample(SensorKind::Accelerometer, 9.8, 0)).is_empty());
+        assert_eq!(engine.evaluate(&sample(SensorKind::Gyroscope, -2.0, 10)).len(), 1);
+    }
+
+    #[test]
+    fn rules_can_be_listed_and_removed() {
+        let mut engine = RuleEngine::new();
+        let first = engine.add(rule(Condition::Above, 10.0, 0.0, 0)).unwrap();
+        let spec: RuleSpec = serde_json::from_str(
+            r#"{"sensor":"magnetometer","condition":"below","threshold":20.0,"cooldown_ms":1000}"#,
+        )
+        .unwrap();
+        let second = engine.add(spec.clone()).unwrap();
+        assert_ne!(first, second);
+
+        assert!(engine.remove(first));
+        assert!(!engine.remove(first));
+        assert_eq!(engine.list(), vec![RuleInfo { id: second, spec }]);
+        let json = serde_json::to_string(&engine.list()).unwrap();
+        assert!(json.starts_with(r#"[{"id":2,"sensor":"magnetometer","signal":"magnitude""#), "{}", json);
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        let mut engine = RuleEngine::new();
+        assert!(engine.add(rule(Condition::Above, f32::NAN, 0.0, 0)).is_err());
+        assert!(engine.add(rule(Condition::Above, 1.0, -1.0, 0)).is_err());
+        assert!(engine.add(rule(Condition::RateOfChange, -1.0, 0.0, 0)).is_err());
+        assert!(engine.add(rule(Condition::Above, 1.0, 0.0, u64::MAX)).is_err());
+        assert!(engine.add(rule(Condition::Above, 1.0, 0.0, MAX_COOLDOWN_MS)).is_ok());
+    }
+}
@@ -0,0 +1,165 @@
+use std::f32::consts::TAU;
+use std::time::{Duration, Instant};
+
+use super::{monotonic_nanos, SensorAccuracy, SensorConfig, SensorEvent, SensorKind, SensorSource};
+use crate::error::Result;
+use crate::tasks::CancellationToken;
+
+/// Amplitude of the pseudo-random noise added to every axis.
+const NOISE: f32 = 0.02;
+
+/// Synthetic signals used on the host and on devices without sensors.
+///
+/// Readings are a pure function of the time since the source started (plus
+/// seeded noise), so tests get the same samples on every run.
+pub struct SimulatedSensors {
+    started: Instant,
+    /// Period and next due time of each enabled sensor, by `SensorKind::index`.
+    schedule: [Option<(Duration, Duration)>; 3],
+    noise: Noise,
+}
+
+impl SimulatedSensors {
+    pub fn new(config: &SensorConfig) -> Self {
+        let mut schedule = [None; 3];
+        for (kind, period) in config.enabled() {
+            schedule[kind.index()] = Some((period, Duration::ZERO));
+        }
+        Self {
+            started: Instant::now(),
+            schedule,
+            noise: Noise::new(0x5eed),
+        }
+    }
+
+    /// Reading of `kind` at `t` after start: a phone lying still on a
+    /// table, gently rocking.
+    pub fn sample(&mut self, kind: SensorKind, t: Duration) -> [f32; 3] {
+        let t = t.as_secs_f32();
+        let [x, y, z] = match kind {
+            SensorKind::Accelerometer => [
+                0.3 * (TAU * 0.5 * t).sin(),
+                0.2 * (TAU * 0.3 * t).cos(),
+                9.81 + 0.1 * (TAU * t).sin(),
+            ],
+            SensorKind::Gyroscope => [
+                0.05 * (TAU * 0.2 * t).sin(),
+                0.03 * (TAU * 0.25 * t).cos(),
+                0.01 * (TAU * 0.1 * t).sin(),
+            ],
+            SensorKind::Magnetometer => [
+                25.0 + 5.0 * (0.1 * t).sin(),
+                30.0 + 3.0 * (0.1 * t).cos(),
+                45.0 + 2.0 * (0.1 * t).sin(),
+            ],
+        };
+        [x + self.noise.next(), y + self.noise.next(), z + self.noise.next()]
+    }
+}
+
+impl SensorSource for SimulatedSensors {
+    fn name(&self) -> &'static str {
+        "simulator"
+    }
+
+    fn poll(&mut self, timeout: Duration, stop: &CancellationToken, events: &mut Vec<SensorEvent>) -> Result<()> {
+        let Some(next_due) = self.schedule.iter().flatten().map(|(_, due)| *due).min() else {
+            stop.sleep(timeout);
+            return Ok(());
+        };
+        let elapsed = self.started.elapsed();
+        if elapsed < next_due {
+            let wait = (next_due - elapsed).min(timeout);
+            if !stop.sleep(wait) || self.started.elapsed() < next_due {
+                return Ok(());
+            }
+        }
+
+        let now = self.started.elapsed();
+        let timestamp_ns = monotonic_nanos();
+        for kind in SensorKind::ALL {
+            let Some((period, due)) = self.schedule[kind.index()] else {
+                continue;
+            };
+            if due > now {
+                continue;
+            }
+            self.schedule[kind.index()] = Some((period, due + period));
+            let [x, y, z] = self.sample(kind, due);
+            events.push(SensorEvent {
+                sensor: kind,
+                x,
+                y,
+                z,
+                accuracy: SensorAccuracy::High,
+                timestamp_ns,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// xorshift32, scaled to `[-NOISE, NOISE]`.
+struct Noise(u32);
+
+impl Noise {
+    fn new(seed: u32) -> Self {
+        Self(seed.max(1))
+    }
+
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        (self.0 as f32 / u32::MAX as f32 * 2.0 - 1.0) * NOISE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_are_deterministic() {
+        let t = Duration::from_millis(1234);
+        let first = SimulatedSensors::new(&SensorConfig::all(10)).sample(SensorKind::Gyroscope, t);
+        let second = SimulatedSensors::new(&SensorConfig::all(10)).sample(SensorKind::Gyroscope, t);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn accelerometer_at_rest_reads_gravity() {
+        let mut simulator = SimulatedSensors::new(&SensorConfig::all(10));
+        let [x, y, z] = simulator.sample(SensorKind::Accelerometer, Duration::ZERO);
+        let magnitude = (x * x + y * y + z * z).sqrt();
+        assert!((magnitude - 9.81).abs() < 0.5, "magnitude {}", magnitude);
+    }
+
+    #[test]
+    fn poll_reports_every_sensor_once_per_period() {
+        let mut simulator = SimulatedSensors::new(&SensorConfig::all(5));
+        let mut events = Vec::new();
+        while events.len() < 6 {
+            simulator.poll(Duration::from_millis(50), &CancellationToken::default(), &mut events).unwrap();
+        }
+        let kinds: Vec<SensorKind> = events.iter().map(|event| event.sensor).collect();
+        assert_eq!(kinds[..3], SensorKind::ALL);
+        assert_eq!(kinds[3..], SensorKind::ALL);
+    }
+
+    #[test]
+    fn poll_honours_per_sensor_periods() {
+        let mut config = SensorConfig::all(4);
+        config.gyroscope.enabled = false;
+        config.magnetometer.period_ms = 8;
+        let mut simulator = SimulatedSensors::new(&config);
+
+        let mut events: Vec<SensorEvent> = Vec::new();
+        while events.iter().filter(|event| event.sensor == SensorKind::Accelerometer).count() < 4 {
+            simulator.poll(Duration::from_millis(50), &CancellationToken::default(), &mut events).unwrap();
+        }
+        let count = |kind| events.iter().filter(|event| event.sensor == kind).count();
+        assert_eq!(count(SensorKind::Gyroscope), 0);
+        assert_eq!(count(SensorKind::Magnetometer), 2);
+    }
+}
@@ -0,0 +1,456 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::{panic_message, Error, Result};
+use crate::events::{Event, EventSender};
+use crate::pool::{Job, Priority, WorkerPool};
+use crate::threads;
+
+/// Finished tasks kept around so Kotlin can still query their final state.
+const MAX_FINISHED_TASKS: usize = 64;
+/// Upper bound on how long `CancellationToken::sleep` goes without
+/// checking for a crate shutdown.
+const SHUTDOWN_POLL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TaskId(pub u64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskState {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl TaskState {
+    pub fn is_finished(self) -> bool {
+        matches!(self, TaskState::Succeeded | TaskState::Failed | TaskState::Cancelled)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TaskState::Queued => "queued",
+            TaskState::Running => "running",
+            TaskState::Succeeded => "succeeded",
+            TaskState::Failed => "failed",
+            TaskState::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// Cooperative cancellation flag shared between a task and `TaskManager`.
+/// Tasks are expected to poll it or wait with `sleep`.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl CancellationToken {
+    pub fn cancel(&self) {
+        let (cancelled, signal) = &*self.inner;
+        *cancelled.lock().unwrap() = true;
+        signal.notify_all();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.inner.0.lock().unwrap() || threads::is_shutting_down()
+    }
+
+    /// Sleeps for `duration`, waking early on cancellation or shutdown.
+    /// Returns `false` if the task should stop.
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        let (cancelled, signal) = &*self.inner;
+        let mut guard = cancelled.lock().unwrap();
+
+        loop {
+            if *guard || threads::is_shutting_down() {
+                return false;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return true;
+            }
+            let wait = (deadline - now).min(SHUTDOWN_POLL);
+            guard = signal.wait_timeout(guard, wait).unwrap().0;
+        }
+    }
+}
+
+/// Handed to every task body: identifies the task, carries its
+/// cancellation token and reports progress to Kotlin.
+#[derive(Clone)]
+pub struct TaskContext {
+    id: TaskId,
+    name: String,
+    token: CancellationToken,
+    tasks: Arc<Mutex<HashMap<TaskId, TaskEntry>>>,
+    events: EventSender,
+}
+
+impl TaskContext {
+    pub fn id(&self) -> TaskId {
+        self.id
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// See `CancellationToken::sleep`.
+    pub fn sleep(&self, duration: Duration) -> bool {
+        self.token.sleep(duration)
+    }
+
+    /// For cancelling the task from outside its body, e.g. from a
+    /// notification button.
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    /// Returns `Error::Cancelled` if the task was asked to stop.
+    pub fn check_cancelled(&self) -> Result<()> {
+        if self.is_cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn progress(&self, percent: u8, message: impl Into<String>) {
+        let percent = percent.min(100);
+        if let Some(entry) = self.tasks.lock().unwrap().get_mut(&self.id) {
+            entry.progress = percent;
+        }
+        self.events.send(Event::TaskProgress {
+            task_id: self.id,
+            name: self.name.clone(),
+            percent,
+            message: message.into(),
+        });
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskInfo {
+    pub id: TaskId,
+    pub name: String,
+    pub state: TaskState,
+    pub progress: u8,
+}
+
+struct TaskEntry {
+    name: String,
+    state: TaskState,
+    progress: u8,
+    token: CancellationToken,
+}
+
+/// Starts background tasks and tracks them until they finish.
+///
+/// Every state change is reported as `Event::TaskState`; tasks report
+/// progress through `TaskContext::progress`.
+pub struct TaskManager {
+    tasks: Arc<Mutex<HashMap<TaskId, TaskEntry>>>,
+    next_id: AtomicU64,
+    events: EventSender,
+    pool: Arc<WorkerPool>,
+}
+
+impl TaskManager {
+    pub fn new(events: EventSender, pool: Arc<WorkerPool>) -> Self {
+        Self {
+            tasks: Arc::new(Mutex::new(HashMap::new())),
+            next_id: AtomicU64::new(1),
+            events,
+            pool,
+        }
+    }
+
+    /// Queues `task` on the worker pool in the `priority` lane. The value
+    /// it returns becomes the result of the `succeeded` state; an error
+    /// marks the task `failed`, or `cancelled` if it was cancelled, and so
+    /// does a panic. Fails with `Error::Rejected` if the pool queue is
+    /// full, after reporting the task `failed`; under
+    /// `RejectionPolicy::CallerRuns` the task runs on the calling thread
+    /// instead and this returns once it finished.
+    pub fn spawn<F>(&self, name: &str, priority: Priority, task: F) -> Result<TaskId>
+    where
+        F: FnOnce(&TaskContext) -> Result<String> + Send + 'static,
+    {
+        let id = TaskId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        let token = CancellationToken::default();
+        self.tasks.lock().unwrap().insert(
+            id,
+            TaskEntry {
+                name: name.to_string(),
+                state: TaskState::Queued,
+                progress: 0,
+                token: token.clone(),
+            },
+        );
+        let context = TaskContext {
+            id,
+            name: name.to_string(),
+            token,
+            tasks: self.tasks.clone(),
+            events: self.events.clone(),
+        };
+        set_state(&context, TaskState::Queued, None);
+
+        let rejected = context.clone();
+        let dropped = context.clone();
+        let job = Job::new(move || run_task(context, task)).on_dropped(move || {
+            log::warn!("Task {} ({}) dropped from the worker queue", dropped.id.0, dropped.name);
+            set_state(&dropped, TaskState::Failed, Some("dropped from the worker queue".to_string()));
+        });
+        if let Err(e) = self.pool.submit(priority, job) {
+            // Kotlin already saw the task queued, so it needs to see it end
+            log::warn!("Task {} ({}) rejected: {}", id.0, name, e);
+            set_state(&rejected, TaskState::Failed, Some(e.to_string()));
+            return Err(e);
+        }
+        Ok(id)
+    }
+
+    /// Requests cancellation. Returns `false` for unknown or finished tasks.
+    pub fn cancel(&self, id: TaskId) -> bool {
+        match self.tasks.lock().unwrap().get(&id) {
+            Some(entry) if !entry.state.is_finished() => {
+                entry.token.cancel();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn info(&self, id: TaskId) -> Option<TaskInfo> {
+        self.tasks.lock().unwrap().get(&id).map(|entry| TaskInfo {
+            id,
+            name: entry.name.clone(),
+            state: entry.state,
+            progress: entry.progress,
+        })
+    }
+
+    pub fn list(&self) -> Vec<TaskInfo> {
+        let tasks = self.tasks.lock().unwrap();
+        let mut infos: Vec<TaskInfo> = tasks
+            .iter()
+            .map(|(id, entry)| TaskInfo {
+                id: *id,
+                name: entry.name.clone(),
+                state: entry.state,
+                progress: entry.progress,
+            })
+            .collect();
+        infos.sort_by_key(|info| info.id);
+        infos
+    }
+}
+
+fn run_task<F>(context: TaskContext, task: F)
+where
+    F: FnOnce(&TaskContext) -> Result<String>,
+{
+    if context.is_cancelled() {
+        set_state(&context, TaskState::Cancelled, None);
+        return;
+    }
+    set_state(&context, TaskState::Running, None);
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| task(&context)))
+        .unwrap_or_else(|payload| Err(Error::Panic(panic_message(payload))));
+    match result {
+        Ok(result) => set_state(&context, TaskState::Succeeded, Some(result)),
+        Err(_) if context.is_cancelled() => set_state(&context, TaskState::Cancelled, None),
+        Err(Error::Cancelled) => set_state(&context, TaskState::Cancelled, None),
+        Err(e) => {
+            log::error!("Task {} ({}) failed: {}", context.id.0, context.name, e);
+            set_state(&context, TaskState::Failed, Some(e.to_string()));
+        }
+    }
+}
+
+fn set_state(context: &TaskContext, state: TaskState, message: Option<String>) {
+    {
+        let mut tasks = context.tasks.lock().unwrap();
+        if let Some(entry) = tasks.get_mut(&context.id) {
+            entry.state = state;
+            if state == TaskState::Succeeded {
+                entry.progress = 100;
+            }
+        }
+        if state.is_finished() {
+            prune_finished(&mut tasks);
+        }
+    }
+
+    context.events.send(Event::TaskState {
+        task_id: context.id,
+        name: context.name.clone(),
+        state,
+        message,
+    });
+}
+
+fn prune_finished(tasks: &mut HashMap<TaskId, TaskEntry>) {
+    let mut finished: Vec<TaskId> = tasks
+        .iter()
+        .filter(|(_, entry)| entry.state.is_finished())
+        .map(|(id, _)| *id)
+        .collect();
+    if finished.len() <= MAX_FINISHED_TASKS {
+        return;
+    }
+    // Ids grow monotonically, so this drops the oldest tasks first
+    finished.sort();
+    for id in &finished[..finished.len() - MAX_FINISHED_TASKS] {
+        tasks.remove(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events;
+    use crate::pool::{PoolConfig, RejectionPolicy};
+    use std::sync::mpsc::Receiver;
+
+    fn manager(queue_capacity: usize) -> (TaskManager, Receiver<Event>) {
+        let (sender, receiver) = events::channel();
+        let pool = WorkerPool::start(PoolConfig {
+            workers: 1,
+            queue_capacity,
+            rejection_policy: RejectionPolicy::Reject,
+        })
+        .unwrap();
+        (TaskManager::new(sender, Arc::new(pool)), receiver)
+    }
+
+    /// Task state events for `id` until it finished, with their messages.
+    fn states(receiver: &Receiver<Event>, id: TaskId) -> Vec<(TaskState, Option<String>)> {
+        let mut states = Vec::new();
+        while states.last().is_none_or(|(state, _): &(TaskState, _)| !state.is_finished()) {
+            match receiver.recv_timeout(Duration::from_secs(5)).expect("task did not finish") {
+                Event::TaskState {
+                    task_id, state, message, ..
+                } if task_id == id => states.push((state, message)),
+                _ => {}
+            }
+        }
+        states
+    }
+
+    #[test]
+    fn reports_every_transition() {
+        let (tasks, receiver) = manager(8);
+        let id = tasks.spawn("ok", Priority::Normal, |_| Ok("done".to_string())).unwrap();
+        assert_eq!(
+            states(&receiver, id),
+            vec![
+                (TaskState::Queued, None),
+                (TaskState::Running, None),
+                (TaskState::Succeeded, Some("done".to_string())),
+            ]
+        );
+        let info = tasks.info(id).unwrap();
+        assert_eq!((info.state, info.progress), (TaskState::Succeeded, 100));
+
+        let id = tasks
+            .spawn("err", Priority::Normal, |_| Err(Error::InvalidArgument("bad".to_string())))
+            .unwrap();
+        let last = states(&receiver, id).pop().unwrap();
+        assert_eq!(last, (TaskState::Failed, Some("Invalid argument: bad".to_string())));
+    }
+
+    #[test]
+    fn panicking_tasks_fail() {
+        let (tasks, receiver) = manager(8);
+        let id = tasks.spawn("panic", Priority::Normal, |_| panic!("boom")).unwrap();
+        let (state, message) = states(&receiver, id).pop().unwrap();
+        assert_eq!(state, TaskState::Failed);
+        assert!(message.unwrap().contains("boom"));
+        assert_eq!(tasks.info(id).unwrap().state, TaskState::Failed);
+    }
+
+    #[test]
+    fn cancellation_stops_a_sleeping_task() {
+        let (tasks, receiver) = manager(8);
+        let id = tasks
+            .spawn("sleeper", Priority::Normal, |context| {
+                context.progress(10, "sleeping");
+                if context.sleep(Duration::from_secs(30)) {
+                    Ok("woke up".to_string())
+                } else {
+                    Err(Error::Cancelled)
+                }
+            })
+            .unwrap();
+        // Wait for the task to be running before cancelling it
+        loop {
+            match receiver.recv_timeout(Duration::from_secs(5)).unwrap() {
+                Event::TaskProgress { task_id, percent, message, .. } => {
+                    assert_eq!((task_id, percent, message.as_str()), (id, 10, "sleeping"));
+                    break;
+                }
+                _ => continue,
+            }
+        }
+        assert_eq!(tasks.info(id).unwrap().progress, 10);
+        assert!(tasks.cancel(id));
+        assert_eq!(states(&receiver, id).pop().unwrap(), (TaskState::Cancelled, None));
+        assert!(!tasks.cancel(id));
+        assert!(!tasks.cancel(TaskId(999)));
+    }
+
+    #[test]
+    fn rejected_tasks_are_reported_failed() {
+        let (tasks, receiver) = manager(1);
+        let (release, released) = std::sync::mpsc::channel::<()>();
+        let blocker = tasks
+            .spawn("blocker", Priority::Normal, move |_| {
+                let _ = released.recv();
+                Ok(String::new())
+            })
+            .unwrap();
+        // Wait until the worker picked it up so the queue is empty again
+        while tasks.info(blocker).unwrap().state != TaskState::Running {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        tasks.spawn("queued", Priority::Normal, |_| Ok(String::new())).unwrap();
+        assert!(matches!(
+            tasks.spawn("rejected", Priority::Normal, |_| Ok(String::new())),
+            Err(Error::Rejected(_))
+        ));
+        let rejected = TaskId(3);
+        let states = states(&receiver, rejected);
+        assert_eq!(states[0], (TaskState::Queued, None));
+        assert_eq!(states.last().unwrap().0, TaskState::Failed);
+        assert_eq!(tasks.info(rejected).unwrap().state, TaskState::Failed);
+        drop(release);
+    }
+
+    #[test]
+    fn prunes_the_oldest_finished_tasks() {
+        let (tasks, receiver) = manager(8);
+        let count = MAX_FINISHED_TASKS as u64 + 5;
+        for _ in 0..count {
+            let id = tasks.spawn("short", Priority::Normal, |_| Ok(String::new())).unwrap();
+            states(&receiver, id);
+        }
+        let list = tasks.list();
+        assert_eq!(list.len(), MAX_FINISHED_TASKS);
+        assert_eq!(list[0].id, TaskId(6));
+        assert_eq!(list.last().unwrap().id, TaskId(count));
+        assert!(tasks.info(TaskId(1)).is_none());
+    }
+}
@@ -0,0 +1,111 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle, ThreadId};
+use std::time::{Duration, Instant};
+
+/// Every thread the crate starts goes through `spawn` so `JNI_OnUnload` can
+/// stop and join them. Long-running loops are expected to wait with `sleep`
+/// and return once it reports a shutdown.
+static THREADS: Mutex<Vec<JoinHandle<()>>> = Mutex::new(Vec::new());
+static SHUTDOWN: Mutex<bool> = Mutex::new(false);
+static SHUTDOWN_SIGNAL: Condvar = Condvar::new();
+
+/// Lets the owner of a thread wait for it. The `JoinHandle` itself stays
+/// in the registry so `shutdown` can still join every thread.
+#[derive(Clone)]
+pub struct ThreadHandle {
+    id: ThreadId,
+    finished: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl ThreadHandle {
+    pub fn is_finished(&self) -> bool {
+        *self.finished.0.lock().unwrap()
+    }
+
+    /// Blocks until the thread returned or panicked. Returns immediately
+    /// when called from the thread itself.
+    pub fn join(&self) {
+        if thread::current().id() == self.id {
+            return;
+        }
+        let (finished, signal) = &*self.finished;
+        let mut finished = finished.lock().unwrap();
+        while !*finished {
+            finished = signal.wait(finished).unwrap();
+        }
+    }
+}
+
+/// Marks a `ThreadHandle` finished when the thread body ends, even by
+/// unwinding.
+struct FinishGuard(Arc<(Mutex<bool>, Condvar)>);
+
+impl Drop for FinishGuard {
+    fn drop(&mut self) {
+        let (finished, signal) = &*self.0;
+        *finished.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        signal.notify_all();
+    }
+}
+
+/// Spawns a named, tracked thread.
+pub fn spawn<F>(name: &str, f: F) -> std::io::Result<ThreadHandle>
+where
+    F: FnOnce() + Send + 'static,
+{
+    let finished = Arc::new((Mutex::new(false), Condvar::new()));
+    let guard = FinishGuard(finished.clone());
+    let handle = thread::Builder::new().name(name.to_string()).spawn(move || {
+        let _guard = guard;
+        f();
+    })?;
+    let thread_handle = ThreadHandle {
+        id: handle.thread().id(),
+        finished,
+    };
+
+    let mut threads = THREADS.lock().unwrap();
+    // Drop handles of threads that already finished so the list stays short
+    threads.retain(|handle| !handle.is_finished());
+    threads.push(handle);
+    Ok(thread_handle)
+}
+
+pub fn is_shutting_down() -> bool {
+    *SHUTDOWN.lock().unwrap()
+}
+
+/// Sleeps for `duration` unless a shutdown is requested first. Returns
+/// `false` if the caller should stop.
+pub fn sleep(duration: Duration) -> bool {
+    let deadline = Instant::now() + duration;
+    let mut shutdown = SHUTDOWN.lock().unwrap();
+
+    while !*shutdown {
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        shutdown = SHUTDOWN_SIGNAL.wait_timeout(shutdown, deadline - now).unwrap().0;
+    }
+    false
+}
+
+/// Asks every tracked thread to stop and waits for them. Threads spawned
+/// after this point see the shutdown immediately.
+pub fn shutdown() {
+    *SHUTDOWN.lock().unwrap() = true;
+    SHUTDOWN_SIGNAL.notify_all();
+
+    let handles: Vec<_> = THREADS.lock().unwrap().drain(..).collect();
+    let current = thread::current().id();
+    for handle in handles {
+        if handle.thread().id() == current {
+            continue;
+        }
+        let name = handle.thread().name().unwrap_or("unnamed").to_string();
+        if handle.join().is_err() {
+            log::error!("Thread {} panicked during shutdown", name);
+        }
+    }
+}